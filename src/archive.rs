@@ -0,0 +1,416 @@
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::errors::Error;
+use crate::utils;
+
+/// Extensions of files which are compressed already, so deflating them only wastes time.
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "avif", "woff", "woff2", "mp3", "mp4", "ogg", "webm",
+    "gz", "zip",
+];
+
+/// Package document generated by epub-builder, which only supports some metadata.
+const PACKAGE_FILE: &str = "OEBPS/content.opf";
+
+/// The EPUB's zip archive like epub-builder's `ZipLibrary`, with a configurable compression
+/// level: `0` stores all files, `1` (fastest) to `9` (smallest) deflates them, except files
+/// which are compressed already, e.g. JPEG images, which are stored.
+///
+/// epub-builder's archive can't be replaced, so the EPUB it generates is repacked into it.
+#[derive(Debug)]
+pub(crate) struct ZipArchive {
+    compression: Option<u8>,
+    /// Navigation documents written instead of epub-builder's ones, by their paths.
+    navigation: Vec<(String, String)>,
+    /// Metadata elements added to the package document, which epub-builder can't write.
+    metadata: String,
+    /// Documents moved to the start of the package document's spine, in this order.
+    spine_order: Vec<String>,
+    /// Documents containing MathML, which their manifest items declare.
+    mathml_documents: Vec<String>,
+}
+
+impl ZipArchive {
+    /// Archive with the compression level, the zip library's default if it's `None`.
+    pub(crate) fn new(compression: Option<u8>) -> Result<Self, Error> {
+        if let Some(level) = compression.filter(|level| *level > 9) {
+            return Err(Error::InvalidConfig(format!(
+                "compression must be in 0-9, got {level}"
+            )));
+        }
+        Ok(ZipArchive {
+            compression,
+            navigation: Vec::new(),
+            metadata: String::new(),
+            spine_order: Vec::new(),
+            mathml_documents: Vec::new(),
+        })
+    }
+
+    /// Write the navigation documents, e.g. `OEBPS/nav.xhtml`, instead of the ones
+    /// generated by epub-builder.
+    pub(crate) fn with_navigation(mut self, navigation: Vec<(String, String)>) -> Self {
+        self.navigation = navigation;
+        self
+    }
+
+    /// Add the metadata elements, e.g. `<dc:source>`, at the end of the package document's
+    /// `<metadata>`.
+    pub(crate) fn with_metadata(mut self, metadata: String) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Move the documents with the paths to the start of the spine, epub-builder puts them
+    /// in the order they're added, like their TOC entries.
+    pub(crate) fn with_spine_order(mut self, spine_order: Vec<String>) -> Self {
+        self.spine_order = spine_order;
+        self
+    }
+
+    /// Add the `mathml` property to the manifest items of the documents with the paths,
+    /// which EPUB 3 requires and epub-builder can't write.
+    pub(crate) fn with_mathml_documents(mut self, mathml_documents: Vec<String>) -> Self {
+        self.mathml_documents = mathml_documents;
+        self
+    }
+
+    fn file_options(&self, path: &Path) -> FileOptions {
+        let compressed = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| COMPRESSED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        match self.compression {
+            None => FileOptions::default(),
+            Some(0) => FileOptions::default().compression_method(CompressionMethod::Stored),
+            Some(_) if compressed => {
+                FileOptions::default().compression_method(CompressionMethod::Stored)
+            }
+            Some(level) => FileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .compression_level(Some(i32::from(level))),
+        }
+    }
+}
+
+impl ZipArchive {
+    /// Repack the files of the EPUB generated by epub-builder from the `source` into the
+    /// archive, which is written `to` the output, returning the archive's size.
+    ///
+    /// Files which are left as they are keep their compressed data with the default
+    /// compression, as epub-builder deflates them the same way.
+    pub(crate) fn repack<R: Read + Seek, W: Write + Seek>(
+        self,
+        source: R,
+        to: W,
+    ) -> Result<u64, Error> {
+        let mut generated = zip::ZipArchive::new(source)?;
+        let mut writer = ZipWriter::new(to);
+        // the 'mimetype' file must be the first one and not compressed
+        writer.start_file(
+            "mimetype",
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        writer.write_all(b"application/epub+zip")?;
+        for index in 0..generated.len() {
+            let file = generated.by_index(index)?;
+            let name = file.name().to_string();
+            if name == "mimetype" {
+                continue;
+            }
+            if self.compression.is_none() && !self.rewrites(&name) {
+                writer.raw_copy_file(file)?;
+            } else {
+                self.write_file(&mut writer, &name, file)?;
+            }
+        }
+        let mut to = writer.finish()?;
+        Ok(to.stream_position()?)
+    }
+
+    // Whether the file's content is changed when it's repacked
+    fn rewrites(&self, file: &str) -> bool {
+        file == PACKAGE_FILE || self.navigation.iter().any(|(path, _)| path == file)
+    }
+
+    fn write_file<W: Write + Seek, R: Read>(
+        &self,
+        writer: &mut ZipWriter<W>,
+        file: &str,
+        mut content: R,
+    ) -> Result<(), Error> {
+        writer.start_file(file, self.file_options(Path::new(file)))?;
+        if file == PACKAGE_FILE {
+            let mut opf = String::new();
+            content.read_to_string(&mut opf)?;
+            if !self.metadata.is_empty() {
+                opf = add_metadata(&opf, &self.metadata);
+            }
+            if !self.spine_order.is_empty() {
+                opf = reorder_spine(&opf, &self.spine_order);
+            }
+            if !self.mathml_documents.is_empty() {
+                opf = add_mathml_property(&opf, &self.mathml_documents);
+            }
+            writer.write_all(opf.as_bytes())?;
+        } else if let Some((_, nav)) = self.navigation.iter().find(|(path, _)| path == file) {
+            writer.write_all(nav.as_bytes())?;
+        } else {
+            io::copy(&mut content, writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Insert the metadata elements before the end of the package document's `<metadata>`.
+fn add_metadata(opf: &str, metadata: &str) -> String {
+    match opf.find("</metadata>") {
+        Some(end) => format!("{}{}\n{}", &opf[..end], metadata, &opf[end..]),
+        None => {
+            warn!("The package document has no <metadata>, the book's metadata is left out");
+            opf.to_string()
+        }
+    }
+}
+
+/// Move the `<itemref>`s of the documents with the paths to the start of the package
+/// document's spine, the others keep their order. The whitespace between them is kept.
+fn reorder_spine(opf: &str, paths: &[String]) -> String {
+    let (Some(start), Some(end)) = (opf.find("<spine"), opf.find("</spine>")) else {
+        warn!("The package document has no <spine>, the spine order is left out");
+        return opf.to_string();
+    };
+    // the ids of the documents' items in the manifest
+    let ids: Vec<&str> = paths
+        .iter()
+        .filter_map(|path| {
+            let href = utils::escape_html(&path.replace('\\', "/"));
+            opf[..start]
+                .split("<item ")
+                .skip(1)
+                .find(|item| attribute(item, "href") == Some(href.as_str()))
+                .and_then(|item| attribute(item, "id"))
+        })
+        .collect();
+    let spine = &opf[start..end];
+    let itemrefs: Vec<&str> = spine
+        .match_indices("<itemref")
+        .filter_map(|(i, _)| spine[i..].find("/>").map(|len| &spine[i..i + len + 2]))
+        .collect();
+    let listed = |itemref: &str| attribute(itemref, "idref").is_some_and(|id| ids.contains(&id));
+    let mut ordered: Vec<&str> = ids
+        .iter()
+        .filter_map(|id| {
+            itemrefs
+                .iter()
+                .find(|itemref| attribute(itemref, "idref") == Some(*id))
+        })
+        .copied()
+        .collect();
+    ordered.extend(itemrefs.iter().filter(|itemref| !listed(itemref)));
+
+    let mut result = opf[..start].to_string();
+    let mut rest = spine;
+    for itemref in ordered {
+        let Some(i) = rest.find("<itemref") else {
+            break;
+        };
+        let len = rest[i..].find("/>").map_or(rest.len() - i, |len| len + 2);
+        result.push_str(&rest[..i]);
+        result.push_str(itemref);
+        rest = &rest[i + len..];
+    }
+    result.push_str(rest);
+    result.push_str(&opf[end..]);
+    result
+}
+
+/// Add the `mathml` property to the manifest `<item>`s of the documents with the paths,
+/// keeping the properties they have.
+fn add_mathml_property(opf: &str, paths: &[String]) -> String {
+    let hrefs: Vec<String> = paths
+        .iter()
+        .map(|path| utils::escape_html(&path.replace('\\', "/")))
+        .collect();
+    let mut result = String::with_capacity(opf.len() + paths.len() * 20);
+    let mut rest = opf;
+    while let Some(start) = rest.find("<item ") {
+        result.push_str(&rest[..start]);
+        let item = &rest[start..];
+        let end = item.find('>').map_or(item.len(), |end| end + 1);
+        let element = &item[..end];
+        let listed = attribute(element, "href").is_some_and(|href| hrefs.iter().any(|h| h == href));
+        match attribute(element, "properties") {
+            _ if !listed => result.push_str(element),
+            Some(properties) => result.push_str(&element.replacen(
+                &format!(" properties=\"{properties}\""),
+                &format!(" properties=\"{properties} mathml\""),
+                1,
+            )),
+            None => {
+                result.push_str("<item properties=\"mathml\" ");
+                result.push_str(&element["<item ".len()..]);
+            }
+        }
+        rest = &item[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+// Value of the element's attribute, as it's written
+fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let element = &element[..element.find('>').unwrap_or(element.len())];
+    let start = element.find(&format!(" {name}=\""))? + name.len() + 3;
+    element[start..].split('"').next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Repack an EPUB with the files, deflated like epub-builder does
+    fn repack(archive: ZipArchive, files: &[(&str, &str)]) -> zip::ZipArchive<Cursor<Vec<u8>>> {
+        let mut source = ZipWriter::new(Cursor::new(Vec::new()));
+        for (file, content) in files {
+            source.start_file(*file, FileOptions::default()).unwrap();
+            source.write_all(content.as_bytes()).unwrap();
+        }
+        let source = source.finish().unwrap();
+        let mut bytes = Cursor::new(Vec::new());
+        let size = archive.repack(source, &mut bytes).unwrap();
+        assert_eq!(size, bytes.get_ref().len() as u64);
+        let mut zip = zip::ZipArchive::new(bytes).unwrap();
+        assert_eq!(zip.by_index(0).unwrap().name(), "mimetype");
+        zip
+    }
+
+    fn read_file(zip: &mut zip::ZipArchive<Cursor<Vec<u8>>>, file: &str) -> String {
+        let mut content = String::new();
+        zip.by_name(file)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    fn compression_of(compression: Option<u8>, file: &str) -> CompressionMethod {
+        let archive = ZipArchive::new(compression).unwrap();
+        let mut zip = repack(archive, &[(file, &"x".repeat(1000))]);
+        let method = zip.by_name(file).unwrap().compression();
+        method
+    }
+
+    #[test]
+    fn compression_level_by_config_and_file_type() {
+        assert_eq!(
+            compression_of(None, "OEBPS/ch.html"),
+            CompressionMethod::Deflated
+        );
+        assert_eq!(
+            compression_of(Some(9), "OEBPS/ch.html"),
+            CompressionMethod::Deflated
+        );
+        assert_eq!(
+            compression_of(Some(0), "OEBPS/ch.html"),
+            CompressionMethod::Stored
+        );
+        assert_eq!(
+            compression_of(Some(9), "OEBPS/photo.JPG"),
+            CompressionMethod::Stored
+        );
+        // the zip library's default is kept
+        assert_eq!(
+            compression_of(None, "OEBPS/photo.jpeg"),
+            CompressionMethod::Deflated
+        );
+        assert!(matches!(
+            ZipArchive::new(Some(10)),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn navigation_documents_replaced() {
+        let archive = ZipArchive::new(None)
+            .unwrap()
+            .with_navigation(vec![("OEBPS/nav.xhtml".to_string(), "<nav/>".to_string())]);
+        let mut zip = repack(
+            archive,
+            &[
+                ("OEBPS/nav.xhtml", "<nav>epub-builder</nav>"),
+                ("OEBPS/toc.xhtml", "<nav>epub-builder</nav>"),
+            ],
+        );
+        assert_eq!(read_file(&mut zip, "OEBPS/nav.xhtml"), "<nav/>");
+        // other files are left as they are
+        assert_eq!(
+            read_file(&mut zip, "OEBPS/toc.xhtml"),
+            "<nav>epub-builder</nav>"
+        );
+    }
+
+    #[test]
+    fn metadata_added_to_package_document() {
+        let opf = "<package>\n<metadata>\n<dc:title>Book</dc:title>\n</metadata>\n</package>";
+        let archive = ZipArchive::new(None)
+            .unwrap()
+            .with_metadata("<dc:source>https://example.com</dc:source>".to_string());
+        let mut zip = repack(archive, &[(PACKAGE_FILE, opf)]);
+        assert_eq!(
+            read_file(&mut zip, PACKAGE_FILE),
+            "<package>\n<metadata>\n<dc:title>Book</dc:title>\n\
+            <dc:source>https://example.com</dc:source>\n</metadata>\n</package>"
+        );
+        assert_eq!(add_metadata("<package/>", "<dc:source/>"), "<package/>");
+    }
+
+    #[test]
+    fn documents_moved_to_start_of_spine() {
+        let opf = "<package>\n<manifest>\n\
+            <item media-type=\"application/xhtml+xml\" id=\"a_html\" href=\"a.html\"/>\n\
+            <item media-type=\"application/xhtml+xml\" id=\"b_html\" href=\"b.html\"/>\n\
+            <item media-type=\"application/xhtml+xml\" id=\"c_d_html\" href=\"c/d.html\"/>\n\
+            </manifest>\n<spine toc=\"ncx\">\n    <itemref idref=\"a_html\"/>\n    \
+            <itemref idref=\"b_html\"/>\n    <itemref idref=\"c_d_html\"/>\n</spine>\n</package>";
+        let archive = ZipArchive::new(None)
+            .unwrap()
+            .with_spine_order(vec!["c/d.html".to_string(), "b.html".to_string()]);
+        let mut zip = repack(archive, &[(PACKAGE_FILE, opf)]);
+        assert_eq!(
+            read_file(&mut zip, PACKAGE_FILE),
+            opf.replace(
+                "\"a_html\"/>\n    <itemref idref=\"b_html\"/>\n    <itemref idref=\"c_d_html\"",
+                "\"c_d_html\"/>\n    <itemref idref=\"b_html\"/>\n    <itemref idref=\"a_html\""
+            )
+        );
+        // unknown documents are skipped
+        assert_eq!(reorder_spine(opf, &["e.html".to_string()]), opf);
+    }
+
+    #[test]
+    fn mathml_property_added_to_manifest_items() {
+        let opf = "<package>\n<manifest>\n\
+            <item media-type=\"application/xhtml+xml\" id=\"a_html\" href=\"a.html\"/>\n\
+            <item media-type=\"application/xhtml+xml\" id=\"b_html\" href=\"b.html\"/>\n\
+            <item media-type=\"application/xhtml+xml\" properties=\"svg\" id=\"c_html\" href=\"c.html\"/>\n\
+            </manifest>\n<spine toc=\"ncx\">\n    <itemref idref=\"a_html\"/>\n</spine>\n</package>";
+        let archive = ZipArchive::new(None)
+            .unwrap()
+            .with_mathml_documents(vec!["b.html".to_string(), "c.html".to_string()]);
+        let mut zip = repack(archive, &[(PACKAGE_FILE, opf)]);
+        assert_eq!(
+            read_file(&mut zip, PACKAGE_FILE),
+            opf.replace(
+                "<item media-type=\"application/xhtml+xml\" id=\"b_html\"",
+                "<item properties=\"mathml\" media-type=\"application/xhtml+xml\" id=\"b_html\""
+            )
+            .replace("properties=\"svg\"", "properties=\"svg mathml\"")
+        );
+    }
+}
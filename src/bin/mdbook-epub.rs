@@ -1,24 +1,28 @@
 #[macro_use]
 extern crate log;
 
-use std::io;
-use std::path::PathBuf;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Instant;
 
 use ::env_logger;
 use ::mdbook;
-use ::serde_json;
 use clap::Parser;
+use mdbook::config::Config as MdConfig;
 use mdbook::renderer::RenderContext;
-use mdbook::MDBook;
 
 use ::mdbook_epub;
 use mdbook_epub::errors::Error;
+use mdbook_epub::{CheckMessage, Diagnostics, Progress};
 
 fn main() {
-    env_logger::init();
-    info!("Booting EPUB generator...");
     let args = Args::parse();
+    let filters = log_filters(&env::var("RUST_LOG").unwrap_or_default(), args.verbose);
+    let _ = mdbook_epub::init_logging_with(&filters, env_logger::Target::Stderr);
+    info!("Booting EPUB generator...");
     debug!("prepared generator args = {:?}", args);
 
     if let Err(e) = run(&args) {
@@ -30,28 +34,74 @@ fn main() {
 
 fn run(args: &Args) -> Result<(), Error> {
     debug!("run EPUB book build...");
-    // get a `RenderContext`, either from stdin (because it's used as a plugin)
-    // or by instrumenting MDBook directly
-    let ctx: RenderContext = if args.standalone {
+    // get a `RenderContext`, either from stdin (because it's used as a plugin),
+    // from a previously captured JSON file or by instrumenting MDBook directly
+    let mut ctx: RenderContext = if let Some(ref context) = args.context {
+        println!(
+            "Running mdbook-epub with the render context from '{}'...",
+            context.display()
+        );
+        mdbook_epub::parse_render_context(BufReader::new(File::open(context)?))?
+    } else if args.standalone {
         println!("Running mdbook-epub as standalone app...");
         let error = format!(
             "book.toml root file is not found by a path {:?}",
             &args.root.display()
         );
-        let md = MDBook::load(&args.root).expect(&error);
+        let md = mdbook_epub::load_book(&args.root).expect(&error);
         let destination = md.build_dir_for("epub");
         debug!(
             "EPUB book destination folder is : {:?}",
             destination.display()
         );
         debug!("EPUB book config is : {:?}", md.config);
-        RenderContext::new(md.root, md.book, md.config, destination)
+        mdbook_epub::standalone_render_context(&md, destination)?
     } else {
         println!("Running mdbook-epub as plugin waiting on the STDIN input. If you wanted to process the files in the current folder, use the -s flag from documentation, See: mdbook-epub --help");
-        serde_json::from_reader(io::stdin()).map_err(|_| Error::RenderContext)?
+        mdbook_epub::parse_render_context(io::stdin())?
+    };
+    let outfile = match args.output {
+        Some(ref output) => {
+            let (destination, outfile) = output_paths(output, &ctx.config);
+            debug!(
+                "EPUB book output is overridden by : {:?}",
+                outfile.display()
+            );
+            ctx.destination = destination;
+            outfile
+        }
+        None => mdbook_epub::output_filename(&ctx.destination, &ctx.config),
     };
-    debug!("calling the main code for epub creation");
-    mdbook_epub::generate(&ctx)?;
+    if args.single_html {
+        let outfile = if is_output_file(&outfile, "epub") {
+            outfile.with_extension("xhtml")
+        } else {
+            outfile
+        };
+        debug!("calling the single XHTML preview creation");
+        mdbook_epub::generate_single_html(&ctx, &outfile)?;
+    } else {
+        debug!("calling the main code for epub creation");
+        let started = Instant::now();
+        // in plugin mode mdbook's output isn't interleaved with a progress line
+        let plugin = args.context.is_none() && !args.standalone;
+        let result = if !plugin && io::stderr().is_terminal() {
+            mdbook_epub::generate_with_progress(&ctx, &outfile, show_progress)
+        } else {
+            mdbook_epub::generate_with_diagnostics(&ctx, &outfile)
+        };
+        if let Some(ref report) = args.diagnostics_json {
+            let diagnostics = match result {
+                Ok(ref diagnostics) => diagnostics.clone(),
+                Err(ref e) => Diagnostics::failed(e, started.elapsed()),
+            };
+            write_diagnostics(report, &diagnostics)?;
+        }
+        result?;
+        if args.check {
+            check(&outfile)?;
+        }
+    }
     println!(
         "Book is READY in directory: '{}'",
         ctx.destination.display()
@@ -60,6 +110,110 @@ fn run(args: &Args) -> Result<(), Error> {
     Ok(())
 }
 
+/// Validate the generated EPUB by epubcheck and print the problems it reports, it fails
+/// if the EPUB isn't valid.
+fn check(outfile: &Path) -> Result<(), Error> {
+    println!("Checking '{}' by epubcheck...", outfile.display());
+    let messages = mdbook_epub::epub_check(outfile)?;
+    for message in &messages {
+        eprintln!("{message}");
+    }
+    let (failures, others): (Vec<&CheckMessage>, _) =
+        messages.iter().partition(|m| m.severity.is_failure());
+    if !failures.is_empty() {
+        return Err(Error::EpubCheck(format!(
+            "'{}' isn't valid, {} error(s) and {} other message(s)",
+            outfile.display(),
+            failures.len(),
+            others.len()
+        )));
+    }
+    println!("No errors found by epubcheck, {} message(s)", others.len());
+    Ok(())
+}
+
+/// Log level of the `-v` flags, which overrides the default one of `RUST_LOG`.
+fn log_level(verbose: u8) -> Option<log::LevelFilter> {
+    match verbose {
+        0 => None,
+        1 => Some(log::LevelFilter::Info),
+        2 => Some(log::LevelFilter::Debug),
+        _ => Some(log::LevelFilter::Trace),
+    }
+}
+
+/// The `RUST_LOG` filters with their default level raised to the one of the `-v` flags.
+/// A higher default level, e.g. of `RUST_LOG=debug -v`, and the module specific ones are kept.
+fn log_filters(rust_log: &str, verbose: u8) -> String {
+    let Some(level) = log_level(verbose) else {
+        return rust_log.to_string();
+    };
+    // e.g. `warn,mdbook_epub=trace/chapter`, where the last default level wins and
+    // the messages are filtered by the regex after '/'
+    let (directives, regex) = match rust_log.split_once('/') {
+        Some((directives, regex)) => (directives, format!("/{regex}")),
+        None => (rust_log, String::new()),
+    };
+    let default = directives
+        .rsplit(',')
+        .find_map(|directive| directive.trim().parse::<log::LevelFilter>().ok())
+        .unwrap_or(log::LevelFilter::Error);
+    match directives {
+        _ if level <= default => rust_log.to_string(),
+        "" => format!("{level}{regex}"),
+        _ => format!("{directives},{level}{regex}"),
+    }
+}
+
+/// Show the progress of the generation on a single line of the terminal, which is
+/// updated in place until the stage is done.
+fn show_progress(progress: Progress) {
+    let mut stderr = io::stderr().lock();
+    // the line is cleared in case the previous one was longer
+    let _ = write!(
+        stderr,
+        "\r\x1b[2K{}: {}/{}",
+        progress.stage, progress.position, progress.total
+    );
+    if progress.position == progress.total {
+        let _ = writeln!(stderr);
+    }
+    let _ = stderr.flush();
+}
+
+/// Write the build's diagnostics as JSON into the `report` file.
+fn write_diagnostics(report: &Path, diagnostics: &Diagnostics) -> Result<(), Error> {
+    debug!("Writing the diagnostics to '{}'", report.display());
+    if let Some(dir) = report.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = File::create(report)?;
+    serde_json::to_writer_pretty(file, diagnostics).map_err(io::Error::from)?;
+    Ok(())
+}
+
+fn is_output_file(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// Destination directory and output file for the `--output` path, which is either
+/// a directory or a full path to a `.epub` (or `.xhtml`) file.
+fn output_paths(output: &Path, config: &MdConfig) -> (PathBuf, PathBuf) {
+    if is_output_file(output, "epub") || is_output_file(output, "xhtml") {
+        let destination = output
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        (destination.to_path_buf(), output.to_path_buf())
+    } else {
+        (
+            output.to_path_buf(),
+            mdbook_epub::output_filename(output, config),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Parser)]
 #[clap(
     name = "MDBook epub utility",
@@ -81,6 +235,51 @@ struct Args {
         name = "root"
     )]
     root: PathBuf,
+
+    #[arg(
+        short = 'o',
+        long = "output",
+        help = "Output directory or .epub file path, overrides the book's build directory",
+        value_parser = clap::value_parser!(PathBuf)
+    )]
+    output: Option<PathBuf>,
+
+    #[arg(
+        long = "context",
+        help = "Read the render context from a JSON file instead of STDIN, e.g. to reproduce a failed build",
+        value_parser = clap::value_parser!(PathBuf),
+        conflicts_with = "standalone"
+    )]
+    context: Option<PathBuf>,
+
+    #[arg(
+        long = "single-html",
+        help = "Render all chapters into a single .xhtml file for previewing, instead of an EPUB"
+    )]
+    single_html: bool,
+
+    #[arg(
+        long = "diagnostics-json",
+        help = "Write a JSON report of the build, e.g. its warnings and timing, into the file",
+        value_parser = clap::value_parser!(PathBuf),
+        conflicts_with = "single_html"
+    )]
+    diagnostics_json: Option<PathBuf>,
+
+    #[arg(
+        long = "check",
+        help = "Validate the generated EPUB by epubcheck, which is run by EPUBCHECK_JAR if it's set",
+        conflicts_with = "single_html"
+    )]
+    check: bool,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        help = "Log more details, -v for info, -vv for debug and -vvv for trace messages",
+        action = clap::ArgAction::Count
+    )]
+    verbose: u8,
 }
 
 #[cfg(test)]
@@ -122,4 +321,161 @@ mod tests {
         debug_assert!(!args.standalone);
         debug_assert_eq!(args.root, PathBuf::from("/another/path"));
     }
+
+    #[test]
+    fn test_output_path() {
+        let args =
+            Args::try_parse_from(["test", "-s", "--output", "out/my.epub", "/book"]).unwrap();
+        debug_assert!(args.standalone);
+        debug_assert_eq!(args.root, PathBuf::from("/book"));
+        debug_assert_eq!(args.output, Some(PathBuf::from("out/my.epub")));
+
+        let args = Args::try_parse_from(["test", "-o", "out"]).unwrap();
+        debug_assert_eq!(args.output, Some(PathBuf::from("out")));
+        let args = Args::try_parse_from(["test"]).unwrap();
+        debug_assert_eq!(args.output, None);
+        debug_assert!(!args.single_html);
+    }
+
+    #[test]
+    fn test_context_file() {
+        let args = Args::try_parse_from(["test", "--context", "ctx.json"]).unwrap();
+        debug_assert!(!args.standalone);
+        debug_assert_eq!(args.context, Some(PathBuf::from("ctx.json")));
+
+        // the book is either loaded from the root or from the context
+        assert!(Args::try_parse_from(["test", "-s", "--context", "ctx.json"]).is_err());
+    }
+
+    #[test]
+    fn test_render_context_from_file() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let context = tmp_dir.path().join("ctx.json");
+        std::fs::write(&context, "{\"version\": \"0.4.44\", \"root\": null}").unwrap();
+        let args = Args::try_parse_from(["test", "--context", context.to_str().unwrap()]).unwrap();
+        let error = run(&args).unwrap_err();
+        assert!(matches!(error, Error::RenderContext { ref path, .. } if path == "$.root"));
+        assert!(error.to_string().starts_with(
+            "Unable to parse render context at '$.root': invalid type: null, expected path string"
+        ));
+
+        let args = Args::try_parse_from(["test", "--context", "no-such-ctx.json"]).unwrap();
+        assert!(matches!(run(&args), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_diagnostics_json() {
+        let args =
+            Args::try_parse_from(["test", "-s", "--diagnostics-json", "report.json"]).unwrap();
+        debug_assert_eq!(args.diagnostics_json, Some(PathBuf::from("report.json")));
+        assert!(Args::try_parse_from([
+            "test",
+            "-s",
+            "--single-html",
+            "--diagnostics-json",
+            "report.json"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_diagnostics_written_for_failed_build() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let context = tmp_dir.path().join("ctx.json");
+        let report = tmp_dir.path().join("reports/build.json");
+        let ctx = serde_json::json!({
+            "version": "0.0.1",
+            "root": tmp_dir.path(),
+            "book": {"sections": [], "__non_exhaustive": null},
+            "config": {"book": {"authors": [], "language": "en", "multilingual": false,
+                "src": "src", "title": "Book"}},
+            "destination": tmp_dir.path().join("book")
+        });
+        std::fs::write(&context, ctx.to_string()).unwrap();
+        let args = Args::try_parse_from([
+            "test",
+            "--context",
+            context.to_str().unwrap(),
+            "--diagnostics-json",
+            report.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        // the 0.0.1 version of mdbook isn't supported
+        assert!(matches!(run(&args), Err(Error::IncompatibleVersion(..))));
+        let diagnostics: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+        assert_eq!(diagnostics["chapters"], 0);
+        assert_eq!(diagnostics["warnings"], serde_json::json!([]));
+        assert!(diagnostics["error"]
+            .as_str()
+            .unwrap()
+            .contains("Incompatible mdbook version"));
+    }
+
+    #[test]
+    fn test_check() {
+        let args = Args::try_parse_from(["test", "-s", "--check"]).unwrap();
+        debug_assert!(args.check);
+        let args = Args::try_parse_from(["test", "-s"]).unwrap();
+        debug_assert!(!args.check);
+        assert!(Args::try_parse_from(["test", "-s", "--single-html", "--check"]).is_err());
+    }
+
+    #[test]
+    fn test_verbose_levels() {
+        let args = Args::try_parse_from(["test", "-s"]).unwrap();
+        assert_eq!(log_level(args.verbose), None);
+        let args = Args::try_parse_from(["test", "-s", "-v"]).unwrap();
+        assert_eq!(log_level(args.verbose), Some(log::LevelFilter::Info));
+        let args = Args::try_parse_from(["test", "-vv", "-s"]).unwrap();
+        assert_eq!(log_level(args.verbose), Some(log::LevelFilter::Debug));
+        let args = Args::try_parse_from(["test", "--verbose", "-vvv"]).unwrap();
+        assert_eq!(log_level(args.verbose), Some(log::LevelFilter::Trace));
+    }
+
+    #[test]
+    fn verbose_flags_only_raise_log_level() {
+        assert_eq!(log_filters("", 0), "");
+        assert_eq!(log_filters("", 1), "INFO");
+        assert_eq!(log_filters("warn,mdbook=off", 2), "warn,mdbook=off,DEBUG");
+        assert_eq!(log_filters("debug", 1), "debug");
+        assert_eq!(log_filters("trace,mdbook=warn", 2), "trace,mdbook=warn");
+        assert_eq!(log_filters("debug,info", 2), "debug,info,DEBUG");
+        assert_eq!(
+            log_filters("mdbook_epub=trace", 1),
+            "mdbook_epub=trace,INFO"
+        );
+        assert_eq!(log_filters("error/chapter", 3), "error,TRACE/chapter");
+    }
+
+    #[test]
+    fn test_single_html() {
+        let args = Args::try_parse_from(["test", "-s", "--single-html"]).unwrap();
+        debug_assert!(args.standalone);
+        debug_assert!(args.single_html);
+    }
+
+    #[test]
+    fn test_output_paths_for_file_or_directory() {
+        let mut config = MdConfig::default();
+        config.book.title = Some("My Book".to_string());
+
+        assert_eq!(
+            output_paths(Path::new("out/my.epub"), &config),
+            (PathBuf::from("out"), PathBuf::from("out/my.epub"))
+        );
+        assert_eq!(
+            output_paths(Path::new("my.EPUB"), &config),
+            (PathBuf::from("."), PathBuf::from("my.EPUB"))
+        );
+        assert_eq!(
+            output_paths(Path::new("preview.xhtml"), &config),
+            (PathBuf::from("."), PathBuf::from("preview.xhtml"))
+        );
+        assert_eq!(
+            output_paths(Path::new("out"), &config),
+            (PathBuf::from("out"), PathBuf::from("out/My Book.epub"))
+        );
+    }
 }
@@ -1,24 +1,128 @@
 use super::Error;
 use mdbook::renderer::RenderContext;
-use std::path::PathBuf;
+use mime_guess::Mime;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 pub const DEFAULT_TEMPLATE: &str = include_str!("index.hbs");
 
+/// Rights statements of well-known SPDX license identifiers for the `license` option.
+const SPDX_LICENSES: &[(&str, &str)] = &[
+    ("CC0-1.0", "CC0 1.0 Universal (Public Domain Dedication)"),
+    ("CC-BY-4.0", "Creative Commons Attribution 4.0 International (CC BY 4.0)"),
+    (
+        "CC-BY-SA-4.0",
+        "Creative Commons Attribution-ShareAlike 4.0 International (CC BY-SA 4.0)",
+    ),
+    (
+        "CC-BY-ND-4.0",
+        "Creative Commons Attribution-NoDerivatives 4.0 International (CC BY-ND 4.0)",
+    ),
+    (
+        "CC-BY-NC-4.0",
+        "Creative Commons Attribution-NonCommercial 4.0 International (CC BY-NC 4.0)",
+    ),
+    (
+        "CC-BY-NC-SA-4.0",
+        "Creative Commons Attribution-NonCommercial-ShareAlike 4.0 International (CC BY-NC-SA 4.0)",
+    ),
+    (
+        "CC-BY-NC-ND-4.0",
+        "Creative Commons Attribution-NonCommercial-NoDerivatives 4.0 International (CC BY-NC-ND 4.0)",
+    ),
+    ("MIT", "MIT License"),
+    ("Apache-2.0", "Apache License 2.0"),
+    ("GFDL-1.3-or-later", "GNU Free Documentation License 1.3 or later"),
+];
+
+/// Titles of the table of contents by the primary language subtag of the book, used
+/// unless the `toc-title` is set. Other languages get epub-builder's English one.
+const TOC_TITLES: &[(&str, &str)] = &[
+    ("de", "Inhaltsverzeichnis"),
+    ("es", "Índice"),
+    ("fr", "Table des matières"),
+    ("it", "Indice"),
+    ("ja", "目次"),
+    ("ko", "목차"),
+    ("nl", "Inhoudsopgave"),
+    ("pl", "Spis treści"),
+    ("pt", "Sumário"),
+    ("ru", "Содержание"),
+    ("sv", "Innehåll"),
+    ("uk", "Зміст"),
+    ("zh", "目录"),
+];
+
 /// The configuration struct used to tweak how an EPUB document is generated.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct Config {
+    /// Generate the EPUB book (default: true). Set to `false` to keep the
+    /// `[output.epub]` section in `book.toml` but skip the backend.
+    pub enable: bool,
+    /// Description of the EPUB, e.g. a marketing blurb, used instead of the book's
+    /// `description` if set.
+    pub description: Option<String>,
+    /// Subjects or keywords of the book, e.g. for categorization in stores.
+    pub subjects: Vec<String>,
+    /// Copyright or license statement of the book, recorded as its rights.
+    pub rights: Option<String>,
+    /// SPDX identifier of the book's license, e.g. `CC-BY-4.0`, recorded as its rights in
+    /// words if `rights` isn't set.
+    pub license: Option<String>,
+    /// UUID of the book recorded as its unique identifier, e.g. to keep the one of an
+    /// earlier edition. One derived from the book's title and authors is used if it's
+    /// not set, so rebuilds of the book have the same identifier.
+    pub identifier: Option<String>,
+    /// Work the book is derived from, e.g. the URL of its online version, recorded as its
+    /// `<dc:source>`.
+    pub source: Option<String>,
+    /// Name of the series the book is a volume of, which readers like Calibre group the
+    /// volumes by.
+    pub series: Option<String>,
+    /// Position of the book in its `series`, e.g. `2` for the second volume.
+    pub series_index: Option<u32>,
+    /// People who contributed to the book besides its authors, e.g. editors or
+    /// illustrators, listed on the "About this book" page.
+    pub contributors: Vec<String>,
+    /// Add an "About this book" page with the authors, contributors, subjects and
+    /// rights of the book after the chapters (default: false).
+    pub about_page: bool,
+    /// Generator recorded in the navigation document's metadata (default: `mdbook-epub`),
+    /// an empty name omits it.
+    pub generator_name: Option<String>,
+    /// Title of the table of contents, e.g. `Sommaire` (default: one in the book's
+    /// language if it's known, otherwise `Table Of Contents`).
+    pub toc_title: Option<String>,
+    /// File name (without the `.epub` extension) of a book without a title (default: `book`).
+    pub default_book_name: Option<String>,
     /// A list of additional stylesheets to include in the document.
     pub additional_css: Vec<PathBuf>,
     /// Should we use the default stylesheet (default: true)?
     pub use_default_css: bool,
+    /// Which built-in stylesheet to use, taking precedence over `use-default-css`
+    /// (default: `full`, or `none` if `use-default-css` is false).
+    pub css_mode: Option<CssMode>,
+    /// Embed the `additional-css` stylesheets as files of their own, named after them
+    /// and linked individually, instead of appending them to `stylesheet.css`
+    /// (default: false).
+    pub separate_stylesheets: bool,
     /// The template file to use when rendering individual chapters (relative
-    /// to the book root).
-    pub index_template: Option<PathBuf>,
+    /// to the book root), or template files by the path patterns of the chapters
+    /// they are used for.
+    pub index_template: Option<IndexTemplate>,
     /// A cover image to use for the epub.
     pub cover_image: Option<PathBuf>,
+    /// Generate a simple cover image with the book's title and authors when no
+    /// `cover-image` is set (default: false).
+    pub generate_cover: bool,
+    /// Width and height of the generated cover in pixels (default: `[1600, 2560]`).
+    pub cover_size: [u32; 2],
     /// Additional assets to include in the ebook, such as typefaces.
     pub additional_resources: Vec<PathBuf>,
+    /// Media types of assets and additional resources by their file extension, e.g.
+    /// `svgz = "image/svg+xml"`, which take precedence over the guessed ones (default: none).
+    pub mime_overrides: BTreeMap<String, String>,
     /// Don't render section labels.
     pub no_section_label: bool,
     /// Use "smart quotes" instead of the usual `"` character.
@@ -27,10 +131,251 @@ pub struct Config {
     /// Requires `epub-version = 3`, in which case it is enabled by default.
     pub footnote_backrefs: bool,
     /// EPUB version to use if specified, otherwise defaults to the epub-builder default.
-    pub epub_version: Option<u8>,
+    pub epub_version: Option<EpubVersionValue>,
+    /// Fail the build when an asset can't be embedded or a chapter can't be
+    /// rendered, instead of logging a warning and skipping it (default: false).
+    pub strict_assets: bool,
+    /// Image (relative to the book root) shown instead of local assets which don't exist,
+    /// unless `strict_assets` is enabled (default: none, missing assets fail the build).
+    pub missing_asset_placeholder: Option<PathBuf>,
+    /// Additional directories outside of `src` (relative to the book root) which
+    /// chapters may reference assets from, e.g. `theme`.
+    pub asset_roots: Vec<PathBuf>,
+    /// Resolve asset links with a leading `/`, e.g. `/images/logo.png`, relative to `src`
+    /// like mdbook's HTML renderer, instead of one folder above the chapter (default: false).
+    pub root_relative_links: bool,
+    /// The file name mdbook renames into `index.md` chapters (default: `README.md`),
+    /// used to resolve relative asset links of such chapters.
+    pub readme_filename: String,
+    /// Element to wrap every chapter's content in, e.g. `section`.
+    pub chapter_wrapper: Option<String>,
+    /// Semantic type of the chapter wrapper (default: `chapter`), emitted as `epub:type`
+    /// for EPUB 3 and as a class name otherwise.
+    pub chapter_wrapper_type: Option<String>,
+    /// Transliterate unicode symbols and emoji of chapter paths into ASCII for the
+    /// chapter class names instead of stripping them (default: true).
+    pub transliterate_slugs: bool,
+    /// Embed a simple JSON inverted index over chapter texts for reading systems
+    /// and tools supporting it (default: false).
+    pub search_index: bool,
+    /// Font files to embed, each one is declared by a `@font-face` rule in the
+    /// stylesheet with the file stem as font family.
+    pub fonts: Vec<PathBuf>,
+    /// Code blocks with more lines than that are handled according to
+    /// `code-block-overflow` (default: no limit).
+    pub code_block_max_lines: Option<usize>,
+    /// How to render code blocks longer than `code-block-max-lines` (default: `scroll`).
+    pub code_block_overflow: CodeBlockOverflow,
+    /// Render ```` ```math ```` code blocks as MathML in EPUB 3 books, instead of
+    /// showing the formula's source (default: false).
+    pub math_code_blocks: bool,
+    /// Group consecutive code blocks of the same language, e.g. snippets of a tutorial,
+    /// so they are shown as one listing (default: false).
+    pub group_code_blocks: bool,
+    /// Command rendering ```` ```mermaid ```` code blocks into embedded SVG images, called
+    /// like the Mermaid CLI: `<command> -i <input> -o <output.svg>` (default: keep as code).
+    pub mermaid_command: Option<String>,
+    /// Render raw `<details>`/`<summary>` blocks as always expanded, styled blocks
+    /// instead of keeping them as they are (default: false).
+    pub expand_details: bool,
+    /// Render GitHub's alert block quotes, e.g. `> [!NOTE]`, as styled admonition blocks
+    /// with a title instead of plain block quotes (default: false).
+    pub admonitions: bool,
+    /// Add a link to the online Rust Playground after runnable Rust code blocks,
+    /// in place of mdbook's "Run" button (default: false).
+    pub playground_links: bool,
+    /// Remove the boilerplate lines of Rust code blocks which mdbook hides, i.e. lines
+    /// starting with `# ` (default: true).
+    pub hide_boilerplate: bool,
+    /// Strip the YAML front matter (`---` delimited `key: value` lines) at the start of
+    /// chapters, using its `title` as the page's title (default: true).
+    pub front_matter: bool,
+    /// Number figures (images with a title) and tables sequentially across the
+    /// book, prefixing their captions with `Figure N` and `Table N` (default: false).
+    pub number_captions: bool,
+    /// Wrap images followed by an emphasized caption, e.g. `*The caption*` on the next
+    /// line, into a `<figure>` with a `<figcaption>` (default: false).
+    pub figure_captions: bool,
+    /// Add part titles (`# Title` lines in `SUMMARY.md`) to the TOC as headers of the
+    /// following chapters, instead of leaving them out (default: false).
+    pub part_titles_in_toc: bool,
+    /// Render chapters with only a heading and no other content as section dividers,
+    /// marked by the `section-divider` class (default: false).
+    pub section_dividers: bool,
+    /// Path of the chapter in `src` where reading starts, marked as the "bodymatter"
+    /// landmark (default: the first chapter with more than a heading).
+    pub start_chapter: Option<PathBuf>,
+    /// Paths in `src` of the chapters to put first in the reading order (spine), in
+    /// this order and with their sub-chapters, e.g. a preface. The other chapters follow
+    /// in the order of `SUMMARY.md` (default: none).
+    pub spine_order: Vec<PathBuf>,
+    /// Scale images down to the height of the page, so tall images like long diagrams
+    /// aren't cut off by e-readers (default: false).
+    pub constrain_image_height: bool,
+    /// Start each top-level chapter on a new page, by the `chapter-start` class of its
+    /// `<div>` (default: false).
+    pub chapter_page_breaks: bool,
+    /// Split chapters into several documents at their headings of this level (1-6),
+    /// nested under the chapter in the TOC, as big documents are slow in some readers
+    /// (default: None).
+    pub split_on_heading_level: Option<u32>,
+    /// Local images smaller than this many bytes are inlined into the chapters as
+    /// `data:` URIs instead of being embedded as files (default: None).
+    pub inline_images_under_bytes: Option<u64>,
+    /// Warn about images without an alt text, or fail the build if `strict_assets` is
+    /// enabled. An image's title is used as its missing alt text (default: false).
+    pub require_alt_text: bool,
+    /// Fail the build on links to `.md` or `.html` files which aren't chapters of the book,
+    /// instead of only warning about them (default: false).
+    pub validate_links: bool,
+    /// Embed the markdown source of every chapter as a `sources/<chapter>.md` resource,
+    /// which is not part of the reading order (default: false).
+    pub embed_sources: bool,
+    /// Also write the book as a single self-contained XHTML document with the stylesheet
+    /// and local images inlined next to the EPUB, e.g. `book.xhtml` (default: false).
+    pub single_html: bool,
+    /// Add draft chapters (without a file) as "Coming soon" placeholder pages, which
+    /// are listed in the TOC, instead of leaving them out (default: false).
+    pub include_drafts: bool,
+    /// Compression level of the EPUB from `0` (none) to `9` (smallest), files which are
+    /// compressed already like JPEG images aren't compressed again (default: the zip
+    /// library's default for all files).
+    pub compression: Option<u8>,
+    /// Label the navigation document's `<nav>` elements with an `aria-label` and its links
+    /// with a `title` for screen readers (default: false).
+    pub accessible_nav: bool,
+    /// Maximum number of remote assets downloaded at the same time (default: 4).
+    pub download_concurrency: usize,
+    /// What to do with remote images which fail to download (default: `fail`).
+    pub remote_failure_mode: RemoteFailureMode,
+    /// Abort the generation with an error when it takes longer than that (default: no limit).
+    pub generation_timeout_secs: Option<u64>,
+}
+
+/// Rendering of code blocks exceeding `code-block-max-lines`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CodeBlockOverflow {
+    /// Wrap the code block into a container of limited height with a scrollbar.
+    #[default]
+    Scroll,
+    /// Only keep the first lines, followed by a note about the omitted ones.
+    Truncate,
+}
+
+/// Built-in stylesheet the book's additional stylesheets are appended to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CssMode {
+    /// The default stylesheet, i.e. [`DEFAULT_CSS`](crate::DEFAULT_CSS).
+    Full,
+    /// Only a small reset of margins and image sizes, so images don't overflow the page.
+    Minimal,
+    /// No built-in stylesheet.
+    None,
+}
+
+/// Handling of remote images which fail to download, e.g. when building offline.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemoteFailureMode {
+    /// Show an embedded "Image unavailable" placeholder instead of the image.
+    EmbedPlaceholder,
+    /// Keep the image's original URL, so it's shown by readers which are online.
+    KeepUrl,
+    /// Keep the original URL and fall back to the embedded placeholder if it can't be loaded.
+    Both,
+    /// Abort the build.
+    #[default]
+    Fail,
+}
+
+/// The EPUB version as written in `book.toml`, e.g. `3`, `3.2` or `"3.2"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EpubVersionValue {
+    Integer(u8),
+    Float(f64),
+    Text(String),
+}
+
+impl EpubVersionValue {
+    /// The `(major, minor)` pair of the version, `None` if it can't be parsed.
+    pub fn major_minor(&self) -> Option<(u8, u8)> {
+        let text = self.to_string();
+        let mut parts = text.trim().split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = match parts.next() {
+            Some(minor) => minor.parse().ok()?,
+            None => 0,
+        };
+        Some((major, minor))
+    }
+
+    /// The major version, e.g. `3` for `3.2`.
+    pub fn major(&self) -> Option<u8> {
+        self.major_minor().map(|(major, _)| major)
+    }
+}
+
+impl From<u8> for EpubVersionValue {
+    fn from(version: u8) -> Self {
+        EpubVersionValue::Integer(version)
+    }
+}
+
+impl std::fmt::Display for EpubVersionValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpubVersionValue::Integer(v) => write!(f, "{v}"),
+            EpubVersionValue::Float(v) => write!(f, "{v}"),
+            EpubVersionValue::Text(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+/// The `index-template` as written in `book.toml`, either a single file or a table of
+/// chapter path patterns (e.g. `"appendix/**"`) to files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IndexTemplate {
+    Single(PathBuf),
+    ByPattern(BTreeMap<String, PathBuf>),
+}
+
+impl IndexTemplate {
+    fn relative_to(self, root: &Path) -> IndexTemplate {
+        match self {
+            IndexTemplate::Single(file) => IndexTemplate::Single(root.join(file)),
+            IndexTemplate::ByPattern(files) => IndexTemplate::ByPattern(
+                files
+                    .into_iter()
+                    .map(|(pattern, file)| (pattern, root.join(file)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<PathBuf> for IndexTemplate {
+    fn from(file: PathBuf) -> Self {
+        IndexTemplate::Single(file)
+    }
 }
 
 impl Config {
+    /// Builder of a config, for generating books without a `book.toml`.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Set the config as the `output.epub` table of the render context, which the
+    /// generator reads it from.
+    pub fn apply_to(&self, ctx: &mut RenderContext) -> Result<(), Error> {
+        ctx.config.set("output.epub", self)?;
+        Ok(())
+    }
+
     /// Get the `output.epub` table from the provided `book.toml` config,
     /// falling back to the default if
     pub fn from_render_context(ctx: &RenderContext) -> Result<Config, Error> {
@@ -40,8 +385,8 @@ impl Config {
 
                 // make sure we update the `index_template` to make it relative
                 // to the book root
-                if let Some(template_file) = cfg.index_template.take() {
-                    cfg.index_template = Some(ctx.root.join(template_file));
+                if let Some(template) = cfg.index_template.take() {
+                    cfg.index_template = Some(template.relative_to(&ctx.root));
                 }
 
                 Ok(cfg)
@@ -50,31 +395,274 @@ impl Config {
         }
     }
 
+    /// The rights statement of the book, the `rights` or the `license` in words, e.g.
+    /// "Creative Commons Attribution 4.0 International (CC BY 4.0)" for `CC-BY-4.0`.
+    /// Unknown license identifiers are used as they are.
+    pub fn rights_statement(&self) -> Option<String> {
+        if let Some(ref rights) = self.rights {
+            return Some(rights.clone());
+        }
+        let license = self.license.as_deref()?.trim();
+        let statement = SPDX_LICENSES
+            .iter()
+            .find(|(id, _)| id.eq_ignore_ascii_case(license))
+            .map_or_else(
+                || {
+                    warn!("License '{license}' is not a known SPDX identifier, it's used as is");
+                    license.to_string()
+                },
+                |(_, name)| name.to_string(),
+            );
+        Some(statement)
+    }
+
+    /// Title of the table of contents, the `toc-title` or the one of the book's `language`,
+    /// e.g. `目次` for `ja`. `None` keeps epub-builder's English title.
+    pub fn toc_title(&self, language: Option<&str>) -> Option<String> {
+        if let Some(ref title) = self.toc_title {
+            return Some(title.clone());
+        }
+        let primary = language?.split(['-', '_']).next()?;
+        TOC_TITLES
+            .iter()
+            .find(|(lang, _)| lang.eq_ignore_ascii_case(primary))
+            .map(|(_, title)| title.to_string())
+    }
+
+    /// Is the EPUB 3 format requested?
+    pub fn is_epub3(&self) -> bool {
+        self.epub_version.as_ref().and_then(EpubVersionValue::major) == Some(3)
+    }
+
+    /// The built-in stylesheet of the `css-mode`, or the one `use-default-css` selects.
+    pub fn stylesheet_mode(&self) -> CssMode {
+        match self.css_mode {
+            Some(mode) => mode,
+            None if self.use_default_css => CssMode::Full,
+            None => CssMode::None,
+        }
+    }
+
+    /// The template of pages which don't have a chapter template, the default one
+    /// unless a single `index-template` is set.
     pub fn template(&self) -> Result<String, Error> {
         match self.index_template {
-            Some(ref filename) => {
-                let buffer = std::fs::read_to_string(filename)
-                    .map_err(|_| Error::OpenTemplate(filename.clone()))?;
+            Some(IndexTemplate::Single(ref filename)) => read_template(filename),
+            _ => Ok(DEFAULT_TEMPLATE.to_string()),
+        }
+    }
+
+    /// Media type of the file by the `mime-overrides`, matching its extension case
+    /// insensitively, if there's a valid one.
+    pub(crate) fn mime_override(&self, path: &Path) -> Option<Mime> {
+        let extension = path.extension()?.to_str()?;
+        self.mime_overrides
+            .iter()
+            .find(|(ext, _)| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+            .and_then(|(_, mimetype)| mimetype.parse().ok())
+    }
+
+    /// Templates of the chapters matching a path pattern, by the pattern and the file name.
+    pub fn chapter_templates(&self) -> Result<Vec<(&str, &Path, String)>, Error> {
+        match self.index_template {
+            Some(IndexTemplate::ByPattern(ref files)) => files
+                .iter()
+                .map(|(pattern, file)| Ok((pattern.as_str(), file.as_path(), read_template(file)?)))
+                .collect(),
+            _ => Ok(Vec::new()),
+        }
+    }
+}
 
-                Ok(buffer)
-            }
-            None => Ok(DEFAULT_TEMPLATE.to_string()),
+fn read_template(filename: &Path) -> Result<String, Error> {
+    std::fs::read_to_string(filename).map_err(|_| Error::OpenTemplate(filename.to_path_buf()))
+}
+
+/// Builder of a [`Config`] for generating books without a `book.toml`, starting with the
+/// defaults. Each setter sets the config's field of the same name, e.g.
+/// `Config::builder().curly_quotes(true).epub_version(3).build()`.
+#[derive(Debug, Default, Clone)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+macro_rules! setters {
+    (
+        plain { $($field:ident: $ty:ty,)* }
+        optional { $($opt_field:ident: $opt_ty:ty,)* }
+    ) => {
+        impl ConfigBuilder {
+            $(
+                #[doc = concat!("Set `", stringify!($field), "`.")]
+                pub fn $field(mut self, value: impl Into<$ty>) -> Self {
+                    self.config.$field = value.into();
+                    self
+                }
+            )*
+            $(
+                #[doc = concat!("Set `", stringify!($opt_field), "`.")]
+                pub fn $opt_field(mut self, value: impl Into<$opt_ty>) -> Self {
+                    self.config.$opt_field = Some(value.into());
+                    self
+                }
+            )*
         }
+    };
+}
+
+setters! {
+    plain {
+        enable: bool,
+        subjects: Vec<String>,
+        contributors: Vec<String>,
+        about_page: bool,
+        additional_css: Vec<PathBuf>,
+        use_default_css: bool,
+        separate_stylesheets: bool,
+        generate_cover: bool,
+        cover_size: [u32; 2],
+        additional_resources: Vec<PathBuf>,
+        mime_overrides: BTreeMap<String, String>,
+        no_section_label: bool,
+        curly_quotes: bool,
+        footnote_backrefs: bool,
+        strict_assets: bool,
+        asset_roots: Vec<PathBuf>,
+        root_relative_links: bool,
+        readme_filename: String,
+        transliterate_slugs: bool,
+        search_index: bool,
+        fonts: Vec<PathBuf>,
+        code_block_overflow: CodeBlockOverflow,
+        math_code_blocks: bool,
+        group_code_blocks: bool,
+        expand_details: bool,
+        admonitions: bool,
+        playground_links: bool,
+        hide_boilerplate: bool,
+        front_matter: bool,
+        number_captions: bool,
+        figure_captions: bool,
+        part_titles_in_toc: bool,
+        spine_order: Vec<PathBuf>,
+        section_dividers: bool,
+        constrain_image_height: bool,
+        chapter_page_breaks: bool,
+        require_alt_text: bool,
+        validate_links: bool,
+        embed_sources: bool,
+        single_html: bool,
+        include_drafts: bool,
+        accessible_nav: bool,
+        download_concurrency: usize,
+        remote_failure_mode: RemoteFailureMode,
+    }
+    optional {
+        description: String,
+        rights: String,
+        license: String,
+        identifier: String,
+        source: String,
+        series: String,
+        series_index: u32,
+        generator_name: String,
+        toc_title: String,
+        default_book_name: String,
+        index_template: IndexTemplate,
+        cover_image: PathBuf,
+        missing_asset_placeholder: PathBuf,
+        epub_version: EpubVersionValue,
+        css_mode: CssMode,
+        chapter_wrapper: String,
+        chapter_wrapper_type: String,
+        code_block_max_lines: usize,
+        mermaid_command: String,
+        start_chapter: PathBuf,
+        split_on_heading_level: u32,
+        inline_images_under_bytes: u64,
+        compression: u8,
+        generation_timeout_secs: u64,
+    }
+}
+
+impl ConfigBuilder {
+    /// The configured [`Config`].
+    pub fn build(self) -> Config {
+        self.config
     }
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
+            enable: true,
+            description: None,
+            subjects: Vec::new(),
+            rights: None,
+            license: None,
+            identifier: None,
+            source: None,
+            series: None,
+            series_index: None,
+            contributors: Vec::new(),
+            about_page: false,
+            generator_name: None,
+            toc_title: None,
+            default_book_name: None,
             use_default_css: true,
+            separate_stylesheets: false,
+            css_mode: None,
             additional_css: Vec::new(),
             index_template: None,
             cover_image: None,
+            generate_cover: false,
+            cover_size: [1600, 2560],
             additional_resources: Vec::new(),
+            mime_overrides: BTreeMap::new(),
             no_section_label: false,
             curly_quotes: false,
             footnote_backrefs: false,
             epub_version: None,
+            strict_assets: false,
+            missing_asset_placeholder: None,
+            asset_roots: Vec::new(),
+            root_relative_links: false,
+            readme_filename: String::from("README.md"),
+            chapter_wrapper: None,
+            chapter_wrapper_type: None,
+            transliterate_slugs: true,
+            search_index: false,
+            fonts: Vec::new(),
+            code_block_max_lines: None,
+            code_block_overflow: CodeBlockOverflow::default(),
+            math_code_blocks: false,
+            group_code_blocks: false,
+            mermaid_command: None,
+            expand_details: false,
+            admonitions: false,
+            playground_links: false,
+            hide_boilerplate: true,
+            front_matter: true,
+            number_captions: false,
+            figure_captions: false,
+            part_titles_in_toc: false,
+            section_dividers: false,
+            start_chapter: None,
+            spine_order: Vec::new(),
+            constrain_image_height: false,
+            chapter_page_breaks: false,
+            split_on_heading_level: None,
+            inline_images_under_bytes: None,
+            require_alt_text: false,
+            validate_links: false,
+            embed_sources: false,
+            single_html: false,
+            include_drafts: false,
+            compression: None,
+            accessible_nav: false,
+            download_concurrency: 4,
+            remote_failure_mode: RemoteFailureMode::Fail,
+            generation_timeout_secs: None,
         }
     }
 }
@@ -98,6 +686,148 @@ mod tests {
         assert!(config.is_ok());
     }
 
+    #[test]
+    fn test_config_builder() {
+        let config = Config::builder()
+            .curly_quotes(true)
+            .epub_version(3)
+            .fonts(vec![PathBuf::from("fonts/serif.woff2")])
+            .cover_image("cover.png")
+            .code_block_overflow(CodeBlockOverflow::Truncate)
+            .build();
+        assert!(config.curly_quotes);
+        assert!(config.is_epub3());
+        assert_eq!(config.fonts, [PathBuf::from("fonts/serif.woff2")]);
+        assert_eq!(config.cover_image, Some(PathBuf::from("cover.png")));
+        assert_eq!(config.code_block_overflow, CodeBlockOverflow::Truncate);
+        // other fields keep their defaults
+        assert_eq!(
+            Config {
+                curly_quotes: false,
+                epub_version: None,
+                fonts: Vec::new(),
+                cover_image: None,
+                code_block_overflow: CodeBlockOverflow::Scroll,
+                ..config.clone()
+            },
+            Config::default()
+        );
+
+        let tmp_dir = TempDir::new().unwrap();
+        let json = ctx_with_template("src", tmp_dir.path().join("mdbook-epub").as_path());
+        let mut ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        config.apply_to(&mut ctx).unwrap();
+        assert_eq!(Config::from_render_context(&ctx).unwrap(), config);
+    }
+
+    #[test]
+    fn test_rights_statement() {
+        assert_eq!(Config::default().rights_statement(), None);
+        let config = Config::builder().license("cc-by-sa-4.0").build();
+        assert_eq!(
+            config.rights_statement().as_deref(),
+            Some("Creative Commons Attribution-ShareAlike 4.0 International (CC BY-SA 4.0)")
+        );
+        let config = Config::builder().license("Proprietary").build();
+        assert_eq!(config.rights_statement().as_deref(), Some("Proprietary"));
+        // explicit rights take precedence
+        let config = Config::builder()
+            .license("MIT")
+            .rights("© 2024 Ferris")
+            .build();
+        assert_eq!(config.rights_statement().as_deref(), Some("© 2024 Ferris"));
+    }
+
+    #[test]
+    fn test_toc_title() {
+        let config = Config::default();
+        assert_eq!(config.toc_title(None), None);
+        assert_eq!(config.toc_title(Some("en")), None);
+        assert_eq!(config.toc_title(Some("ja")).as_deref(), Some("目次"));
+        assert_eq!(
+            config.toc_title(Some("fr-CA")).as_deref(),
+            Some("Table des matières")
+        );
+        assert_eq!(
+            config.toc_title(Some("DE_at")).as_deref(),
+            Some("Inhaltsverzeichnis")
+        );
+        // the configured title takes precedence
+        let config = Config::builder().toc_title("Sommaire").build();
+        assert_eq!(config.toc_title(Some("ja")).as_deref(), Some("Sommaire"));
+    }
+
+    #[test]
+    fn test_mime_overrides() {
+        let cfg: Config = toml::from_str(
+            "[mime-overrides]\nxhtml = \"application/xhtml+xml\"\n\".svgz\" = \"image/svg+xml\"",
+        )
+        .unwrap();
+        let mime = |path: &str| cfg.mime_override(Path::new(path)).map(|m| m.to_string());
+        assert_eq!(
+            mime("extra/page.xhtml").as_deref(),
+            Some("application/xhtml+xml")
+        );
+        assert_eq!(mime("Page.XHTML").as_deref(), Some("application/xhtml+xml"));
+        assert_eq!(mime("logo.svgz").as_deref(), Some("image/svg+xml"));
+        assert_eq!(mime("logo.svg"), None);
+        assert_eq!(mime("xhtml"), None);
+    }
+
+    #[test]
+    fn test_index_template_forms() {
+        let cfg: Config = toml::from_str("index-template = \"theme/index.hbs\"").unwrap();
+        assert_eq!(
+            cfg.index_template,
+            Some(IndexTemplate::Single(PathBuf::from("theme/index.hbs")))
+        );
+
+        let cfg: Config =
+            toml::from_str("[index-template]\n\"appendix/**\" = \"theme/appendix.hbs\"\n").unwrap();
+        assert_eq!(
+            cfg.index_template,
+            Some(IndexTemplate::ByPattern(BTreeMap::from([(
+                "appendix/**".to_string(),
+                PathBuf::from("theme/appendix.hbs")
+            )])))
+        );
+        // chapters not matching any pattern use the default template
+        assert_eq!(cfg.template().unwrap(), DEFAULT_TEMPLATE);
+        assert!(matches!(
+            cfg.chapter_templates(),
+            Err(Error::OpenTemplate(ref file)) if file == Path::new("theme/appendix.hbs")
+        ));
+    }
+
+    #[test]
+    fn test_epub_version_forms() {
+        let parse =
+            |value: &str| -> Config { toml::from_str(&format!("epub-version = {value}")).unwrap() };
+
+        let cfg = parse("3");
+        assert_eq!(cfg.epub_version, Some(EpubVersionValue::Integer(3)));
+        assert!(cfg.is_epub3());
+
+        let cfg = parse("3.2");
+        assert_eq!(cfg.epub_version, Some(EpubVersionValue::Float(3.2)));
+        assert_eq!(cfg.epub_version.unwrap().major_minor(), Some((3, 2)));
+
+        let cfg = parse("\"3.1\"");
+        assert_eq!(
+            cfg.epub_version.as_ref().unwrap().major_minor(),
+            Some((3, 1))
+        );
+        assert!(cfg.is_epub3());
+
+        let cfg = parse("2");
+        assert_eq!(cfg.epub_version.unwrap().major_minor(), Some((2, 0)));
+
+        let cfg = parse("\"three\"");
+        assert_eq!(cfg.epub_version.unwrap().major_minor(), None);
+
+        assert!(!Config::default().is_epub3());
+    }
+
     fn ctx_with_template(source: &str, destination: &Path) -> serde_json::Value {
         json!({
             "version": mdbook::MDBOOK_VERSION,
@@ -0,0 +1,168 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{ImageFormat, Rgb, RgbImage};
+use std::io::Cursor;
+
+use crate::errors::Error;
+
+/// Font used to render the generated cover's text.
+const COVER_FONT: &[u8] = include_bytes!("fonts/DejaVuSerif-Bold.ttf");
+
+const BACKGROUND: Rgb<u8> = Rgb([0x2b, 0x3a, 0x55]);
+const FOREGROUND: Rgb<u8> = Rgb([0xf5, 0xf1, 0xe8]);
+
+/// Render a simple PNG cover with the book's title and authors on a solid background.
+pub(crate) fn generate_cover(
+    title: &str,
+    authors: &str,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, Error> {
+    debug!("Generating {}x{} cover for '{}'", width, height, title);
+    let font = FontRef::try_from_slice(COVER_FONT)
+        .map_err(|e| Error::CoverGenerate(format!("Unable to load the cover font: {e}")))?;
+    let mut cover = RgbImage::from_pixel(width, height, BACKGROUND);
+
+    let margin = width as f32 / 10.0;
+    let max_line_width = width as f32 - 2.0 * margin;
+
+    // title in the upper third, authors at the bottom
+    let title_scale = PxScale::from(width as f32 / 11.0);
+    draw_text_block(
+        &mut cover,
+        &font,
+        title_scale,
+        title,
+        height as f32 / 4.0,
+        max_line_width,
+    );
+    let authors_scale = PxScale::from(width as f32 / 20.0);
+    let authors_lines = wrap_text(&font, authors_scale, authors, max_line_width).len() as f32;
+    let authors_top =
+        height as f32 - margin - authors_lines * font.as_scaled(authors_scale).height();
+    draw_text_block(
+        &mut cover,
+        &font,
+        authors_scale,
+        authors,
+        authors_top,
+        max_line_width,
+    );
+
+    let mut png = Cursor::new(Vec::new());
+    cover
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| Error::CoverGenerate(format!("Unable to encode the cover image: {e}")))?;
+    Ok(png.into_inner())
+}
+
+// Draw text wrapped into horizontally centered lines, starting at 'top'
+fn draw_text_block(
+    image: &mut RgbImage,
+    font: &FontRef,
+    scale: PxScale,
+    text: &str,
+    top: f32,
+    max_line_width: f32,
+) {
+    let scaled = font.as_scaled(scale);
+    let mut baseline = top + scaled.ascent();
+    for line in wrap_text(font, scale, text, max_line_width) {
+        let left = (image.width() as f32 - line_width(font, scale, &line)) / 2.0;
+        draw_line(image, font, scale, &line, left, baseline);
+        baseline += scaled.height() + scaled.line_gap();
+    }
+}
+
+fn draw_line(
+    image: &mut RgbImage,
+    font: &FontRef,
+    scale: PxScale,
+    line: &str,
+    left: f32,
+    baseline: f32,
+) {
+    let scaled = font.as_scaled(scale);
+    let mut caret = left;
+    let mut previous = None;
+    for c in line.chars() {
+        let glyph_id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, glyph_id);
+        }
+        let glyph = glyph_id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled.h_advance(glyph_id);
+        previous = Some(glyph_id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue; // e.g. whitespace
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, coverage| {
+            let x = bounds.min.x as i64 + x as i64;
+            let y = bounds.min.y as i64 + y as i64;
+            if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+                return;
+            }
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            for (channel, fg) in pixel.0.iter_mut().zip(FOREGROUND.0) {
+                *channel = (*channel as f32 * (1.0 - coverage) + fg as f32 * coverage) as u8;
+            }
+        });
+    }
+}
+
+fn line_width(font: &FontRef, scale: PxScale, line: &str) -> f32 {
+    let scaled = font.as_scaled(scale);
+    line.chars()
+        .map(|c| scaled.h_advance(scaled.glyph_id(c)))
+        .sum()
+}
+
+// Greedy word wrapping, a single word longer than the line is kept as is
+fn wrap_text(font: &FontRef, scale: PxScale, text: &str, max_line_width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{current} {word}")
+        };
+        if current.is_empty() || line_width(font, scale, &candidate) <= max_line_width {
+            current = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_cover_is_png_of_requested_size() {
+        let png = generate_cover("A Rather Long Book Title", "Ferris", 400, 640).unwrap();
+        let cover = image::load_from_memory_with_format(&png, ImageFormat::Png)
+            .unwrap()
+            .to_rgb8();
+        assert_eq!((400, 640), cover.dimensions());
+        // background in the corner, some text was drawn
+        assert_eq!(&BACKGROUND, cover.get_pixel(0, 0));
+        assert!(cover.pixels().any(|p| *p == FOREGROUND));
+    }
+
+    #[test]
+    fn wrap_title_into_lines() {
+        let font = FontRef::try_from_slice(COVER_FONT).unwrap();
+        let scale = PxScale::from(40.0);
+        let lines = wrap_text(&font, scale, "The Rust Programming Language", 300.0);
+        assert!(lines.len() > 1);
+        assert_eq!("The Rust Programming Language", lines.join(" "));
+        assert!(wrap_text(&font, scale, "", 300.0).is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::generator::BuildSummary;
+
+/// Machine-readable report of an EPUB build, e.g. for build dashboards, written as JSON by
+/// the `--diagnostics-json` flag.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Diagnostics {
+    /// Chapters added to the EPUB, without the skipped ones.
+    pub chapters: usize,
+    /// Embedded local assets, e.g. images in `src`.
+    pub local_assets: usize,
+    /// Embedded remote assets, which were downloaded.
+    pub remote_assets: usize,
+    /// Size of the EPUB file.
+    pub bytes: u64,
+    /// Duration of the build in milliseconds.
+    pub duration_ms: u64,
+    /// Problems with the book which didn't fail the build, in the order they were found.
+    pub warnings: Vec<Warning>,
+    /// Why the build failed, if it did.
+    pub error: Option<String>,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(summary: BuildSummary, duration: Duration) -> Self {
+        Diagnostics {
+            chapters: summary.chapters,
+            local_assets: summary.local_assets,
+            remote_assets: summary.remote_assets,
+            bytes: summary.bytes,
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            warnings: summary.warnings,
+            error: None,
+        }
+    }
+
+    /// Report of a build which failed with the `error`.
+    pub fn failed(error: impl ToString, duration: Duration) -> Self {
+        Diagnostics {
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            error: Some(error.to_string()),
+            ..Diagnostics::default()
+        }
+    }
+}
+
+/// A problem with the book which didn't fail the build.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+impl Warning {
+    pub(crate) fn new(kind: WarningKind, message: impl Into<String>) -> Self {
+        Warning {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// What kind of problem a [`Warning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningKind {
+    /// The book has no title.
+    MissingTitle,
+    /// A draft chapter, which has no content, isn't included.
+    SkippedDraft,
+    /// A chapter which failed to render isn't included.
+    SkippedChapter,
+    /// Images without an alt text.
+    MissingAltText,
+    /// Links to chapters which aren't part of the book.
+    BrokenLinks,
+    /// An asset outside of the book's source folder isn't embedded.
+    OutsideSrcAsset,
+    /// A missing asset is replaced by the placeholder.
+    MissingAsset,
+    /// A remote asset failed to download.
+    FailedDownload,
+}
+
+/// Log the warning and record it for the build's diagnostics.
+pub(crate) fn warn(warnings: &mut Vec<Warning>, kind: WarningKind, message: String) {
+    warn!("{}", message);
+    warnings.push(Warning::new(kind, message));
+}
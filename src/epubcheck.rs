@@ -0,0 +1,192 @@
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::process::Command;
+
+use crate::errors::Error;
+
+/// Environment variable with the path of epubcheck's JAR, which is run by `java -jar`
+/// instead of an `epubcheck` executable.
+pub const EPUBCHECK_JAR: &str = "EPUBCHECK_JAR";
+
+/// How serious a problem reported by epubcheck is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Fatal,
+    Error,
+    Warning,
+    /// Advice on the usage of EPUB features, only reported with epubcheck's `-u` flag.
+    Usage,
+    Info,
+}
+
+impl Severity {
+    /// Whether the EPUB isn't valid because of the problem.
+    pub fn is_failure(self) -> bool {
+        matches!(self, Severity::Fatal | Severity::Error)
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "FATAL" => Some(Severity::Fatal),
+            "ERROR" => Some(Severity::Error),
+            "WARNING" => Some(Severity::Warning),
+            "USAGE" => Some(Severity::Usage),
+            "INFO" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Fatal => "FATAL",
+            Severity::Error => "ERROR",
+            Severity::Warning => "WARNING",
+            Severity::Usage => "USAGE",
+            Severity::Info => "INFO",
+        })
+    }
+}
+
+/// A problem reported by epubcheck, e.g.
+/// `ERROR(RSC-005): book.epub/OEBPS/chapter_1.html(10,5): Error while parsing file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckMessage {
+    pub severity: Severity,
+    /// epubcheck's identifier of the check, e.g. `RSC-005`.
+    pub id: Option<String>,
+    /// File in the EPUB with the line and column, if the problem is in one.
+    pub location: Option<String>,
+    pub message: String,
+}
+
+impl CheckMessage {
+    // Parse a line of epubcheck's output, the other lines like its summary are skipped
+    fn parse(line: &str) -> Option<Self> {
+        let (label, rest) = line.split_once(':')?;
+        let (severity, id) = match label.split_once('(') {
+            Some((severity, id)) => (severity, Some(id.strip_suffix(')')?.to_string())),
+            None => (label, None),
+        };
+        let severity = Severity::from_label(severity)?;
+        let rest = rest.trim();
+        let (location, message) = match rest.split_once(": ") {
+            Some((location, message)) if location.contains(".epub") || !location.contains(' ') => {
+                (Some(location.to_string()), message)
+            }
+            _ => (None, rest),
+        };
+        Some(CheckMessage {
+            severity,
+            id,
+            location,
+            message: message.trim().to_string(),
+        })
+    }
+}
+
+impl Display for CheckMessage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.severity)?;
+        if let Some(ref id) = self.id {
+            write!(f, "({id})")?;
+        }
+        if let Some(ref location) = self.location {
+            write!(f, " {location}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Validate the EPUB file by running epubcheck, which is either the `epubcheck`
+/// executable or the JAR of the `EPUBCHECK_JAR` environment variable. The reported
+/// problems are returned, the book is valid if there are no failures among them. It
+/// fails if epubcheck can't be run at all.
+pub fn epub_check(path: &Path) -> Result<Vec<CheckMessage>, Error> {
+    debug!("Checking the EPUB '{}' by epubcheck", path.display());
+    let mut cmd = match env::var_os(EPUBCHECK_JAR) {
+        Some(jar) => {
+            debug!("Running epubcheck by 'java -jar {:?}'", jar);
+            let mut cmd = Command::new("java");
+            cmd.arg("-jar").arg(jar);
+            cmd
+        }
+        None => Command::new("epubcheck"),
+    };
+    let output = cmd.arg(path).output().map_err(|e| {
+        Error::EpubCheck(format!(
+            "unable to run epubcheck, install it or set {EPUBCHECK_JAR} to its JAR: {e}"
+        ))
+    })?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    debug!("epubcheck exited with {}", output.status);
+    trace!("epubcheck stdout: {}", stdout);
+    trace!("epubcheck stderr: {}", stderr);
+
+    let messages = parse_output(&format!("{stdout}\n{stderr}"));
+    // e.g. an unknown option or a broken Java installation, which isn't about the book
+    if !output.status.success() && !messages.iter().any(|m| m.severity.is_failure()) {
+        return Err(Error::EpubCheck(stderr.trim().to_string()));
+    }
+    Ok(messages)
+}
+
+fn parse_output(output: &str) -> Vec<CheckMessage> {
+    output.lines().filter_map(CheckMessage::parse).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_epubcheck_output() {
+        let output = "Validating using EPUB version 3.3 rules.\n\
+            ERROR(RSC-005): /tmp/book.epub/OEBPS/chapter_1.html(10,5): Error while parsing file: element \"foo\" not allowed here\n\
+            WARNING(OPF-085): /tmp/book.epub/OEBPS/content.opf(4,40): \"dc:identifier\" value \"x\" is marked as a UUID\n\
+            FATAL(PKG-008): /tmp/book.epub: Unable to read file: zip file is empty\n\
+            \n\
+            Check finished with errors\n\
+            Messages: 1 fatal / 1 error / 1 warning / 0 infos\n";
+        let messages = parse_output(output);
+        assert_eq!(
+            messages,
+            vec![
+                CheckMessage {
+                    severity: Severity::Error,
+                    id: Some("RSC-005".to_string()),
+                    location: Some("/tmp/book.epub/OEBPS/chapter_1.html(10,5)".to_string()),
+                    message: "Error while parsing file: element \"foo\" not allowed here"
+                        .to_string(),
+                },
+                CheckMessage {
+                    severity: Severity::Warning,
+                    id: Some("OPF-085".to_string()),
+                    location: Some("/tmp/book.epub/OEBPS/content.opf(4,40)".to_string()),
+                    message: "\"dc:identifier\" value \"x\" is marked as a UUID".to_string(),
+                },
+                CheckMessage {
+                    severity: Severity::Fatal,
+                    id: Some("PKG-008".to_string()),
+                    location: Some("/tmp/book.epub".to_string()),
+                    message: "Unable to read file: zip file is empty".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            messages[0].to_string(),
+            "ERROR(RSC-005) /tmp/book.epub/OEBPS/chapter_1.html(10,5): Error while parsing file: element \"foo\" not allowed here"
+        );
+        assert!(messages[0].severity.is_failure());
+        assert!(!messages[1].severity.is_failure());
+
+        let message = CheckMessage::parse("ERROR: Unable to find the file").unwrap();
+        assert_eq!(message.id, None);
+        assert_eq!(message.location, None);
+        assert_eq!(message.to_string(), "ERROR: Unable to find the file");
+        assert!(parse_output("No errors or warnings detected.\n").is_empty());
+    }
+}
@@ -9,6 +9,13 @@ pub enum Error {
     #[error("{0}")]
     EpubDocCreate(String),
 
+    #[error("Unable to open the generated EPUB {path:?}: {source}")]
+    EpubDocOpen {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
     #[error("Could not parse the template")]
     TemplateParse,
 
@@ -21,14 +28,43 @@ pub enum Error {
     #[error("Asset was not a file {0}")]
     AssetFile(PathBuf),
 
+    #[error("{} assets are missing:\n  {}", .0.len(), .0.join("\n  "))]
+    MissingAssets(Vec<String>),
+
+    #[error("{} links are broken:\n  {}", .0.len(), .0.join("\n  "))]
+    BrokenLinks(Vec<String>),
+
+    #[error("Asset link '{0}' resolves outside of the book root")]
+    PathTraversal(String),
+
+    #[error("Asset link '{0}' is relative to a Windows drive, use a path relative to the chapter instead")]
+    DriveRelativeLink(String),
+
+    #[error("{kind} {path:?} was not found, tried {tried:?}")]
+    ResourceNotFound {
+        /// What the file is for, e.g. `Cover image`.
+        kind: String,
+        path: PathBuf,
+        /// Paths the file was looked up by.
+        tried: Vec<PathBuf>,
+    },
+
     #[error("Could not open css file {0}")]
     CssOpen(PathBuf),
 
     #[error("Unable to open template {0}")]
     OpenTemplate(PathBuf),
 
-    #[error("Unable to parse render context")]
-    RenderContext,
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Unable to parse render context at '{path}': {source}")]
+    RenderContext {
+        /// Path of the offending field, e.g. `$.config.output.epub.cover-image`.
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
 
     #[error("Unable to open asset")]
     AssetOpen,
@@ -36,6 +72,15 @@ pub enum Error {
     #[error("Error reading stylesheet")]
     StylesheetRead,
 
+    #[error("Unable to render mermaid diagram: {0}")]
+    MermaidRender(String),
+
+    #[error("Unable to generate cover image: {0}")]
+    CoverGenerate(String),
+
+    #[error("EPUB generation exceeded the timeout of {0} seconds")]
+    Timeout(u64),
+
     #[error("epubcheck has failed: {0}")]
     EpubCheck(String),
 
@@ -54,6 +99,8 @@ pub enum Error {
     #[error(transparent)]
     Render(#[from] handlebars::RenderError),
     #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
     TomlDeser(#[from] toml::de::Error),
     #[error(transparent)]
     HttpError(#[from] Box<ureq::Error>),
@@ -1,3 +1,13 @@
+pub(crate) mod admonition;
+pub(crate) mod alt_text;
 pub(crate) mod asset_link;
+pub(crate) mod caption_numbering;
+pub(crate) mod code_block;
+pub(crate) mod details;
+pub(crate) mod figure;
 pub(crate) mod footnote;
+pub(crate) mod hidden_lines;
+pub(crate) mod internal_link;
+pub(crate) mod playground;
 pub(crate) mod quote_converter;
+pub(crate) mod task_list;
@@ -0,0 +1,160 @@
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+/// Kinds of GitHub's alerts with their titles, e.g. `> [!NOTE]`.
+const KINDS: &[(&str, &str)] = &[
+    ("note", "Note"),
+    ("tip", "Tip"),
+    ("important", "Important"),
+    ("warning", "Warning"),
+    ("caution", "Caution"),
+];
+
+/// Renders GitHub's alert block quotes, which start with a `[!NOTE]` line, as styled
+/// `<div class="admonition note">` blocks with a title, instead of plain block quotes.
+pub(crate) struct AdmonitionFilter<'a> {
+    enabled: bool,
+    /// Events of a block quote's start, until it's known whether it's an alert.
+    pending: Option<Vec<Event<'a>>>,
+    /// Whether each of the open block quotes is an alert, the innermost one last.
+    quotes: Vec<bool>,
+}
+
+impl<'a> AdmonitionFilter<'a> {
+    pub(crate) fn new(enabled: bool) -> Self {
+        AdmonitionFilter {
+            enabled,
+            pending: None,
+            quotes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn apply(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        if !self.enabled {
+            return vec![event];
+        }
+        let Some(mut pending) = self.pending.take() else {
+            return match event {
+                Event::Start(Tag::BlockQuote) => {
+                    self.pending = Some(vec![event]);
+                    Vec::new()
+                }
+                Event::End(TagEnd::BlockQuote) if self.quotes.pop() == Some(true) => {
+                    vec![Event::Html(CowStr::from("</div>\n"))]
+                }
+                _ => vec![event],
+            };
+        };
+        match event {
+            Event::Start(Tag::Paragraph) if pending.len() == 1 => {
+                pending.push(event);
+                self.pending = Some(pending);
+                Vec::new()
+            }
+            Event::Text(_) if pending.len() > 1 => {
+                pending.push(event);
+                self.pending = Some(pending);
+                Vec::new()
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::Paragraph)
+                if pending.len() > 2 =>
+            {
+                match Self::alert_kind(&pending[2..]) {
+                    Some((kind, title)) => {
+                        self.quotes.push(true);
+                        let mut events = vec![Event::Html(CowStr::from(format!(
+                            "<div class=\"admonition {kind}\">\n\
+                            <p class=\"admonition-title\">{title}</p>\n"
+                        )))];
+                        // the text after the marker line stays in the paragraph
+                        if !matches!(event, Event::End(TagEnd::Paragraph)) {
+                            events.push(Event::Start(Tag::Paragraph));
+                        }
+                        events
+                    }
+                    None => self.not_an_alert(pending, event),
+                }
+            }
+            _ => self.not_an_alert(pending, event),
+        }
+    }
+
+    // Pass on the block quote's events, a nested block quote may be an alert on its own
+    fn not_an_alert(&mut self, mut pending: Vec<Event<'a>>, event: Event<'a>) -> Vec<Event<'a>> {
+        self.quotes.push(false);
+        if matches!(event, Event::Start(Tag::BlockQuote)) {
+            self.pending = Some(vec![event]);
+        } else {
+            pending.extend(self.apply(event));
+        }
+        pending
+    }
+
+    // The kind and title of the alert by the text of its first line, e.g. `[!TIP]`
+    fn alert_kind(first_line: &[Event<'_>]) -> Option<(&'static str, &'static str)> {
+        let mut text = String::new();
+        for event in first_line {
+            if let Event::Text(ref t) = event {
+                text.push_str(t);
+            }
+        }
+        let kind = text.trim().strip_prefix("[!")?.strip_suffix(']')?;
+        KINDS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(kind))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    fn render(markdown: &str, enabled: bool) -> String {
+        let mut filter = AdmonitionFilter::new(enabled);
+        let events =
+            utils::create_new_pull_down_parser(markdown).flat_map(|event| filter.apply(event));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    #[test]
+    fn alerts_rendered_as_admonitions() {
+        assert_eq!(
+            render("> [!NOTE]\n> Read *this*.\n", true),
+            "<div class=\"admonition note\">\n<p class=\"admonition-title\">Note</p>\n\
+            <p>Read <em>this</em>.</p>\n</div>\n"
+        );
+        assert_eq!(
+            render("> [!warning]\n>\n> First\n>\n> Second\n", true),
+            "<div class=\"admonition warning\">\n<p class=\"admonition-title\">Warning</p>\n\
+            <p>First</p>\n<p>Second</p>\n</div>\n"
+        );
+        // a nested alert in a plain block quote
+        assert_eq!(
+            render("> Quote\n>\n> > [!TIP]\n> > Nested\n", true),
+            "<blockquote>\n<p>Quote</p>\n<div class=\"admonition tip\">\n\
+            <p class=\"admonition-title\">Tip</p>\n<p>Nested</p>\n</div>\n</blockquote>\n"
+        );
+    }
+
+    #[test]
+    fn other_block_quotes_kept() {
+        for markdown in [
+            "> Just a quote\n",
+            "> [!UNKNOWN]\n> Text\n",
+            "> Text [!NOTE]\n",
+            "> > Nested\n",
+            "> - [!NOTE]\n",
+        ] {
+            assert_eq!(
+                render(markdown, true),
+                render(markdown, false),
+                "{markdown}"
+            );
+        }
+        assert!(render("> [!NOTE]\n> Text\n", false).starts_with("<blockquote>"));
+    }
+}
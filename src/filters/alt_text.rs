@@ -0,0 +1,123 @@
+use html_parser::{Dom, Element, Node};
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+/// Finds images without an alt text, which screen readers can't describe. The title of
+/// an image without a description, e.g. `![](img.png "A title")`, is used as its alt text.
+pub(crate) struct AltTextFilter<'a> {
+    enabled: bool,
+    /// Link and title of the image being processed, and whether it has a description.
+    image: Option<(CowStr<'a>, CowStr<'a>, bool)>,
+    missing: Vec<String>,
+}
+
+impl<'a> AltTextFilter<'a> {
+    pub(crate) fn new(enabled: bool) -> Self {
+        AltTextFilter {
+            enabled,
+            image: None,
+            missing: Vec::new(),
+        }
+    }
+
+    pub(crate) fn apply(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        if !self.enabled {
+            return vec![event];
+        }
+        match event {
+            Event::Start(Tag::Image {
+                ref dest_url,
+                ref title,
+                ..
+            }) => {
+                self.image = Some((dest_url.clone(), title.clone(), false));
+                vec![event]
+            }
+            Event::Text(ref text) | Event::Code(ref text) => {
+                if let Some((_, _, ref mut described)) = self.image {
+                    *described |= !text.trim().is_empty();
+                }
+                vec![event]
+            }
+            Event::End(TagEnd::Image) => match self.image.take() {
+                Some((_, title, false)) if !title.trim().is_empty() => {
+                    vec![Event::Text(title), event]
+                }
+                Some((dest_url, _, false)) => {
+                    self.missing.push(dest_url.into_string());
+                    vec![event]
+                }
+                _ => vec![event],
+            },
+            Event::Html(ref html) | Event::InlineHtml(ref html) => {
+                if let Ok(dom) = Dom::parse(html) {
+                    for node in dom.children.iter() {
+                        self.find_html_images(node);
+                    }
+                }
+                vec![event]
+            }
+            _ => vec![event],
+        }
+    }
+
+    /// Links of the images without an alt text, in the order they were found.
+    pub(crate) fn missing(&self) -> &[String] {
+        &self.missing
+    }
+
+    fn find_html_images(&mut self, node: &Node) {
+        let Node::Element(Element {
+            name,
+            attributes,
+            children,
+            ..
+        }) = node
+        else {
+            return;
+        };
+        if name == "img" {
+            let described = attributes
+                .get("alt")
+                .and_then(Option::as_deref)
+                .is_some_and(|alt| !alt.trim().is_empty());
+            if !described {
+                let src = attributes.get("src").cloned().flatten();
+                self.missing.push(src.unwrap_or_default());
+            }
+        }
+        for child in children {
+            self.find_html_images(child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    fn render<'a>(markdown: &'a str, filter: &mut AltTextFilter<'a>) -> String {
+        let events = utils::create_new_pull_down_parser(markdown).flat_map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    #[test]
+    fn images_without_alt_text_found() {
+        let markdown = "![A chart](chart.png) ![](blank.png) ![](titled.png \"Titled\")\n\n\
+            <img src=\"raw.png\">\n\n<p><img src=\"nested.png\" alt=\" \"/></p>\n\n\
+            <img src=\"fine.png\" alt=\"Fine\">\n";
+        let mut filter = AltTextFilter::new(true);
+        let html = render(markdown, &mut filter);
+        assert!(html.contains("<img src=\"chart.png\" alt=\"A chart\" />"));
+        assert!(html.contains("<img src=\"titled.png\" alt=\"Titled\" title=\"Titled\" />"));
+        assert_eq!(filter.missing(), ["blank.png", "raw.png", "nested.png"]);
+
+        let mut filter = AltTextFilter::new(false);
+        let html = render(markdown, &mut filter);
+        assert!(html.contains("<img src=\"titled.png\" alt=\"\" title=\"Titled\" />"));
+        assert!(filter.missing().is_empty());
+    }
+}
@@ -1,28 +1,129 @@
+use crate::config::RemoteFailureMode;
 use crate::resources::asset::Asset;
-use crate::utils::encode_non_ascii_symbols;
+use crate::utils::{
+    encode_non_ascii_symbols, epub_path, escape_html, image_links, is_data_uri, is_drive_relative,
+    native_separators, normalize_path, root_relative_link,
+};
 use html_parser::{Dom, Node};
-use pulldown_cmark::{CowStr, Event, Tag};
+use mime_guess::Mime;
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
 use std::collections::HashMap;
-use std::ffi::OsString;
 use std::iter;
 use std::path::Path;
 use url::Url;
 
+/// File name of the placeholder shown for remote images which failed to download.
+pub(crate) const REMOTE_PLACEHOLDER: &str = "remote-placeholder.svg";
+
+/// The "Image unavailable" placeholder image.
+pub(crate) const REMOTE_PLACEHOLDER_SVG: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+    width=\"320\" height=\"180\" viewBox=\"0 0 320 180\">\n\
+    <rect width=\"320\" height=\"180\" fill=\"#eee\" stroke=\"#999\" stroke-dasharray=\"8 4\"/>\n\
+    <text x=\"160\" y=\"96\" font-family=\"sans-serif\" font-size=\"18\" fill=\"#666\" \
+    text-anchor=\"middle\">Image unavailable</text>\n</svg>\n";
+
 /// Filter is used for replacing remote urls with local images downloaded from internet
+/// and links to assets from additional asset roots with their embedded file names
 pub struct AssetRemoteLinkFilter<'a> {
-    // Keeps pairs: 'remote url' | 'asset'
+    // Keeps pairs: 'remote url' or 'original link' | 'asset'
     assets: &'a HashMap<String, Asset>,
     depth: usize,
+    /// `data:` URIs of inlined images by their path in `src`, and the chapter's folder
+    /// in `src` their links are relative to.
+    inlined: Option<(&'a HashMap<String, String>, &'a Path)>,
+    /// Media types of the remote images which failed to download by their URLs, and how
+    /// they're rendered.
+    failed: Option<(&'a HashMap<String, Mime>, RemoteFailureMode)>,
+    /// The image being processed is wrapped into an `<object>` with the placeholder.
+    in_fallback: bool,
+    /// Links with a leading `/` are relative to `src` and rewritten relative to the chapter.
+    root_relative: bool,
 }
 
 impl<'a> AssetRemoteLinkFilter<'a> {
     pub(crate) fn new(assets: &'a HashMap<String, Asset>, depth: usize) -> Self {
-        Self { assets, depth }
+        Self {
+            assets,
+            depth,
+            inlined: None,
+            failed: None,
+            in_fallback: false,
+            root_relative: false,
+        }
+    }
+
+    /// Rewrite the links with a leading `/`, which are relative to `src`, relative to the
+    /// chapter, if `root-relative-links` is enabled.
+    pub(crate) fn with_root_relative(mut self, root_relative: bool) -> Self {
+        self.root_relative = root_relative;
+        self
+    }
+
+    /// Render the links to the remote images which failed to download by the `mode`.
+    pub(crate) fn with_failed(
+        mut self,
+        failed: &'a HashMap<String, Mime>,
+        mode: RemoteFailureMode,
+    ) -> Self {
+        if !failed.is_empty() {
+            self.failed = Some((failed, mode));
+        }
+        self
+    }
+
+    /// Replace links to the inlined images of the chapter in the `chapter_dir` by their
+    /// `data:` URIs.
+    pub(crate) fn with_inlined(
+        mut self,
+        inlined: &'a HashMap<String, String>,
+        chapter_dir: &'a Path,
+    ) -> Self {
+        if !inlined.is_empty() {
+            self.inlined = Some((inlined, chapter_dir));
+        }
+        self
     }
 
     /// Do processing of chapter's content and replace 'remote link' by 'local file name'
-    pub(crate) fn apply(&self, event: Event<'a>) -> Event<'a> {
+    pub(crate) fn apply(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
         // trace!("AssetLinkFilter: Processing Event = {:?}", &event);
+        if let Event::Start(Tag::Image {
+            link_type,
+            ref dest_url,
+            ref title,
+            ref id,
+        }) = event
+        {
+            if let Some((mimetype, mode)) = self.failed_download(dest_url) {
+                let placeholder = Event::Start(Tag::Image {
+                    link_type,
+                    dest_url: CowStr::from(self.path_prefix(Path::new(REMOTE_PLACEHOLDER))),
+                    title: title.to_owned(),
+                    id: id.to_owned(),
+                });
+                return match mode {
+                    RemoteFailureMode::EmbedPlaceholder => vec![placeholder],
+                    RemoteFailureMode::Both => {
+                        self.in_fallback = true;
+                        let object = format!(
+                            "<object data=\"{}\" type=\"{}\">",
+                            escape_html(dest_url),
+                            mimetype
+                        );
+                        vec![Event::InlineHtml(CowStr::from(object)), placeholder]
+                    }
+                    RemoteFailureMode::KeepUrl | RemoteFailureMode::Fail => vec![event],
+                };
+            }
+        }
+        if self.in_fallback && event == Event::End(TagEnd::Image) {
+            self.in_fallback = false;
+            return vec![event, Event::InlineHtml(CowStr::from("</object>"))];
+        }
+        vec![self.apply_links(event)]
+    }
+
+    fn apply_links(&self, event: Event<'a>) -> Event<'a> {
         match event {
             Event::Start(Tag::Image {
                 link_type,
@@ -40,6 +141,22 @@ impl<'a> AssetRemoteLinkFilter<'a> {
                         title: title.to_owned(),
                         id: id.to_owned(),
                     })
+                } else if let Some(data_uri) = self.inlined_image(dest_url) {
+                    debug!("Inlined image '{}'", &dest_url);
+                    Event::Start(Tag::Image {
+                        link_type,
+                        dest_url: CowStr::from(data_uri),
+                        title: title.to_owned(),
+                        id: id.to_owned(),
+                    })
+                } else if let Some(relative) = self.root_relative_link(dest_url) {
+                    debug!("Root relative image '{}'", &dest_url);
+                    Event::Start(Tag::Image {
+                        link_type,
+                        dest_url: CowStr::from(self.path_prefix(Path::new(relative))),
+                        title: title.to_owned(),
+                        id: id.to_owned(),
+                    })
                 } else {
                     event
                 }
@@ -47,17 +164,26 @@ impl<'a> AssetRemoteLinkFilter<'a> {
             Event::Html(ref html) => {
                 let mut found = Vec::new();
                 if let Ok(dom) = Dom::parse(&html.clone().into_string()) {
-                    for item in dom.children {
-                        match item {
-                            Node::Element(ref element) if element.name == "img" => {
-                                if let Some(dest) = &element.attributes["src"] {
-                                    if Url::parse(dest).is_ok() {
-                                        debug!("Found a valid remote img src:\"{}\".", dest);
-                                        found.push(dest.to_owned());
-                                    }
-                                }
-                            }
-                            _ => {}
+                    // 'srcset' candidates of images and of '<source>'s in a '<picture>' too
+                    let links = dom.children.iter().flat_map(|item| match item {
+                        Node::Element(ref element) => image_links(element),
+                        _ => Vec::new(),
+                    });
+                    for dest in links {
+                        if is_data_uri(&dest) {
+                            trace!("Inline data URI img src is left as is.");
+                        } else if Url::parse(&dest).is_ok() && !is_drive_relative(&dest) {
+                            debug!("Found a valid remote img src:\"{}\".", dest);
+                            found.push(dest);
+                        } else if self.assets.contains_key(&dest) {
+                            debug!("Found an external img src:\"{}\".", dest);
+                            found.push(dest);
+                        } else if self.inlined_image(&dest).is_some() {
+                            debug!("Found an inlined img src:\"{}\".", dest);
+                            found.push(dest);
+                        } else if self.root_relative_link(&dest).is_some() {
+                            debug!("Found a root relative img src:\"{}\".", dest);
+                            found.push(dest);
                         }
                     }
                 }
@@ -77,12 +203,37 @@ impl<'a> AssetRemoteLinkFilter<'a> {
                             // REAL SRC REPLACING happens here...
                             content = content.replace(&link, new.as_str());
                             trace!("new content after replacement\n{}", &content);
+                        } else if let Some(data_uri) = self.inlined_image(&link) {
+                            content = content.replace(&link, data_uri);
+                        } else if let Some(relative) = self.root_relative_link(&link) {
+                            // only whole attribute values, as the link is part of the new one
+                            let new = self.path_prefix(Path::new(relative));
+                            for quote in ['"', '\''] {
+                                content = content.replace(
+                                    &format!("{quote}{link}{quote}"),
+                                    &format!("{quote}{new}{quote}"),
+                                );
+                            }
+                        } else if let Some((mimetype, mode)) =
+                            self.failed_download(&encoded_link_key)
+                        {
+                            let placeholder = self.path_prefix(Path::new(REMOTE_PLACEHOLDER));
+                            content = match mode {
+                                RemoteFailureMode::EmbedPlaceholder => {
+                                    content.replace(&link, &placeholder)
+                                }
+                                RemoteFailureMode::Both => {
+                                    wrap_in_object(&content, &link, &placeholder, mimetype)
+                                }
+                                RemoteFailureMode::KeepUrl | RemoteFailureMode::Fail => content,
+                            };
                         } else {
-                            error!(
-                                "Asset was not found by encoded_link key: {}",
+                            // e.g. a remote image of the single XHTML document, which
+                            // isn't downloaded
+                            debug!(
+                                "Asset was not found by encoded_link key: {}, it's left as is",
                                 encoded_link_key
                             );
-                            unreachable!("{link} should be replaced, but it doesn't.");
                         }
                     }
                     Event::Html(CowStr::from(content))
@@ -92,18 +243,37 @@ impl<'a> AssetRemoteLinkFilter<'a> {
         }
     }
 
+    // Media type of a remote image which failed to download, and how it's rendered
+    fn failed_download(&self, link: &str) -> Option<(&'a Mime, RemoteFailureMode)> {
+        let (failed, mode) = self.failed?;
+        failed.get(link).map(|mimetype| (mimetype, mode))
+    }
+
+    // Inlined image of a local link, which is relative to the chapter
+    fn inlined_image(&self, link: &str) -> Option<&'a str> {
+        let (inlined, chapter_dir) = self.inlined?;
+        if Url::parse(link).is_ok() && !is_drive_relative(link) {
+            return None;
+        }
+        let path = match self.root_relative_link(link) {
+            Some(relative) => normalize_path(Path::new(&native_separators(relative))),
+            None => normalize_path(&chapter_dir.join(native_separators(link))),
+        };
+        inlined.get(&epub_path(&path)).map(String::as_str)
+    }
+
+    // The link relative to `src` without its leading '/', if root relative links are enabled
+    fn root_relative_link<'l>(&self, link: &'l str) -> Option<&'l str> {
+        if self.root_relative {
+            root_relative_link(link)
+        } else {
+            None
+        }
+    }
+
     fn path_prefix(&self, path: &Path) -> String {
         // compatible to Windows, translate to forward slash in file path.
-        let mut fsp = OsString::new();
-        for (i, component) in path.components().enumerate() {
-            if i > 0 {
-                fsp.push("/");
-            }
-            fsp.push(component);
-        }
-        let filename = fsp
-            .into_string()
-            .unwrap_or_else(|orig| orig.to_string_lossy().to_string());
+        let filename = epub_path(path);
         (0..self.depth)
             .map(|_| "..")
             .chain(iter::once(filename.as_str()))
@@ -111,3 +281,30 @@ impl<'a> AssetRemoteLinkFilter<'a> {
             .join("/")
     }
 }
+
+/// Wrap the `<img>` tags with the link into an `<object>` of the link, which falls back
+/// to the image with the link replaced by the placeholder if it can't be loaded.
+fn wrap_in_object(html: &str, link: &str, placeholder: &str, mimetype: &Mime) -> String {
+    let mut result = String::with_capacity(html.len() * 2);
+    let mut rest = html;
+    while let Some(start) = rest.find("<img") {
+        let Some(end) = rest[start..].find('>').map(|end| start + end + 1) else {
+            break;
+        };
+        let tag = &rest[start..end];
+        result.push_str(&rest[..start]);
+        if tag.contains(link) {
+            result.push_str(&format!(
+                "<object data=\"{}\" type=\"{}\">{}</object>",
+                escape_html(link),
+                mimetype,
+                tag.replace(link, placeholder)
+            ));
+        } else {
+            result.push_str(tag);
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
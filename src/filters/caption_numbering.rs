@@ -0,0 +1,111 @@
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+use crate::utils;
+
+/// Numbers of the last figure and table, carried over from chapter to chapter.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct CaptionNumbers {
+    pub(crate) figures: usize,
+    pub(crate) tables: usize,
+}
+
+/// Numbers figures (images with a title, e.g. `![alt](image.png "Caption")`) and tables
+/// sequentially across the book. Every figure gets a `Figure N: Caption` caption and every
+/// table a `Table N` caption, with `figure-N` and `table-N` ids to link to.
+pub(crate) struct CaptionNumberingFilter {
+    enabled: bool,
+    numbers: CaptionNumbers,
+    /// Caption of the figure being processed, emitted after the image.
+    figure_caption: Option<String>,
+}
+
+impl CaptionNumberingFilter {
+    pub(crate) fn new(enabled: bool, numbers: CaptionNumbers) -> Self {
+        CaptionNumberingFilter {
+            enabled,
+            numbers,
+            figure_caption: None,
+        }
+    }
+
+    /// Numbers including the figures and tables seen so far.
+    pub(crate) fn numbers(&self) -> CaptionNumbers {
+        self.numbers
+    }
+
+    pub(crate) fn apply<'a>(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        if !self.enabled {
+            return vec![event];
+        }
+        match event {
+            Event::Start(Tag::Image { ref title, .. }) if !title.is_empty() => {
+                self.numbers.figures += 1;
+                let n = self.numbers.figures;
+                let title = utils::escape_html(title);
+                self.figure_caption = Some(format!(
+                    "<span class=\"caption\" id=\"figure-{n}\">Figure {n}: {title}</span>"
+                ));
+                vec![event]
+            }
+            Event::End(TagEnd::Image) => match self.figure_caption.take() {
+                Some(caption) => vec![event, Event::InlineHtml(CowStr::from(caption))],
+                None => vec![event],
+            },
+            Event::Start(Tag::Table(_)) => {
+                self.numbers.tables += 1;
+                let n = self.numbers.tables;
+                let caption =
+                    format!("<p class=\"table-caption\" id=\"table-{n}\">Table {n}</p>\n");
+                vec![Event::Html(CowStr::from(caption)), event]
+            }
+            _ => vec![event],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::html;
+
+    fn render(markdown: &str, filter: &mut CaptionNumberingFilter) -> String {
+        let events = utils::create_new_pull_down_parser(markdown).flat_map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    #[test]
+    fn number_figures_and_tables() {
+        let mut filter = CaptionNumberingFilter::new(true, CaptionNumbers::default());
+        let html = render(
+            "![Logo](logo.png \"Rust logo\")\n\n![No caption](plain.png)\n\n\
+            | a | b |\n|---|---|\n| 1 | 2 |\n",
+            &mut filter,
+        );
+        assert!(html.contains(
+            "<img src=\"logo.png\" alt=\"Logo\" title=\"Rust logo\" />\
+            <span class=\"caption\" id=\"figure-1\">Figure 1: Rust logo</span>"
+        ));
+        assert!(html.contains("<p><img src=\"plain.png\" alt=\"No caption\" /></p>"));
+        assert!(html.contains("<p class=\"table-caption\" id=\"table-1\">Table 1</p>\n<table>"));
+        assert_eq!(
+            CaptionNumbers {
+                figures: 1,
+                tables: 1
+            },
+            filter.numbers()
+        );
+    }
+
+    #[test]
+    fn disabled_filter_keeps_content() {
+        let mut filter = CaptionNumberingFilter::new(false, CaptionNumbers::default());
+        let html = render("![Logo](logo.png \"Rust logo\")\n", &mut filter);
+        assert_eq!(
+            "<p><img src=\"logo.png\" alt=\"Logo\" title=\"Rust logo\" /></p>\n",
+            html
+        );
+        assert_eq!(CaptionNumbers::default(), filter.numbers());
+    }
+}
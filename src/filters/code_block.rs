@@ -0,0 +1,405 @@
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag, TagEnd};
+use std::collections::HashMap;
+use std::iter;
+
+use crate::config::CodeBlockOverflow;
+use crate::{math, mermaid};
+
+/// Line height of code blocks in the default stylesheet in tenths of `em`, used to
+/// compute the scroll container's height.
+const CODE_LINE_HEIGHT_TENTHS_EM: usize = 12;
+
+/// Renderer of code blocks which are not shown as code, chosen by the language token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LanguageRenderer {
+    /// LaTeX formula rendered as MathML.
+    Math,
+    /// Mermaid diagram rendered into an embedded SVG image by an external command.
+    Mermaid,
+}
+
+/// Renders code blocks of special languages (e.g. ```` ```math ````) with their renderer.
+/// Wraps other code blocks longer than the configured number of lines into a scrollable
+/// container, or truncates them with a note. Optionally groups a code block with the
+/// previous one of the same language by wrapping it into a `code-continued` container.
+pub(crate) struct CodeBlockFilter<'a> {
+    max_lines: Option<usize>,
+    overflow: CodeBlockOverflow,
+    math: bool,
+    mermaid_command: Option<String>,
+    /// Depth of the chapter, to link the diagrams relative to it.
+    chapter_depth: usize,
+    /// Rendered diagrams by their embedded file name.
+    diagrams: HashMap<String, Vec<u8>>,
+    /// Events of the code block currently being processed.
+    code_block: Option<Vec<Event<'a>>>,
+    group_blocks: bool,
+    /// Language of the code block which was the last event, if any.
+    previous_language: Option<String>,
+    /// Language of the code block currently being processed, and whether it continues
+    /// the previous one.
+    current_language: Option<(String, bool)>,
+}
+
+impl<'a> CodeBlockFilter<'a> {
+    pub(crate) fn new(max_lines: Option<usize>, overflow: CodeBlockOverflow, math: bool) -> Self {
+        CodeBlockFilter {
+            max_lines,
+            overflow,
+            math,
+            mermaid_command: None,
+            chapter_depth: 0,
+            diagrams: HashMap::new(),
+            code_block: None,
+            group_blocks: false,
+            previous_language: None,
+            current_language: None,
+        }
+    }
+
+    /// Group consecutive code blocks of the same language, which are not separated by any
+    /// other content.
+    pub(crate) fn with_grouping(mut self, group_blocks: bool) -> Self {
+        self.group_blocks = group_blocks;
+        self
+    }
+
+    /// Render ```` ```mermaid ```` code blocks by the `command` into SVG images, which are
+    /// linked relative to a chapter of the `chapter_depth`.
+    pub(crate) fn with_mermaid(mut self, command: Option<String>, chapter_depth: usize) -> Self {
+        self.mermaid_command = command;
+        self.chapter_depth = chapter_depth;
+        self
+    }
+
+    /// Diagrams rendered so far by their file name, which should be embedded into the book.
+    pub(crate) fn take_diagrams(&mut self) -> HashMap<String, Vec<u8>> {
+        std::mem::take(&mut self.diagrams)
+    }
+
+    pub(crate) fn apply(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        if !self.group_blocks {
+            return self.process(event);
+        }
+        let previous_language = self.previous_language.take();
+        match event {
+            Event::Start(Tag::CodeBlock(ref kind)) => {
+                self.current_language = match Self::language(kind) {
+                    Some(language) if self.renderer(kind).is_none() => {
+                        let continued = previous_language.as_deref() == Some(language);
+                        Some((language.to_string(), continued))
+                    }
+                    _ => None,
+                };
+                let mut events = self.process(event);
+                if let Some((_, true)) = self.current_language {
+                    events.insert(
+                        0,
+                        Event::Html(CowStr::from("<div class=\"code-continued\">\n")),
+                    );
+                }
+                events
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                let mut events = self.process(event);
+                if let Some((language, continued)) = self.current_language.take() {
+                    if continued {
+                        events.push(Event::Html(CowStr::from("</div>\n")));
+                    }
+                    self.previous_language = Some(language);
+                }
+                events
+            }
+            _ => self.process(event),
+        }
+    }
+
+    fn process(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        match event {
+            Event::Start(Tag::CodeBlock(ref kind))
+                if self.max_lines.is_some() || self.renderer(kind).is_some() =>
+            {
+                self.code_block = Some(vec![event]);
+                vec![]
+            }
+            Event::End(TagEnd::CodeBlock) => match self.code_block.take() {
+                Some(mut events) => {
+                    events.push(event);
+                    self.finish_code_block(events)
+                }
+                None => vec![event],
+            },
+            _ => match self.code_block {
+                Some(ref mut events) => {
+                    events.push(event);
+                    vec![]
+                }
+                None => vec![event],
+            },
+        }
+    }
+
+    // The language token is the first word of the info string, e.g. "math" of "math,ignore"
+    fn language<'k>(kind: &'k CodeBlockKind) -> Option<&'k str> {
+        let CodeBlockKind::Fenced(info) = kind else {
+            return None;
+        };
+        info.split(|c: char| c == ',' || c.is_whitespace())
+            .next()
+            .filter(|language| !language.is_empty())
+    }
+
+    fn renderer(&self, kind: &CodeBlockKind) -> Option<LanguageRenderer> {
+        match Self::language(kind).unwrap_or_default() {
+            "math" if self.math => Some(LanguageRenderer::Math),
+            "mermaid" if self.mermaid_command.is_some() => Some(LanguageRenderer::Mermaid),
+            _ => None,
+        }
+    }
+
+    fn finish_code_block(&mut self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let code: String = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) => Some(text.as_ref()),
+                _ => None,
+            })
+            .collect();
+
+        let renderer = match events.first() {
+            Some(Event::Start(Tag::CodeBlock(kind))) => self.renderer(kind),
+            _ => None,
+        };
+        match renderer {
+            Some(LanguageRenderer::Math) => {
+                let mathml = math::render_mathml(&code);
+                return vec![Event::Html(CowStr::from(format!(
+                    "<div class=\"math\">{mathml}</div>\n"
+                )))];
+            }
+            Some(LanguageRenderer::Mermaid) => {
+                if let Some(diagram) = self.render_diagram(&code) {
+                    return vec![Event::Html(CowStr::from(diagram))];
+                }
+            }
+            None => {}
+        }
+
+        let Some(max_lines) = self.max_lines else {
+            return events;
+        };
+        let line_count = code.lines().count();
+        if line_count <= max_lines {
+            return events;
+        }
+        debug!(
+            "Code block has {} lines, more than {}: {:?}",
+            line_count, max_lines, self.overflow
+        );
+
+        match self.overflow {
+            CodeBlockOverflow::Scroll => {
+                let max_height = max_lines * CODE_LINE_HEIGHT_TENTHS_EM;
+                let mut wrapped = Vec::with_capacity(events.len() + 2);
+                wrapped.push(Event::Html(CowStr::from(format!(
+                    "<div class=\"code-scroll\" style=\"max-height: {}.{}em;\">\n",
+                    max_height / 10,
+                    max_height % 10
+                ))));
+                wrapped.extend(events);
+                wrapped.push(Event::Html(CowStr::from("</div>\n")));
+                wrapped
+            }
+            CodeBlockOverflow::Truncate => {
+                let mut truncated: String =
+                    code.lines().take(max_lines).collect::<Vec<_>>().join("\n");
+                truncated.push('\n');
+                let omitted = line_count - max_lines;
+                let mut events = events.into_iter();
+                let start = events.next();
+                let end = events.last();
+                start
+                    .into_iter()
+                    .chain(iter::once(Event::Text(CowStr::from(truncated))))
+                    .chain(end)
+                    .chain(iter::once(Event::Html(CowStr::from(format!(
+                        "<p class=\"code-truncated\">({omitted} more lines not shown)</p>\n"
+                    )))))
+                    .collect()
+            }
+        }
+    }
+
+    // Image of the rendered diagram, None (keeping the code block) if it can't be rendered
+    fn render_diagram(&mut self, source: &str) -> Option<String> {
+        let command = self.mermaid_command.as_deref()?;
+        let filename = mermaid::diagram_filename(source);
+        if !self.diagrams.contains_key(&filename) {
+            match mermaid::render_svg(command, source) {
+                Ok(svg) => {
+                    self.diagrams.insert(filename.clone(), svg);
+                }
+                Err(e) => {
+                    warn!("Mermaid diagram is kept as code: {}", e);
+                    return None;
+                }
+            }
+        }
+        let link = format!("{}{filename}", "../".repeat(self.chapter_depth));
+        Some(format!(
+            "<div class=\"mermaid-diagram\"><img src=\"{link}\" alt=\"Diagram\" /></div>\n"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    fn render(markdown: &str, max_lines: Option<usize>, overflow: CodeBlockOverflow) -> String {
+        render_with_filter(markdown, CodeBlockFilter::new(max_lines, overflow, false))
+    }
+
+    fn render_with_filter<'a>(markdown: &'a str, mut filter: CodeBlockFilter<'a>) -> String {
+        let events = utils::create_new_pull_down_parser(markdown).flat_map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    const LONG_BLOCK: &str = "```rust\nline 1\nline 2\nline 3\nline 4\n```\n";
+
+    #[test]
+    fn long_code_block_in_scroll_container() {
+        assert_eq!(
+            render(LONG_BLOCK, Some(3), CodeBlockOverflow::Scroll),
+            "<div class=\"code-scroll\" style=\"max-height: 3.6em;\">\n\
+            <pre><code class=\"language-rust\">line 1\nline 2\nline 3\nline 4\n</code></pre>\n\
+            </div>\n"
+        );
+    }
+
+    #[test]
+    fn long_code_block_truncated() {
+        assert_eq!(
+            render(LONG_BLOCK, Some(3), CodeBlockOverflow::Truncate),
+            "<pre><code class=\"language-rust\">line 1\nline 2\nline 3\n</code></pre>\n\
+            <p class=\"code-truncated\">(1 more lines not shown)</p>\n"
+        );
+    }
+
+    #[test]
+    fn short_or_unlimited_code_blocks_unchanged() {
+        let expected =
+            "<pre><code class=\"language-rust\">line 1\nline 2\nline 3\nline 4\n</code></pre>\n";
+        assert_eq!(
+            render(LONG_BLOCK, Some(4), CodeBlockOverflow::Scroll),
+            expected
+        );
+        assert_eq!(
+            render(LONG_BLOCK, None, CodeBlockOverflow::Truncate),
+            expected
+        );
+    }
+
+    #[test]
+    fn math_code_block_rendered_as_mathml() {
+        let markdown = "```math\n\\frac{1}{2}\n```\n\n```mermaid\ngraph TD;\n```\n";
+        assert_eq!(
+            render_with_filter(markdown, CodeBlockFilter::new(Some(1), CodeBlockOverflow::Truncate, true)),
+            "<div class=\"math\"><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">\
+            <semantics><mrow><mfrac><mrow><mn>1</mn></mrow><mrow><mn>2</mn></mrow></mfrac></mrow>\
+            <annotation encoding=\"application/x-tex\">\\frac{1}{2}</annotation></semantics></math></div>\n\
+            <pre><code class=\"language-mermaid\">graph TD;\n</code></pre>\n"
+        );
+        // disabled math is kept as code
+        assert_eq!(
+            render_with_filter(
+                markdown,
+                CodeBlockFilter::new(None, CodeBlockOverflow::Scroll, false)
+            ),
+            "<pre><code class=\"language-math\">\\frac{1}{2}\n</code></pre>\n\
+            <pre><code class=\"language-mermaid\">graph TD;\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn consecutive_code_blocks_of_same_language_grouped() {
+        let markdown = "```rust\nlet a = 1;\n```\n```rust,ignore\nlet b = 2;\n```\n\
+            ```rust\nlet c = 3;\n```\n\n```toml\n[book]\n```\n\n\
+            Text\n\n```toml\n[output.epub]\n```\n";
+        let filter =
+            CodeBlockFilter::new(None, CodeBlockOverflow::Scroll, false).with_grouping(true);
+        assert_eq!(
+            render_with_filter(markdown, filter),
+            "<pre><code class=\"language-rust\">let a = 1;\n</code></pre>\n\
+            <div class=\"code-continued\">\n\
+            <pre><code class=\"language-rust,ignore\">let b = 2;\n</code></pre>\n</div>\n\
+            <div class=\"code-continued\">\n\
+            <pre><code class=\"language-rust\">let c = 3;\n</code></pre>\n</div>\n\
+            <pre><code class=\"language-toml\">[book]\n</code></pre>\n\
+            <p>Text</p>\n\
+            <pre><code class=\"language-toml\">[output.epub]\n</code></pre>\n"
+        );
+
+        // grouped blocks can still be truncated
+        let markdown = "```rust\na\n```\n\n```rust\nb\nc\n```\n";
+        let filter =
+            CodeBlockFilter::new(Some(1), CodeBlockOverflow::Truncate, false).with_grouping(true);
+        assert_eq!(
+            render_with_filter(markdown, filter),
+            "<pre><code class=\"language-rust\">a\n</code></pre>\n\
+            <div class=\"code-continued\">\n\
+            <pre><code class=\"language-rust\">b\n</code></pre>\n\
+            <p class=\"code-truncated\">(1 more lines not shown)</p>\n</div>\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mermaid_code_block_rendered_as_svg() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // fake Mermaid CLI, called as 'mmdc -i <input> -o <output>'
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let mmdc = tmp_dir.path().join("mmdc");
+        std::fs::write(
+            &mmdc,
+            "#!/bin/sh\necho '<svg xmlns=\"http://www.w3.org/2000/svg\"/>' > \"$4\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&mmdc, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let markdown = "```mermaid\ngraph TD;\n  A-->B;\n```\n";
+        let filename = mermaid::diagram_filename("graph TD;\n  A-->B;\n");
+        let mut filter = CodeBlockFilter::new(None, CodeBlockOverflow::Scroll, false)
+            .with_mermaid(Some(mmdc.display().to_string()), 1);
+        let events = utils::create_new_pull_down_parser(markdown).flat_map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+
+        assert_eq!(
+            html_buf,
+            format!("<div class=\"mermaid-diagram\"><img src=\"../{filename}\" alt=\"Diagram\" /></div>\n")
+        );
+        let diagrams = filter.take_diagrams();
+        assert_eq!(
+            diagrams.get(&filename).map(Vec::as_slice),
+            Some(&b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>\n"[..])
+        );
+
+        // failing command keeps the code block
+        let mut filter = CodeBlockFilter::new(None, CodeBlockOverflow::Scroll, false)
+            .with_mermaid(Some("no-such-mmdc".to_string()), 0);
+        let events = utils::create_new_pull_down_parser(markdown).flat_map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        assert_eq!(
+            html_buf,
+            "<pre><code class=\"language-mermaid\">graph TD;\n  A--&gt;B;\n</code></pre>\n"
+        );
+        assert!(filter.take_diagrams().is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use pulldown_cmark::{CowStr, Event};
+
+const DETAILS_OPEN: &str = "<div class=\"details\">";
+const SUMMARY_OPEN: &str = "<div class=\"summary\">";
+const CLOSE: &str = "</div>";
+
+/// Rewrites raw `<details>`/`<summary>` disclosure widgets into always expanded,
+/// styled `<div>` blocks, as many reading systems don't support them.
+pub(crate) struct DetailsFilter {
+    enabled: bool,
+}
+
+impl DetailsFilter {
+    pub(crate) fn new(enabled: bool) -> Self {
+        DetailsFilter { enabled }
+    }
+
+    pub(crate) fn apply<'a>(&self, event: Event<'a>) -> Event<'a> {
+        if !self.enabled {
+            return event;
+        }
+        match event {
+            // html blocks come line by line, so 'summary' is usually a separate event
+            Event::Html(ref html) if Self::has_details(html) => {
+                Event::Html(CowStr::from(Self::expand_details(html)))
+            }
+            Event::InlineHtml(ref html) if Self::has_details(html) => {
+                Event::InlineHtml(CowStr::from(Self::expand_details(html)))
+            }
+            _ => event,
+        }
+    }
+
+    fn has_details(html: &str) -> bool {
+        let html = html.to_ascii_lowercase();
+        ["<details", "</details", "<summary", "</summary"]
+            .iter()
+            .any(|tag| html.contains(tag))
+    }
+
+    // Replace 'details' and 'summary' tags, any attributes (e.g. 'open') are dropped
+    fn expand_details(html: &str) -> String {
+        let mut result = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            result.push_str(&rest[..start]);
+            let tag = &rest[start..];
+            let Some(end) = tag.find('>') else {
+                rest = tag;
+                break;
+            };
+            let inner = &tag[1..end];
+            let closing = inner.starts_with('/');
+            let name = inner
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            match (name.as_str(), closing) {
+                ("details", false) => result.push_str(DETAILS_OPEN),
+                ("summary", false) => result.push_str(SUMMARY_OPEN),
+                ("details" | "summary", true) => result.push_str(CLOSE),
+                _ => result.push_str(&tag[..=end]),
+            }
+            rest = &tag[end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    fn render(markdown: &str, enabled: bool) -> String {
+        let filter = DetailsFilter::new(enabled);
+        let events = utils::create_new_pull_down_parser(markdown).map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    const DETAILS: &str =
+        "<details open>\n<summary>Show <b>answer</b></summary>\n\nIt is *42*.\n\n</details>\n";
+
+    #[test]
+    fn details_rendered_expanded() {
+        assert_eq!(
+            render(DETAILS, true),
+            "<div class=\"details\">\n<div class=\"summary\">Show <b>answer</b></div>\n\
+            <p>It is <em>42</em>.</p>\n\
+            </div>\n"
+        );
+    }
+
+    #[test]
+    fn details_kept_when_disabled() {
+        assert_eq!(
+            render(DETAILS, false),
+            "<details open>\n<summary>Show <b>answer</b></summary>\n\
+            <p>It is <em>42</em>.</p>\n\
+            </details>\n"
+        );
+    }
+
+    #[test]
+    fn other_html_untouched() {
+        assert_eq!(
+            DetailsFilter::expand_details("<DETAILS><p class=\"x\">a < b</p></Details>"),
+            "<div class=\"details\"><p class=\"x\">a < b</p></div>"
+        );
+        let event = Event::Html(CowStr::from("<div class=\"summary-note\">"));
+        assert_eq!(DetailsFilter::new(true).apply(event.clone()), event);
+    }
+}
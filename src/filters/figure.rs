@@ -0,0 +1,195 @@
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+/// Wraps an image paragraph followed by an emphasized caption, e.g. `*The caption*` on the
+/// next line or in the next paragraph, into a `<figure>` with a `<figcaption>`. EPUB 2
+/// has no such elements, `<div class="figure">` and `<p class="figcaption">` are used.
+///
+/// The caption of an image at the end of the chapter is only known at its end, so the
+/// events are passed in as `Some`, followed by a `None` at the end.
+pub(crate) struct FigureFilter<'a> {
+    enabled: bool,
+    html5: bool,
+    /// Events of the paragraph being processed.
+    paragraph: Option<Vec<Event<'a>>>,
+    /// Paragraph with only an image, which may be followed by its caption's paragraph.
+    image: Option<Vec<Event<'a>>>,
+}
+
+impl<'a> FigureFilter<'a> {
+    pub(crate) fn new(enabled: bool, html5: bool) -> Self {
+        FigureFilter {
+            enabled,
+            html5,
+            paragraph: None,
+            image: None,
+        }
+    }
+
+    pub(crate) fn apply(&mut self, event: Option<Event<'a>>) -> Vec<Event<'a>> {
+        let Some(event) = event else {
+            return self.image.take().unwrap_or_default();
+        };
+        if !self.enabled {
+            return vec![event];
+        }
+        if let Some(mut paragraph) = self.paragraph.take() {
+            let end = matches!(event, Event::End(TagEnd::Paragraph));
+            paragraph.push(event);
+            if !end {
+                self.paragraph = Some(paragraph);
+                return Vec::new();
+            }
+            return self.paragraph_done(paragraph);
+        }
+        if matches!(event, Event::Start(Tag::Paragraph)) {
+            self.paragraph = Some(vec![event]);
+            return Vec::new();
+        }
+        let mut events = self.image.take().unwrap_or_default();
+        events.push(event);
+        events
+    }
+
+    fn paragraph_done(&mut self, paragraph: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let inner = &paragraph[1..paragraph.len() - 1];
+        if let Some(image) = self.image.take() {
+            if let Some(caption) = Self::emphasis(inner) {
+                return self.figure(&image[1..image.len() - 1], caption);
+            }
+            // the new paragraph may be an image itself
+            let mut events = image;
+            events.extend(self.paragraph_done(paragraph));
+            return events;
+        }
+        let Some(image_len) = Self::image_len(inner) else {
+            return paragraph;
+        };
+        let (image, rest) = inner.split_at(image_len);
+        match rest {
+            [] => {
+                self.image = Some(paragraph);
+                Vec::new()
+            }
+            [Event::SoftBreak | Event::HardBreak, caption @ ..] => match Self::emphasis(caption) {
+                Some(caption) => self.figure(image, caption),
+                None => paragraph,
+            },
+            _ => paragraph,
+        }
+    }
+
+    fn figure(&self, image: &[Event<'a>], caption: &[Event<'a>]) -> Vec<Event<'a>> {
+        let (start, caption_start, end) = if self.html5 {
+            ("<figure>\n", "\n<figcaption>", "</figcaption>\n</figure>\n")
+        } else {
+            (
+                "<div class=\"figure\">\n",
+                "\n<p class=\"figcaption\">",
+                "</p>\n</div>\n",
+            )
+        };
+        let mut events = vec![Event::Html(CowStr::from(start))];
+        events.extend_from_slice(image);
+        events.push(Event::Html(CowStr::from(caption_start)));
+        events.extend_from_slice(caption);
+        events.push(Event::Html(CowStr::from(end)));
+        events
+    }
+
+    // Number of the events of the image the paragraph starts with
+    fn image_len(events: &[Event<'_>]) -> Option<usize> {
+        if !matches!(events.first(), Some(Event::Start(Tag::Image { .. }))) {
+            return None;
+        }
+        events
+            .iter()
+            .position(|event| matches!(event, Event::End(TagEnd::Image)))
+            .map(|end| end + 1)
+    }
+
+    // Content of the emphasis, if the events are a single emphasized text
+    fn emphasis<'e>(events: &'e [Event<'a>]) -> Option<&'e [Event<'a>]> {
+        let [Event::Start(Tag::Emphasis), content @ .., Event::End(TagEnd::Emphasis)] = events
+        else {
+            return None;
+        };
+        // e.g. `*Ferris* and *Corro*` are two emphasized texts
+        let mut depth = 0;
+        for event in content {
+            match event {
+                Event::Start(Tag::Emphasis) => depth += 1,
+                Event::End(TagEnd::Emphasis) if depth == 0 => return None,
+                Event::End(TagEnd::Emphasis) => depth -= 1,
+                _ => {}
+            }
+        }
+        Some(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+    use std::iter;
+
+    fn render(markdown: &str, enabled: bool, html5: bool) -> String {
+        let mut filter = FigureFilter::new(enabled, html5);
+        let events = utils::create_new_pull_down_parser(markdown)
+            .map(Some)
+            .chain(iter::once(None))
+            .flat_map(|event| filter.apply(event));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    #[test]
+    fn image_with_caption_wrapped_in_figure() {
+        let figure = "<figure>\n<img src=\"ferris.png\" alt=\"Ferris\" />\n\
+            <figcaption>Ferris the <strong>crab</strong></figcaption>\n</figure>\n";
+        assert_eq!(
+            render("![Ferris](ferris.png)\n*Ferris the **crab***\n", true, true),
+            figure
+        );
+        assert_eq!(
+            render(
+                "![Ferris](ferris.png)\n\n*Ferris the **crab***\n",
+                true,
+                true
+            ),
+            figure
+        );
+        assert_eq!(
+            render(
+                "Text\n\n![Ferris](ferris.png)\n\n_Ferris_\n\nMore",
+                true,
+                false
+            ),
+            "<p>Text</p>\n<div class=\"figure\">\n<img src=\"ferris.png\" alt=\"Ferris\" />\n\
+            <p class=\"figcaption\">Ferris</p>\n</div>\n<p>More</p>\n"
+        );
+    }
+
+    #[test]
+    fn other_images_kept() {
+        for markdown in [
+            "![Ferris](ferris.png)\n",
+            "![Ferris](ferris.png)\n\nText\n",
+            "![Ferris](ferris.png)\n\n![Corro](corro.png)\n",
+            "![Ferris](ferris.png) and *Corro*\n",
+            "![Ferris](ferris.png)\n*Ferris* and *Corro*\n",
+            "Ferris:\n\n*crab*\n",
+        ] {
+            assert_eq!(
+                render(markdown, true, true),
+                render(markdown, false, true),
+                "{markdown}"
+            );
+        }
+        // the second image is captioned
+        assert!(render("![A](a.png)\n\n![B](b.png)\n\n*B*\n", true, true)
+            .starts_with("<p><img src=\"a.png\" alt=\"A\" /></p>\n<figure>\n<img src=\"b.png\""));
+    }
+}
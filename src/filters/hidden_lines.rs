@@ -0,0 +1,118 @@
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag, TagEnd};
+
+/// Removes the boilerplate lines of Rust code blocks which mdbook hides in its HTML
+/// renderer: lines starting with `# ` or a lone `#`. A leading `##` is mdbook's escape
+/// for a visible line starting with `#`, e.g. `#[derive(Debug)]` written as `##[derive(Debug)]`.
+pub(crate) struct HiddenLinesFilter {
+    enabled: bool,
+    /// Code of the Rust code block being processed.
+    code_block: Option<String>,
+}
+
+impl HiddenLinesFilter {
+    pub(crate) fn new(enabled: bool) -> Self {
+        HiddenLinesFilter {
+            enabled,
+            code_block: None,
+        }
+    }
+
+    pub(crate) fn apply<'a>(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        if !self.enabled {
+            return vec![event];
+        }
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => {
+                if Self::is_rust(info) {
+                    self.code_block = Some(String::new());
+                }
+                vec![event]
+            }
+            Event::Text(ref text) => match self.code_block {
+                Some(ref mut code) => {
+                    code.push_str(text);
+                    vec![]
+                }
+                None => vec![event],
+            },
+            Event::End(TagEnd::CodeBlock) => match self.code_block.take() {
+                Some(code) => vec![Event::Text(CowStr::from(Self::visible_lines(&code))), event],
+                None => vec![event],
+            },
+            _ => vec![event],
+        }
+    }
+
+    // The language token is the first word of the info string, e.g. "rust" of "rust,ignore"
+    fn is_rust(info: &str) -> bool {
+        info.split(|c: char| c == ',' || c.is_whitespace())
+            .next()
+            .is_some_and(|language| language == "rust")
+    }
+
+    fn visible_lines(code: &str) -> String {
+        let mut visible = String::with_capacity(code.len());
+        for line in code.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let trimmed = content.trim_start();
+            if trimmed.starts_with("##") {
+                let indent = &content[..content.len() - trimmed.len()];
+                visible.push_str(indent);
+                visible.push_str(&line[indent.len() + 1..]);
+            } else if !(trimmed == "#" || trimmed.starts_with("# ")) {
+                visible.push_str(line);
+            }
+        }
+        visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    fn render(markdown: &str, filter: &mut HiddenLinesFilter) -> String {
+        let events = utils::create_new_pull_down_parser(markdown).flat_map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    #[test]
+    fn hidden_lines_are_removed_from_rust_code_blocks() {
+        let markdown = "```rust,editable\n\
+            # use std::fmt;\n\
+            #\n\
+            ##[derive(Debug)]\n\
+            struct Point;\n\
+            # fn main() {\n    \
+                # let hidden = 1;\n    \
+                #visible();\n\
+            # }\n\
+            ```\n";
+        let mut filter = HiddenLinesFilter::new(true);
+        assert_eq!(
+            render(markdown, &mut filter),
+            "<pre><code class=\"language-rust,editable\">#[derive(Debug)]\n\
+            struct Point;\n    \
+            #visible();\n\
+            </code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn other_code_blocks_unchanged() {
+        let mut filter = HiddenLinesFilter::new(true);
+        let markdown = "```bash\n# a comment\necho\n```\n\n    # indented\n";
+        assert_eq!(
+            render(markdown, &mut filter),
+            "<pre><code class=\"language-bash\"># a comment\necho\n</code></pre>\n\
+            <pre><code># indented\n</code></pre>\n"
+        );
+
+        let mut filter = HiddenLinesFilter::new(false);
+        assert!(render("```rust\n# fn main() {}\n```\n", &mut filter).contains("# fn main"));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::utils::{epub_path, is_drive_relative, native_separators, normalize_path};
+use pulldown_cmark::{CowStr, Event, Tag};
+use std::collections::HashMap;
+use std::path::Path;
+use url::Url;
+
+/// Points links to other chapters' markdown files, e.g. `intro.md#setup`, to their pages
+/// in the EPUB like mdbook does, and collects the links to `.md` and `.html` files which
+/// aren't chapters of the book. Anchors aren't checked.
+pub(crate) struct InternalLinkFilter<'a> {
+    /// File names of the chapters' pages by their paths in `src` with `/` separators, e.g.
+    /// `index.html` for `intro/README.md`, which is renamed by mdbook's index preprocessor.
+    chapters: &'a HashMap<String, String>,
+    /// Folder of the chapter in `src`, which its links are relative to.
+    chapter_dir: &'a Path,
+    broken: Vec<String>,
+}
+
+impl<'a> InternalLinkFilter<'a> {
+    pub(crate) fn new(chapters: &'a HashMap<String, String>, chapter_dir: &'a Path) -> Self {
+        InternalLinkFilter {
+            chapters,
+            chapter_dir,
+            broken: Vec::new(),
+        }
+    }
+
+    pub(crate) fn apply(&mut self, event: Event<'a>) -> Event<'a> {
+        match event {
+            Event::Start(Tag::Link {
+                link_type,
+                ref dest_url,
+                ref title,
+                ref id,
+            }) => match self.chapter_link(dest_url) {
+                Some(Ok(page_link)) => Event::Start(Tag::Link {
+                    link_type,
+                    dest_url: CowStr::from(page_link),
+                    title: title.clone(),
+                    id: id.clone(),
+                }),
+                Some(Err(())) => {
+                    self.broken.push(dest_url.to_string());
+                    event
+                }
+                None => event,
+            },
+            _ => event,
+        }
+    }
+
+    /// Targets of the links which aren't chapters of the book, in the order they were found.
+    pub(crate) fn broken(&self) -> &[String] {
+        &self.broken
+    }
+
+    // Link to the chapter's page for a link to its '.md' file, an error if a '.md' or '.html'
+    // link isn't a chapter, nothing for other links like remote URLs and anchors.
+    fn chapter_link(&self, link: &str) -> Option<Result<String, ()>> {
+        if Url::parse(link).is_ok() && !is_drive_relative(link) {
+            return None;
+        }
+        let (path, fragment) = match link.find(['#', '?']) {
+            Some(i) => link.split_at(i),
+            None => (link, ""),
+        };
+        let extension = Path::new(path).extension()?.to_str()?;
+        let page = path.strip_suffix(extension)?;
+        let target = epub_path(&normalize_path(
+            &self.chapter_dir.join(native_separators(page)),
+        ));
+        let chapter_page = self.chapters.get(&format!("{target}md"));
+        match (extension, chapter_page) {
+            ("md", Some(file_name)) => {
+                let dir = &page[..page.rfind('/').map_or(0, |i| i + 1)];
+                Some(Ok(format!("{dir}{file_name}{fragment}")))
+            }
+            ("html", Some(_)) => None,
+            ("md" | "html", None) => Some(Err(())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    #[test]
+    fn chapter_links_rewritten_and_broken_ones_found() {
+        let chapters: HashMap<String, String> = [
+            ("intro.md", "intro.html"),
+            ("part/index.md", "index.html"),
+            ("part/README.md", "index.html"),
+            ("part/chapter_1.md", "chapter_1.html"),
+            ("part/chapter_2.md", "chapter_2.html"),
+        ]
+        .into_iter()
+        .map(|(path, page)| (path.to_string(), page.to_string()))
+        .collect();
+        let markdown = "[Next](chapter_2.md#setup) [Intro](../intro.md) [Page](chapter_2.html) \
+            [Part](./README.md) [Part](../part/index.html) \
+            [Old](chapter_0.md) [Gone](../gone.html?q=1) [Anchor](#top) [Web](https://example.com/a.md) \
+            [File](data.csv)";
+        let chapter_dir = Path::new("part");
+        let mut filter = InternalLinkFilter::new(&chapters, chapter_dir);
+        let events = utils::create_new_pull_down_parser(markdown).map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+
+        assert_eq!(
+            html_buf,
+            "<p><a href=\"chapter_2.html#setup\">Next</a> <a href=\"../intro.html\">Intro</a> \
+            <a href=\"chapter_2.html\">Page</a> <a href=\"./index.html\">Part</a> \
+            <a href=\"../part/index.html\">Part</a> <a href=\"chapter_0.md\">Old</a> \
+            <a href=\"../gone.html?q=1\">Gone</a> <a href=\"#top\">Anchor</a> \
+            <a href=\"https://example.com/a.md\">Web</a> <a href=\"data.csv\">File</a></p>\n"
+        );
+        assert_eq!(filter.broken(), ["chapter_0.md", "../gone.html?q=1"]);
+    }
+}
@@ -0,0 +1,127 @@
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag, TagEnd};
+
+const PLAYGROUND_URL: &str = "https://play.rust-lang.org/";
+
+/// Code block attributes which make mdbook leave out the playground's "Run" button.
+const NOT_RUNNABLE: &[&str] = &["ignore", "noplayground", "compile_fail", "should_panic"];
+
+/// Reading systems can't run code, so runnable Rust code blocks are followed by a static
+/// link to the online Rust Playground instead of mdbook's "Run" button.
+pub(crate) struct PlaygroundFilter {
+    enabled: bool,
+    /// Edition of the book's `[rust]` config, e.g. `2021`.
+    default_edition: Option<String>,
+    /// Edition and code of the runnable code block being processed.
+    code_block: Option<(Option<String>, String)>,
+}
+
+impl PlaygroundFilter {
+    pub(crate) fn new(enabled: bool, default_edition: Option<String>) -> Self {
+        PlaygroundFilter {
+            enabled,
+            default_edition,
+            code_block: None,
+        }
+    }
+
+    pub(crate) fn apply<'a>(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        if !self.enabled {
+            return vec![event];
+        }
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => {
+                self.code_block = Self::runnable_edition(info)
+                    .map(|edition| (edition.or(self.default_edition.clone()), String::new()));
+                vec![event]
+            }
+            Event::Text(ref text) => {
+                if let Some((_, ref mut code)) = self.code_block {
+                    code.push_str(text);
+                }
+                vec![event]
+            }
+            Event::End(TagEnd::CodeBlock) => match self.code_block.take() {
+                Some((edition, code)) => {
+                    let link = Self::playground_link(&code, edition.as_deref());
+                    vec![event, Event::Html(CowStr::from(link))]
+                }
+                None => vec![event],
+            },
+            _ => vec![event],
+        }
+    }
+
+    // Some(edition) if the code block is runnable Rust, e.g. "rust,edition2018"
+    fn runnable_edition(info: &str) -> Option<Option<String>> {
+        let mut attributes = info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|a| !a.is_empty());
+        if attributes.next() != Some("rust") {
+            return None;
+        }
+        let mut edition = None;
+        for attribute in attributes {
+            if NOT_RUNNABLE.contains(&attribute) {
+                return None;
+            }
+            if let Some(year) = attribute.strip_prefix("edition") {
+                edition = Some(year.to_string());
+            }
+        }
+        Some(edition)
+    }
+
+    fn playground_link(code: &str, edition: Option<&str>) -> String {
+        // lines hidden by mdbook (starting with '#') are needed to run the code
+        let mut url = format!("{PLAYGROUND_URL}?code={}", urlencoding::encode(code));
+        if let Some(edition) = edition {
+            url.push_str(&format!("&amp;edition={edition}"));
+        }
+        format!(
+            "<p class=\"playground-link\"><a href=\"{url}\">Run this code in the Rust Playground</a></p>\n"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    fn render(markdown: &str, filter: &mut PlaygroundFilter) -> String {
+        let events = utils::create_new_pull_down_parser(markdown).flat_map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        html_buf
+    }
+
+    #[test]
+    fn runnable_code_block_gets_playground_link() {
+        let mut filter = PlaygroundFilter::new(true, Some("2021".to_string()));
+        let html = render(
+            "```rust\nfn main() {}\n```\n\n```rust,edition2018\nfn main() {}\n```\n",
+            &mut filter,
+        );
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n\
+            <p class=\"playground-link\"><a href=\"https://play.rust-lang.org/?code=fn%20main%28%29%20%7B%7D%0A&amp;edition=2021\">\
+            Run this code in the Rust Playground</a></p>\n\
+            <pre><code class=\"language-rust,edition2018\">fn main() {}\n</code></pre>\n\
+            <p class=\"playground-link\"><a href=\"https://play.rust-lang.org/?code=fn%20main%28%29%20%7B%7D%0A&amp;edition=2018\">\
+            Run this code in the Rust Playground</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn not_runnable_code_blocks_unchanged() {
+        let mut filter = PlaygroundFilter::new(true, None);
+        let markdown =
+            "```rust,ignore\nfn main() {}\n```\n\n```toml\n[book]\n```\n\n    indented();\n";
+        assert!(!render(markdown, &mut filter).contains("playground-link"));
+
+        let mut filter = PlaygroundFilter::new(false, None);
+        assert!(!render("```rust\nfn main() {}\n```\n", &mut filter).contains("playground-link"));
+    }
+}
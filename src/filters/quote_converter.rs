@@ -1,9 +1,52 @@
 use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
 
+/// Inline HTML elements with code as their text, e.g. `<code>"literal"</code>`.
+const HTML_CODE_ELEMENTS: &[&str] = &["code", "kbd", "pre", "samp", "script", "style"];
+
+/// Opening and closing curly quotes of a language, for double and single quotes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct QuoteStyle {
+    double: (char, char),
+    single: (char, char),
+}
+
+impl QuoteStyle {
+    const ENGLISH: QuoteStyle = QuoteStyle {
+        double: ('“', '”'),
+        single: ('‘', '’'),
+    };
+
+    /// Quotes of the language by its primary subtag, e.g. `de` for `de-CH`, English ones
+    /// for unknown languages.
+    fn for_language(lang: &str) -> QuoteStyle {
+        let primary = lang.split(['-', '_']).next().unwrap_or_default();
+        match primary.to_ascii_lowercase().as_str() {
+            "de" | "cs" | "sk" => QuoteStyle {
+                double: ('„', '“'),
+                single: ('‚', '‘'),
+            },
+            "fr" | "ru" | "uk" => QuoteStyle {
+                double: ('«', '»'),
+                single: ('‹', '›'),
+            },
+            "ja" | "zh" => QuoteStyle {
+                double: ('「', '」'),
+                single: ('『', '』'),
+            },
+            _ => QuoteStyle::ENGLISH,
+        }
+    }
+}
+
 /// From `mdbook/src/utils/mod.rs`, where this is a private struct.
+/// Only prose is converted: code spans, code blocks and HTML, including its attribute
+/// values and the text of inline HTML code elements, are left as they are.
 pub struct QuoteConverterFilter {
     enabled: bool,
     convert_text: bool,
+    /// Number of inline HTML code elements the text is in.
+    html_code_depth: usize,
+    style: QuoteStyle,
 }
 
 impl QuoteConverterFilter {
@@ -11,9 +54,17 @@ impl QuoteConverterFilter {
         QuoteConverterFilter {
             enabled,
             convert_text: true,
+            html_code_depth: 0,
+            style: QuoteStyle::ENGLISH,
         }
     }
 
+    /// Use the quotes of the book's language, e.g. `„x“` for German.
+    pub(crate) fn with_language(mut self, lang: Option<&str>) -> Self {
+        self.style = lang.map_or(QuoteStyle::ENGLISH, QuoteStyle::for_language);
+        self
+    }
+
     pub(crate) fn apply<'a>(&mut self, event: Event<'a>) -> Event<'a> {
         if !self.enabled {
             return event;
@@ -28,50 +79,80 @@ impl QuoteConverterFilter {
                 self.convert_text = true;
                 event
             }
-            Event::Text(ref text) if self.convert_text => {
-                Event::Text(CowStr::from(Self::convert_quotes_to_curly(text)))
+            Event::InlineHtml(ref html) => {
+                match Self::html_code_tag(html) {
+                    Some(true) => self.html_code_depth += 1,
+                    Some(false) => self.html_code_depth = self.html_code_depth.saturating_sub(1),
+                    None => {}
+                }
+                event
             }
+            Event::Text(ref text) if self.convert_text && self.html_code_depth == 0 => {
+                Event::Text(CowStr::from(Self::convert_quotes(text, self.style)))
+            }
+            // code spans and HTML blocks aren't text, so they're never converted
             _ => event,
         }
     }
 
+    // Whether the inline HTML opens (`true`) or closes (`false`) a code element
+    fn html_code_tag(html: &str) -> Option<bool> {
+        let tag = html.trim().strip_prefix('<')?;
+        let (opening, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (false, tag),
+            None => (true, tag),
+        };
+        let name: String = tag
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if !HTML_CODE_ELEMENTS.contains(&name.as_str()) || opening && html.ends_with("/>") {
+            return None;
+        }
+        Some(opening)
+    }
+
+    #[cfg(test)]
     fn convert_quotes_to_curly(original_text: &str) -> String {
+        Self::convert_quotes(original_text, QuoteStyle::ENGLISH)
+    }
+
+    fn convert_quotes(original_text: &str, style: QuoteStyle) -> String {
         // We'll consider the start to be "whitespace".
         let mut preceded_by_whitespace = true;
+        let mut chars = original_text.chars().peekable();
+        let mut converted = String::with_capacity(original_text.len());
 
-        original_text
-            .chars()
-            .map(|original_char| {
-                let converted_char = match original_char {
-                    '\'' => {
-                        if preceded_by_whitespace {
-                            '‘'
-                        } else {
-                            '’'
-                        }
-                    }
-                    '"' => {
-                        if preceded_by_whitespace {
-                            '“'
-                        } else {
-                            '”'
-                        }
-                    }
-                    _ => original_char,
-                };
-
-                preceded_by_whitespace = original_char.is_whitespace();
-
-                converted_char
-            })
-            .collect()
+        while let Some(original_char) = chars.next() {
+            let (open, close) = match original_char {
+                '\'' => style.single,
+                '"' => style.double,
+                _ => (original_char, original_char),
+            };
+            let converted_char = if preceded_by_whitespace {
+                open
+            } else if original_char == '\''
+                && chars.peek().is_some_and(|next| next.is_alphanumeric())
+            {
+                // an apostrophe within a word, e.g. "l'homme", in every language
+                '’'
+            } else {
+                close
+            };
+            converted.push(converted_char);
+
+            preceded_by_whitespace = original_char.is_whitespace();
+        }
+        converted
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pulldown_cmark::{CodeBlockKind, CowStr};
+    use crate::utils;
+    use pulldown_cmark::{html, CodeBlockKind, CowStr};
 
     #[test]
     fn test_basic_quote_conversion() {
@@ -135,6 +216,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_code_spans_and_html_left_untouched() {
+        let markdown = "She said \"hi\" to `\"literal\"` and \
+            <a href=\"https://example.com/it's\" title=\"a 'title'\">it's \"linked\"</a>, \
+            <code>\"raw\"</code> <kbd>'k'</kbd> 'done'\n";
+        let mut filter = QuoteConverterFilter::new(true);
+        let events = utils::create_new_pull_down_parser(markdown).map(|e| filter.apply(e));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+        assert_eq!(
+            html_buf,
+            "<p>She said “hi” to <code>\"literal\"</code> and \
+            <a href=\"https://example.com/it's\" title=\"a 'title'\">it’s “linked”</a>, \
+            <code>\"raw\"</code> <kbd>'k'</kbd> ‘done’</p>\n"
+        );
+    }
+
     #[test]
     fn test_convert_quotes_to_curly() {
         // Test various quote patterns
@@ -149,6 +247,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_quotes_of_language() {
+        let convert = |lang: &str, text: &str| {
+            let mut filter = QuoteConverterFilter::new(true).with_language(Some(lang));
+            match filter.apply(Event::Text(CowStr::from(text.to_string()))) {
+                Event::Text(result) => result.into_string(),
+                _ => panic!("Expected Text event"),
+            }
+        };
+        let text = r#"Er sagt "Hallo" und 'tschüss', l'homme"#;
+        assert_eq!(
+            convert("de", text),
+            "Er sagt „Hallo“ und ‚tschüss‘, l’homme"
+        );
+        assert_eq!(
+            convert("de-CH", text),
+            "Er sagt „Hallo“ und ‚tschüss‘, l’homme"
+        );
+        assert_eq!(
+            convert("fr", text),
+            "Er sagt «Hallo» und ‹tschüss›, l’homme"
+        );
+        assert_eq!(convert("ja", r#""東京""#), "「東京」");
+        // unknown languages get English quotes
+        assert_eq!(
+            convert("xx", text),
+            "Er sagt “Hallo” und ‘tschüss’, l’homme"
+        );
+        assert_eq!(
+            QuoteConverterFilter::new(true).with_language(None).style,
+            QuoteStyle::ENGLISH
+        );
+    }
+
     #[test]
     fn test_whitespace_handling() {
         assert_eq!(
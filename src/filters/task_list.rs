@@ -0,0 +1,49 @@
+use pulldown_cmark::{CowStr, Event};
+
+const UNCHECKED: &str = "<input type=\"checkbox\" disabled=\"disabled\" />";
+const CHECKED: &str = "<input type=\"checkbox\" disabled=\"disabled\" checked=\"checked\" />";
+
+/// Writes the checkboxes of task list items, e.g. `- [x] Done`, with the attribute values
+/// XHTML requires instead of pulldown-cmark's empty ones, which strict EPUB validators reject.
+pub(crate) struct TaskListFilter;
+
+impl TaskListFilter {
+    pub(crate) fn apply(event: Event<'_>) -> Event<'_> {
+        match event {
+            // a space separates the checkbox from the item's text like in mdbook's output
+            Event::TaskListMarker(checked) => Event::InlineHtml(CowStr::from(format!(
+                "{} ",
+                if checked { CHECKED } else { UNCHECKED }
+            ))),
+            _ => event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use pulldown_cmark::html;
+
+    #[test]
+    fn task_list_checkboxes_are_valid_xhtml() {
+        let markdown = "- [x] Done\n- [ ] To do\n  - [X] Nested\n- Plain [ ] item\n";
+        let events = utils::create_new_pull_down_parser(markdown).map(TaskListFilter::apply);
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+
+        assert_eq!(
+            html_buf,
+            "<ul>\n\
+            <li><input type=\"checkbox\" disabled=\"disabled\" checked=\"checked\" /> Done</li>\n\
+            <li><input type=\"checkbox\" disabled=\"disabled\" /> To do\n\
+            <ul>\n\
+            <li><input type=\"checkbox\" disabled=\"disabled\" checked=\"checked\" /> Nested</li>\n\
+            </ul>\n\
+            </li>\n\
+            <li>Plain [ ] item</li>\n\
+            </ul>\n"
+        );
+    }
+}
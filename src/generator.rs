@@ -1,35 +1,204 @@
-use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ZipLibrary};
-use handlebars::{Handlebars, RenderError, RenderErrorReason};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ReferenceType, TocElement, ZipLibrary};
+use glob::Pattern;
+use handlebars::{handlebars_helper, Handlebars, RenderError, RenderErrorReason};
 use mdbook::book::{BookItem, Chapter};
 use mdbook::renderer::RenderContext;
-use pulldown_cmark::html;
+use mime_guess::Mime;
+use pulldown_cmark::{html, HeadingLevel};
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{self, Debug, Formatter},
     fs::File,
-    io::{Read, Write},
-    iter,
-    path::PathBuf,
+    io::{self, Read, Seek, Write},
+    iter, panic,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::mpsc::{self, RecvTimeoutError},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
-
-use crate::config::Config;
-use crate::filters::asset_link::AssetRemoteLinkFilter;
+use uuid::Uuid;
+
+use crate::archive::ZipArchive;
+use crate::config::{Config, CssMode, RemoteFailureMode};
+use crate::cover;
+use crate::diagnostics::{self, Warning, WarningKind};
+use crate::filters::admonition::AdmonitionFilter;
+use crate::filters::alt_text::AltTextFilter;
+use crate::filters::asset_link::{
+    AssetRemoteLinkFilter, REMOTE_PLACEHOLDER, REMOTE_PLACEHOLDER_SVG,
+};
+use crate::filters::caption_numbering::{CaptionNumberingFilter, CaptionNumbers};
+use crate::filters::code_block::CodeBlockFilter;
+use crate::filters::details::DetailsFilter;
+use crate::filters::figure::FigureFilter;
 use crate::filters::footnote::FootnoteFilter;
+use crate::filters::hidden_lines::HiddenLinesFilter;
+use crate::filters::internal_link::InternalLinkFilter;
+use crate::filters::playground::PlaygroundFilter;
 use crate::filters::quote_converter::QuoteConverterFilter;
+use crate::filters::task_list::TaskListFilter;
+use crate::nav::{NavContext, Navigation, NAV_FILES, NAV_TEMPLATE};
+use crate::progress::{Progress, ProgressCallback, Stage};
 use crate::resources::asset::{Asset, AssetKind};
 use crate::resources::resource::{self};
 use crate::resources::retrieve::{ContentRetriever, ResourceHandler};
+use crate::search_index::{SearchIndex, SEARCH_INDEX_FILE};
+use crate::split::{self, ChapterPart};
 use crate::DEFAULT_CSS;
 use crate::{utils, Error};
 
+/// Folder the chapters' markdown sources are embedded into.
+const SOURCES_FOLDER: &str = "sources";
+
+/// File name of the page showing the cover image.
+const COVER_PAGE_FILE: &str = "cover.xhtml";
+
+/// The book's stylesheet, which epub-builder embeds in the root folder.
+const STYLESHEET: &str = "stylesheet.css";
+
+/// Reset of the `minimal` CSS mode, which keeps the reader's typography but stops images
+/// and code blocks from overflowing the page.
+const MINIMAL_CSS: &str =
+    "@page {\n  margin: 10px;\n}\n\nbody {\n  margin: 0;\n  padding: 0;\n}\n\n\
+    img,\nsvg {\n  max-width: 100%;\n  height: auto;\n}\n\n\
+    pre {\n  white-space: pre-wrap;\n  word-wrap: break-word;\n}\n";
+
+/// Scales tall images, e.g. long diagrams, down to fit on a page.
+const CONSTRAIN_IMAGE_HEIGHT_CSS: &str =
+    "\nimg {\n  max-height: 100vh;\n  object-fit: contain;\n  page-break-inside: avoid;\n}\n";
+
+/// Starts each top-level chapter on a new page.
+const CHAPTER_PAGE_BREAKS_CSS: &str =
+    "\ndiv.chapter-start {\n  page-break-before: always;\n  break-before: page;\n}\n";
+
+/// File name and title of the generated "About this book" page.
+const ABOUT_PAGE: &str = "about.xhtml";
+const ABOUT_TITLE: &str = "About this book";
+/// Title of the navigation documents if the book's language has no translated one.
+const DEFAULT_TOC_TITLE: &str = "Table Of Contents";
+
+/// Media types of the downloaded remote assets by their links, and the links of the ones
+/// which failed to download.
+type Downloads = (Vec<(String, Mime)>, Vec<String>);
+
+/// Message of the worker thread generating the book within `generation-timeout-secs`.
+enum Generation {
+    Progress(Progress),
+    /// What the generated EPUB contains.
+    Done(Result<BuildSummary, Error>),
+}
+
+/// What the generated EPUB contains, logged when it's done.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct BuildSummary {
+    pub(crate) chapters: usize,
+    pub(crate) local_assets: usize,
+    pub(crate) remote_assets: usize,
+    /// Size of the EPUB file.
+    pub(crate) bytes: u64,
+    pub(crate) warnings: Vec<Warning>,
+}
+
+impl fmt::Display for BuildSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} chapters, {} local and {} remote assets, {} bytes",
+            self.chapters, self.local_assets, self.remote_assets, self.bytes
+        )
+    }
+}
+
+handlebars_helper!(uppercase: |text: str| text.to_uppercase());
+handlebars_helper!(lowercase: |text: str| text.to_lowercase());
+
+/// Data a page is rendered with by the `index.hbs` template (or the `index-template`).
+#[derive(Debug, Serialize)]
+struct TemplateContext<'a> {
+    /// Whether the book is an EPUB 3 one, e.g. for its doctype.
+    epub_version_3: bool,
+    /// Title of the page, e.g. the chapter's name.
+    title: &'a str,
+    /// Rendered content of the page.
+    body: &'a str,
+    /// Link to the book's stylesheet, relative to the page.
+    stylesheet: String,
+    /// Links to all stylesheets of the book, relative to the page, starting with
+    /// `stylesheet`. There are several ones if `separate-stylesheets` is enabled.
+    stylesheets: Vec<String>,
+    /// Section number of the chapter, e.g. `1.2.`, if it's numbered.
+    chapter_number: Option<String>,
+    /// Path of the chapter's markdown file in `src`, e.g. `intro/README.md`, if the page
+    /// is rendered from one.
+    chapter_path: Option<String>,
+    /// Fields of the chapter's YAML front matter, e.g. `author`.
+    front_matter: BTreeMap<String, String>,
+}
+
 /// The actual EPUB book renderer.
 pub struct Generator<'a> {
     ctx: &'a RenderContext,
     builder: EpubBuilder<ZipLibrary>,
+    /// Archive the EPUB generated by the builder is repacked into.
+    archive: ZipArchive,
     config: Config,
     hbs: Handlebars<'a>,
+    /// Names of the templates registered for chapters by their path pattern, the most
+    /// specific (longest) pattern first.
+    chapter_templates: Vec<(Pattern, String)>,
+    /// File names of the chapters' pages by the chapters' paths in `src`, for their links.
+    chapter_pages: HashMap<String, String>,
+    /// Broken links to other chapters found while rendering the chapters.
+    broken_links: RefCell<Vec<String>>,
     assets: HashMap<String, Asset>,
-    handler: Box<dyn ContentRetriever>,
+    /// Whether the assets were found for rendering single chapters already.
+    assets_found: bool,
+    /// The remote and external assets by their URLs, whose links the chapters are rendered
+    /// with. Indexed once after finding the assets instead of for every chapter.
+    remote_assets: HashMap<String, Asset>,
+    /// `data:` URIs of the images inlined into the chapters, by their path in `src`.
+    inlined_images: HashMap<String, String>,
+    /// Media types of the remote images which failed to download by their URLs, which are
+    /// rendered by `remote_failure_mode` instead of being embedded.
+    failed_downloads: HashMap<String, Mime>,
+    handler: Box<dyn ContentRetriever + Send + Sync>,
+    search_index: SearchIndex,
+    /// TOC and landmarks of the navigation documents, which are rendered by `nav.hbs`.
+    navigation: Navigation,
+    /// Documents containing MathML, whose manifest items are marked by the archive.
+    mathml_documents: Vec<String>,
+    /// Figure and table numbers are continued from the previously rendered chapter.
+    caption_numbers: Cell<CaptionNumbers>,
+    /// Rendered diagrams by their file name, embedded after the chapters.
+    diagrams: RefCell<HashMap<String, Vec<u8>>>,
+    /// Path in `src` of the chapter where reading starts, added as the "bodymatter" landmark.
+    start_page: Option<PathBuf>,
+    /// Paths in `src` of the chapters added to the spine first, in the `spine-order`.
+    spine_order: Vec<PathBuf>,
+    /// Documents of the chapters listed by the spine order, which the archive moves to the
+    /// start of the spine.
+    spine_documents: HashMap<PathBuf, Vec<String>>,
+    /// Part title waiting for its first chapter to be added to the TOC.
+    part_title: Option<String>,
+    /// Number of draft chapters added as placeholder pages so far.
+    draft_count: usize,
+    summary: BuildSummary,
+    /// Problems with the book which didn't fail the build, for the build's diagnostics.
+    warnings: RefCell<Vec<Warning>>,
+    /// Point in time the generation should be finished by, if limited.
+    deadline: Option<Instant>,
+    /// Set when the generation has been abandoned after its timeout, it's checked with
+    /// the deadline.
+    cancelled: Arc<AtomicBool>,
+    progress: Option<ProgressCallback<'a>>,
+    /// Number of the chapters rendered so far and in total, for reporting the progress.
+    chapter_count: (usize, usize),
 }
 
 impl<'a> Generator<'a> {
@@ -39,23 +208,100 @@ impl<'a> Generator<'a> {
 
     fn new_with_handler(
         ctx: &'a RenderContext,
-        handler: impl ContentRetriever + 'static,
+        handler: impl ContentRetriever + Send + Sync + 'static,
     ) -> Result<Generator<'a>, Error> {
         let handler = Box::new(handler);
         let config = Config::from_render_context(ctx)?;
 
+        // EPUB 3.1 - 3.3 documents still declare the '3.0' package version
         let epub_version = match config.epub_version {
-            Some(2) => Some(EpubVersion::V20),
-            Some(3) => Some(EpubVersion::V30),
-            Some(v) => {
-                return Err(Error::EpubDocCreate(format!(
-                    "Unsupported epub version specified in book.toml: {}",
-                    v
-                )))
-            }
+            Some(ref v) => match v.major_minor() {
+                Some((2, 0)) => Some(EpubVersion::V20),
+                Some((3, 0..=3)) => Some(EpubVersion::V30),
+                _ => {
+                    return Err(Error::EpubDocCreate(format!(
+                        "Unsupported epub version specified in book.toml: {}",
+                        v
+                    )))
+                }
+            },
             None => None,
         };
 
+        if let Some(ref element) = config.chapter_wrapper {
+            if element.is_empty() || !element.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(Error::InvalidConfig(format!(
+                    "chapter-wrapper should be an element name, got '{element}'"
+                )));
+            }
+        }
+        if let Some(ref wrapper_type) = config.chapter_wrapper_type {
+            if !wrapper_type
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | ':' | ' '))
+            {
+                return Err(Error::InvalidConfig(format!(
+                    "chapter-wrapper-type contains invalid characters: '{wrapper_type}'"
+                )));
+            }
+        }
+
+        if let Some(font) = config
+            .fonts
+            .iter()
+            .find(|f| utils::font_mime_type(f).is_none())
+        {
+            return Err(Error::InvalidConfig(format!(
+                "Unsupported font type {font:?}, expected one of woff2, woff, ttf, otf"
+            )));
+        }
+
+        if let Some((extension, mimetype)) = config
+            .mime_overrides
+            .iter()
+            .find(|(_, mimetype)| mimetype.parse::<Mime>().is_err())
+        {
+            return Err(Error::InvalidConfig(format!(
+                "mime-overrides has an invalid media type for '{extension}': '{mimetype}'"
+            )));
+        }
+
+        if config.separate_stylesheets {
+            let mut names = vec![STYLESHEET.to_string()];
+            for css in config.additional_css.iter() {
+                let name = Self::stylesheet_filename(css);
+                if names.contains(&name) {
+                    return Err(Error::InvalidConfig(format!(
+                        "additional-css has several stylesheets named '{name}', which is \
+                        ambiguous with separate-stylesheets"
+                    )));
+                }
+                names.push(name);
+            }
+        }
+
+        if config.download_concurrency == 0 {
+            return Err(Error::InvalidConfig(
+                "download-concurrency should be positive, got 0".to_string(),
+            ));
+        }
+        if let Some(level) = config
+            .split_on_heading_level
+            .filter(|level| !(1..=6).contains(level))
+        {
+            return Err(Error::InvalidConfig(format!(
+                "split-on-heading-level must be in 1-6, got {level}"
+            )));
+        }
+        if config.cover_size.contains(&0) {
+            return Err(Error::InvalidConfig(format!(
+                "cover-size should be positive, got {:?}",
+                config.cover_size
+            )));
+        }
+
+        let archive =
+            ZipArchive::new(config.compression)?.with_metadata(Self::package_metadata(&config));
         let mut builder = EpubBuilder::new(ZipLibrary::new()?)?;
         if let Some(version) = epub_version {
             builder.epub_version(version);
@@ -64,37 +310,121 @@ impl<'a> Generator<'a> {
         let mut hbs = Handlebars::new();
         hbs.register_template_string("index", config.template()?)
             .map_err(|_| Error::TemplateParse)?;
+        hbs.register_template_string("nav", NAV_TEMPLATE)
+            .map_err(|_| Error::TemplateParse)?;
+        let mut chapter_templates = Vec::new();
+        for (pattern, file, template) in config.chapter_templates()? {
+            // a template used for several patterns is registered once
+            let name = format!("index:{}", file.display());
+            if !hbs.has_template(&name) {
+                hbs.register_template_string(&name, template)
+                    .map_err(|_| Error::TemplateParse)?;
+            }
+            chapter_templates.push((utils::glob_pattern(pattern)?, name));
+        }
+        chapter_templates.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.as_str().len()));
+        hbs.register_helper("uppercase", Box::new(uppercase));
+        hbs.register_helper("lowercase", Box::new(lowercase));
+
+        let start_page = Self::start_page(ctx, &config)?;
+        let spine_order = Self::spine_order(ctx, &config)?;
+        let mut chapter_pages = HashMap::new();
+        for item in ctx.book.iter() {
+            let BookItem::Chapter(Chapter {
+                path: Some(ref path),
+                ..
+            }) = *item
+            else {
+                continue;
+            };
+            let page = path.with_extension("html");
+            let page = page.file_name().unwrap_or_default().to_string_lossy();
+            // links to a README chapter keep working after it's renamed into 'index.md'
+            if path.ends_with("index.md") {
+                let readme = path.with_file_name(&config.readme_filename);
+                chapter_pages.insert(utils::epub_path(&readme), page.to_string());
+            }
+            chapter_pages.insert(utils::epub_path(path), page.to_string());
+        }
 
-        Ok(Generator {
+        let generator = Generator {
             builder,
+            archive,
             ctx,
             config,
             hbs,
+            chapter_templates,
+            chapter_pages,
+            broken_links: RefCell::new(Vec::new()),
             assets: HashMap::new(),
+            assets_found: false,
+            remote_assets: HashMap::new(),
+            inlined_images: HashMap::new(),
+            failed_downloads: HashMap::new(),
             handler,
-        })
+            search_index: SearchIndex::default(),
+            navigation: Navigation::default(),
+            mathml_documents: Vec::new(),
+            caption_numbers: Cell::new(CaptionNumbers::default()),
+            diagrams: RefCell::new(HashMap::new()),
+            start_page,
+            spine_order,
+            spine_documents: HashMap::new(),
+            part_title: None,
+            draft_count: 0,
+            summary: BuildSummary::default(),
+            warnings: RefCell::new(Vec::new()),
+            deadline: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            progress: None,
+            chapter_count: (0, 0),
+        };
+        // a wrong path fails the build before any chapter is rendered
+        generator.check_resources()?;
+        Ok(generator)
     }
 
     fn populate_metadata(&mut self) -> Result<(), Error> {
         info!("1. populate metadata ==");
-        self.builder.metadata("generator", "mdbook-epub")?;
 
         if let Some(title) = self.ctx.config.book.title.clone() {
             self.builder.metadata("title", title)?;
         } else {
-            warn!("No `title` attribute found yet all EPUB documents should have a title");
+            self.warn(
+                WarningKind::MissingTitle,
+                "No `title` attribute found yet all EPUB documents should have a title".to_string(),
+            );
         }
 
-        if let Some(desc) = self.ctx.config.book.description.clone() {
+        // the EPUB specific description takes precedence over the book's one
+        let description = self
+            .config
+            .description
+            .clone()
+            .or_else(|| self.ctx.config.book.description.clone());
+        if let Some(desc) = description {
             self.builder.metadata("description", desc)?;
         }
 
+        for subject in self.config.subjects.iter() {
+            self.builder.metadata("subject", subject.as_str())?;
+        }
+
+        // epub-builder records the license as <dc:rights>
+        if let Some(rights) = self.config.rights_statement() {
+            self.builder.metadata("license", rights)?;
+        }
+
         if !self.ctx.config.book.authors.is_empty() {
             self.builder
                 .metadata("author", self.ctx.config.book.authors.join(", "))?;
         }
 
-        self.builder.metadata("generator", env!("CARGO_PKG_NAME"))?;
+        // an explicitly empty name is left out of the navigation documents
+        let generator = self.generator_name().to_string();
+        if !generator.is_empty() {
+            self.builder.metadata("generator", generator)?;
+        }
 
         if let Some(lang) = self.ctx.config.book.language.clone() {
             self.builder.metadata("lang", lang)?;
@@ -102,26 +432,410 @@ impl<'a> Generator<'a> {
             self.builder.metadata("lang", "en")?;
         }
 
+        // the title of the navigation documents, epub-builder's English one by default
+        let language = self.ctx.config.book.language.as_deref();
+        let toc_name = self
+            .config
+            .toc_title(language)
+            .unwrap_or_else(|| DEFAULT_TOC_TITLE.to_string());
+        self.builder.metadata("toc_name", toc_name.as_str())?;
+        // adds the "toc" landmark, its TOC entry is titled by the name set above
+        self.builder.inline_toc();
+        self.navigation.add_inline_toc(&toc_name);
+
+        // epub-builder records it as the <dc:identifier>, a random one by default
+        let uuid = self.book_uuid()?;
+        self.builder.set_uuid(uuid);
+
+        Ok(())
+    }
+
+    /// Name of the generator, `mdbook-epub` unless it's configured.
+    fn generator_name(&self) -> &str {
+        self.config
+            .generator_name
+            .as_deref()
+            .unwrap_or(env!("CARGO_PKG_NAME"))
+    }
+
+    /// Render the navigation documents by their paths, which are written instead of
+    /// epub-builder's ones as its template can't be changed.
+    fn render_navigation(&self) -> Result<Vec<(String, String)>, Error> {
+        let context = NavContext {
+            epub_version_3: self.config.is_epub3(),
+            generator: self.generator_name(),
+            accessible: self.config.accessible_nav,
+        };
+        NAV_FILES
+            .iter()
+            .map(|(path, numbered)| {
+                let nav = self.navigation.render(&self.hbs, &context, *numbered)?;
+                Ok((path.to_string(), nav))
+            })
+            .collect()
+    }
+
+    /// Metadata elements epub-builder doesn't write: the `<dc:source>` and the series, as
+    /// Calibre's `calibre:series` meta and, in EPUB 3, as a `belongs-to-collection`.
+    fn package_metadata(config: &Config) -> String {
+        let mut metadata = Vec::new();
+        if let Some(ref source) = config.source {
+            metadata.push(format!(
+                "<dc:source>{}</dc:source>",
+                utils::escape_html(source)
+            ));
+        }
+        if let Some(ref series) = config.series {
+            let series = utils::escape_html(series);
+            metadata.push(format!(
+                "<meta name=\"calibre:series\" content=\"{series}\"/>"
+            ));
+            if let Some(index) = config.series_index {
+                metadata.push(format!(
+                    "<meta name=\"calibre:series_index\" content=\"{index}\"/>"
+                ));
+            }
+            if config.is_epub3() {
+                metadata.push(format!(
+                    "<meta property=\"belongs-to-collection\" id=\"series\">{series}</meta>"
+                ));
+                metadata.push(
+                    "<meta refines=\"#series\" property=\"collection-type\">series</meta>"
+                        .to_string(),
+                );
+                if let Some(index) = config.series_index {
+                    metadata.push(format!(
+                        "<meta refines=\"#series\" property=\"group-position\">{index}</meta>"
+                    ));
+                }
+            }
+        }
+        metadata.join("\n")
+    }
+
+    /// UUID of the book, the configured one or one derived from the book's title and
+    /// authors, so rebuilds of the book have the same identifier.
+    fn book_uuid(&self) -> Result<Uuid, Error> {
+        if let Some(identifier) = self.config.identifier.as_deref() {
+            return Uuid::parse_str(identifier.trim()).map_err(|e| {
+                Error::InvalidConfig(format!("identifier '{identifier}' isn't a UUID: {e}"))
+            });
+        }
+        let book = &self.ctx.config.book;
+        let mut hasher = Sha256::new();
+        hasher.update(book.title.as_deref().unwrap_or_default());
+        for author in book.authors.iter() {
+            hasher.update("\n");
+            hasher.update(author);
+        }
+        let hash = hasher.finalize();
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&hash[..16]);
+        Ok(uuid::Builder::from_custom_bytes(bytes).into_uuid())
+    }
+
+    pub fn generate<W: Write>(self, mut writer: W) -> Result<(), Error> {
+        // the archive is written into a seekable file
+        let mut epub = tempfile::tempfile()?;
+        let summary = self.generate_with_summary(&mut epub)?;
+        epub.rewind()?;
+        io::copy(&mut epub, &mut writer)?;
+        info!("Generated the EPUB book: {}", summary);
         Ok(())
     }
 
-    pub fn generate<W: Write>(mut self, writer: W) -> Result<(), Error> {
+    /// Generate the book of the `ctx` like [`Generator::generate_with_summary`], reporting
+    /// the progress to the `progress` callback. With `generation-timeout-secs`, it's
+    /// generated into the `writer` on a worker thread, and [`Error::Timeout`] is returned
+    /// as soon as it takes longer, even in the middle of a slow step like a download.
+    ///
+    /// The worker thread outlives the error: it's cancelled, but only stops at its next
+    /// check, e.g. once the download it's waiting for returns, and it may have written a
+    /// partial EPUB into the `writer` by then.
+    pub(crate) fn generate_with_timeout<W: Write + Seek + Send + 'static>(
+        ctx: &RenderContext,
+        progress: impl Fn(Progress) + Send + Sync,
+        writer: W,
+    ) -> Result<BuildSummary, Error> {
+        Self::generate_with_timeout_and_handler(ctx, ResourceHandler, progress, writer)
+    }
+
+    fn generate_with_timeout_and_handler<W: Write + Seek + Send + 'static>(
+        ctx: &RenderContext,
+        handler: impl ContentRetriever + Send + Sync + 'static,
+        progress: impl Fn(Progress) + Send + Sync,
+        writer: W,
+    ) -> Result<BuildSummary, Error> {
+        let Some(secs) = Config::from_render_context(ctx)?.generation_timeout_secs else {
+            return Generator::new_with_handler(ctx, handler)?
+                .with_progress(progress)
+                .generate_with_summary(writer);
+        };
+        let deadline = Instant::now() + Duration::from_secs(secs);
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = Arc::clone(&cancelled);
+        let worker = thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let result = Generator::new_with_handler(&ctx, handler).and_then(|generator| {
+                generator
+                    .with_progress(move |progress| {
+                        let _ = progress_sender.send(Generation::Progress(progress));
+                    })
+                    .with_cancellation(worker_cancelled)
+                    .generate_with_summary(writer)
+            });
+            let _ = sender.send(Generation::Done(result));
+        });
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(remaining) {
+                Ok(Generation::Progress(update)) => progress(update),
+                Ok(Generation::Done(result)) => return result,
+                Err(RecvTimeoutError::Timeout) => {
+                    // the worker stops at its next check, it isn't waited for
+                    cancelled.store(true, Ordering::Relaxed);
+                    error!("EPUB generation took longer than {} seconds", secs);
+                    return Err(Error::Timeout(secs));
+                }
+                // the worker always sends its result, unless it panicked
+                Err(RecvTimeoutError::Disconnected) => {
+                    panic::resume_unwind(worker.join().expect_err("the generation is done"))
+                }
+            }
+        }
+    }
+
+    /// Report the progress of the long running stages, i.e. downloading the remote assets,
+    /// rendering the chapters and embedding the assets, to the `callback`, e.g. for showing
+    /// a progress bar. It may be called from several threads.
+    pub fn with_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'a) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Stop the generation with [`Error::Timeout`] at its next check once `cancelled` is set.
+    fn with_cancellation(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = cancelled;
+        self
+    }
+
+    fn report_progress(&self, stage: Stage, position: usize, total: usize) {
+        if let Some(ref progress) = self.progress {
+            progress(Progress {
+                stage,
+                position,
+                total,
+            });
+        }
+    }
+
+    /// Generate the book like [`Generator::generate`] into a seekable `writer`, returning
+    /// what it contains.
+    pub(crate) fn generate_with_summary<W: Write + Seek>(
+        mut self,
+        writer: W,
+    ) -> Result<BuildSummary, Error> {
         info!("Generating the EPUB book");
+        self.deadline = self
+            .config
+            .generation_timeout_secs
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
 
         self.populate_metadata()?;
         self.find_assets()?;
+        self.dedup_remote_assets()?;
+        self.inline_small_images()?;
+        self.check_deadline()?;
+        // the cover page goes first in the reading order
+        self.add_cover_image()?;
         self.generate_chapters()?;
+        self.check_links()?;
+        self.add_about_page()?;
+        self.embed_diagrams()?;
 
-        self.add_cover_image()?;
         self.embed_stylesheets()?;
         self.additional_assets()?;
         self.additional_resources()?;
-        info!("8. final generation ==");
-        self.builder.generate(writer)?;
+        self.embed_search_index()?;
+        self.check_deadline()?;
+        info!("9. final generation ==");
+        // chapters listed by `spine-order` come first in the reading order, while their
+        // TOC entries stay at their place in the book
+        let spine_order = self
+            .spine_order
+            .iter()
+            .filter_map(|path| self.spine_documents.remove(path))
+            .flatten()
+            .collect();
+        // epub-builder's EPUB is kept in a temporary file while it's repacked
+        let mut epub = tempfile::tempfile()?;
+        self.builder.generate(&mut epub)?;
+        epub.rewind()?;
+        let navigation = self.render_navigation()?;
+        self.summary.bytes = self
+            .archive
+            .with_navigation(navigation)
+            .with_mathml_documents(std::mem::take(&mut self.mathml_documents))
+            .with_spine_order(spine_order)
+            .repack(epub, writer)?;
         info!("Generating the EPUB book - DONE !");
+        self.summary.warnings = self.warnings.take();
+        Ok(self.summary)
+    }
+
+    /// Render all chapters into one self-contained XHTML document with the stylesheet
+    /// inlined, instead of an EPUB. It's meant for quick reviews and diffing rendering
+    /// changes: local images are embedded as `data:` URIs, remote ones stay linked.
+    pub fn generate_single_html<W: Write>(mut self, mut writer: W) -> Result<(), Error> {
+        info!("Generating the single XHTML document");
+        // the assets aren't embedded as files, so the links to them aren't rewritten
+        let assets = resource::find(self.ctx, &self.config)?;
+        self.inlined_images = self.inline_images(assets.values(), None)?;
+        let mut body = String::new();
+        for item in self.ctx.book.iter() {
+            let BookItem::Chapter(ref ch) = *item else {
+                continue;
+            };
+            if ch.path.is_none() {
+                if self.config.include_drafts {
+                    body.push_str(&Self::render_draft_body(ch));
+                } else {
+                    debug!("Draft chapter '{}' has no content, skipped", &ch.name);
+                }
+                continue;
+            }
+            match self.render_chapter_body(ch) {
+                Ok(rendered) => body.push_str(&rendered),
+                Err(error_msg) if self.config.strict_assets => {
+                    error!("Failed to render chapter '{}': {}", &ch.name, error_msg);
+                    return Err(error_msg.into());
+                }
+                Err(error_msg) => warn!(
+                    "SKIPPED chapter '{}' due to error = {}",
+                    &ch.name, error_msg
+                ),
+            }
+        }
+        self.check_links()?;
+
+        let mut stylesheet = self.generate_stylesheet()?;
+        for (_, full_path) in self.separate_stylesheets()? {
+            stylesheet.extend(std::fs::read(&full_path).map_err(|_| Error::CssOpen(full_path))?);
+        }
+        let stylesheet = String::from_utf8_lossy(&stylesheet).into_owned();
+        let title = self.ctx.config.book.title.clone().unwrap_or_default();
+        write!(
+            writer,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <!DOCTYPE html>\n\
+            <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+            <head>\n\
+            <meta charset=\"utf-8\"/>\n\
+            <title>{}</title>\n\
+            <style>\n/*<![CDATA[*/\n{}\n/*]]>*/\n</style>\n\
+            </head>\n\
+            <body>\n{}</body>\n\
+            </html>\n",
+            utils::escape_html(&title),
+            stylesheet,
+            body
+        )?;
+        info!("Generating the single XHTML document - DONE !");
+        Ok(())
+    }
+
+    /// Abort the generation when it takes longer than `generation-timeout-secs` or it has
+    /// been cancelled. It's checked between the steps, chapters and assets, so a worker
+    /// thread abandoned by [`Generator::generate_with_timeout`] stops soon after.
+    fn check_deadline(&self) -> Result<(), Error> {
+        let Some(secs) = self.config.generation_timeout_secs else {
+            return Ok(());
+        };
+        let expired = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() > deadline);
+        if expired || self.cancelled.load(Ordering::Relaxed) {
+            error!("EPUB generation took longer than {} seconds", secs);
+            return Err(Error::Timeout(secs));
+        }
         Ok(())
     }
 
+    /// Path in `src` of the chapter where reading starts, the `start_chapter` or the first
+    /// chapter with more than a heading, as readers may open the book at an empty divider
+    /// otherwise.
+    fn start_page(ctx: &RenderContext, config: &Config) -> Result<Option<PathBuf>, Error> {
+        let chapters: Vec<&Chapter> = ctx
+            .book
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(ch) if ch.path.is_some() => Some(ch),
+                _ => None,
+            })
+            .collect();
+        let Some(ref start) = config.start_chapter else {
+            let start = chapters
+                .iter()
+                .find(|ch| !ch.content.trim().is_empty() && !utils::is_heading_only(&ch.content))
+                .or(chapters.first());
+            return Ok(start.and_then(|ch| ch.path.clone()));
+        };
+        Self::chapter_path(&chapters, start, config)
+            .map(Some)
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!(
+                    "start-chapter '{}' isn't a chapter of the book",
+                    utils::epub_path(start)
+                ))
+            })
+    }
+
+    /// Paths in `src` of the chapters listed by `spine-order`.
+    fn spine_order(ctx: &RenderContext, config: &Config) -> Result<Vec<PathBuf>, Error> {
+        let chapters: Vec<&Chapter> = ctx
+            .book
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(ch) if ch.path.is_some() => Some(ch),
+                _ => None,
+            })
+            .collect();
+        let mut order: Vec<PathBuf> = Vec::new();
+        for listed in config.spine_order.iter() {
+            let path = Self::chapter_path(&chapters, listed, config).ok_or_else(|| {
+                Error::InvalidConfig(format!(
+                    "spine-order lists '{}', which isn't a chapter of the book",
+                    utils::epub_path(listed)
+                ))
+            })?;
+            if order.contains(&path) {
+                return Err(Error::InvalidConfig(format!(
+                    "spine-order lists '{}' more than once",
+                    utils::epub_path(listed)
+                )));
+            }
+            order.push(path);
+        }
+        Ok(order)
+    }
+
+    /// Path in `src` of the chapter with the `path`, also finding a README chapter, which
+    /// is renamed into `index.md`, by its original name.
+    fn chapter_path(chapters: &[&Chapter], path: &Path, config: &Config) -> Option<PathBuf> {
+        let wanted = utils::epub_path(path);
+        chapters
+            .iter()
+            .filter_map(|ch| ch.path.as_ref())
+            .find(|path| {
+                utils::epub_path(path) == wanted
+                    || path.ends_with("index.md")
+                        && utils::epub_path(&path.with_file_name(&config.readme_filename)) == wanted
+            })
+            .cloned()
+    }
+
     /// Find assets for adding to the document later. For remote linked assets, they would be
     /// rendered differently in the document by provided information of assets.
     fn find_assets(&mut self) -> Result<(), Error> {
@@ -129,38 +843,361 @@ impl<'a> Generator<'a> {
         let error = String::from("Failed finding/fetch resource taken from content? Look up content for possible error...");
         // resources::find can emit very unclear error based on internal MD content,
         // so let's give a tip to user in error message
-        let assets = resource::find(self.ctx).map_err(|e| {
-            error!("{} Caused by: {}", error, e);
-            e
-        })?;
+        let warnings = self.warnings.get_mut();
+        let assets =
+            resource::find_with_warnings(self.ctx, &self.config, warnings).map_err(|e| {
+                error!("{} Caused by: {}", error, e);
+                e
+            })?;
         self.assets.extend(assets);
+        self.index_remote_assets();
         Ok(())
     }
 
-    fn generate_chapters(&mut self) -> Result<(), Error> {
-        info!("3. Generate chapters == ");
+    /// Index the remote and external assets by their URLs for rendering the chapters, which
+    /// has to be repeated after changing the assets.
+    fn index_remote_assets(&mut self) {
+        self.remote_assets.clear();
+        for (key, value) in self.assets.iter() {
+            trace!("{} / {:?}", key, value);
+            match value.source {
+                AssetKind::Remote(ref remote_url) => {
+                    trace!(
+                        "Adding remote_assets = '{}' / {:?}",
+                        remote_url.to_string(),
+                        value
+                    );
+                    self.remote_assets
+                        .insert(remote_url.to_string(), value.clone());
+                }
+                AssetKind::External(ref original_link) => {
+                    trace!(
+                        "Adding external asset = '{:?}' / {:?}",
+                        original_link,
+                        value
+                    );
+                    self.remote_assets
+                        .insert(original_link.to_string_lossy().to_string(), value.clone());
+                }
+                AssetKind::Local(_) => {}
+            }
+        }
+    }
+
+    /// Download the remote assets, up to `download_concurrency` at a time. Returns the media
+    /// types of the downloaded contents by the assets' links, if they are known, and the
+    /// links of the assets which failed to download unless `remote_failure_mode` is `fail`.
+    fn download_remote_assets(&self) -> Result<Downloads, Error> {
+        let remote: Vec<(&String, &Asset)> = self
+            .assets
+            .iter()
+            .filter(|(_, asset)| matches!(asset.source, AssetKind::Remote(_)))
+            .collect();
+        let workers = self.config.download_concurrency.min(remote.len());
+        let total = remote.len();
+        let queue = Mutex::new(remote.into_iter());
+        let failed = AtomicBool::new(false);
+        let completed = AtomicUsize::new(0);
+        let handler = &*self.handler;
+        let deadline = self.deadline;
+        let cancelled = &*self.cancelled;
+        let failure_mode = self.config.remote_failure_mode;
+        let progress = self.progress.as_deref();
+
+        let download = || -> Result<Downloads, Error> {
+            let mut downloaded_types = Vec::new();
+            let mut failed_links = Vec::new();
+            // the remaining downloads are skipped after a failure, the timeout or cancellation
+            while !failed.load(Ordering::Relaxed)
+                && !cancelled.load(Ordering::Relaxed)
+                && deadline.is_none_or(|d| Instant::now() <= d)
+            {
+                let next = queue.lock().expect("download queue is poisoned").next();
+                let Some((link, asset)) = next else {
+                    break;
+                };
+                let result = handler.download(asset);
+                // the downloads finish in any order, the position counts the finished ones
+                let position = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(progress) = progress {
+                    progress(Progress {
+                        stage: Stage::DownloadingAssets,
+                        position,
+                        total,
+                    });
+                }
+                match result {
+                    Ok(Some(mimetype)) => downloaded_types.push((link.clone(), mimetype)),
+                    Ok(None) => {}
+                    Err(error) if failure_mode != RemoteFailureMode::Fail => {
+                        warn!("Failed to download remote asset '{}': {}", link, error);
+                        failed_links.push(link.clone());
+                    }
+                    Err(error) => {
+                        failed.store(true, Ordering::Relaxed);
+                        return Err(error);
+                    }
+                }
+            }
+            Ok((downloaded_types, failed_links))
+        };
+        let downloaded_types = thread::scope(|scope| {
+            let workers: Vec<_> = (0..workers).map(|_| scope.spawn(download)).collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("download thread panicked"))
+                .collect::<Result<Vec<_>, Error>>()
+        })?;
+        self.check_deadline()?;
+        let (downloaded_types, failed_links): (Vec<_>, Vec<_>) =
+            downloaded_types.into_iter().unzip();
+        Ok((
+            downloaded_types.into_iter().flatten().collect(),
+            failed_links.into_iter().flatten().collect(),
+        ))
+    }
+
+    /// Download remote assets and let the ones with identical content, e.g. the same logo
+    /// referenced by slightly different URLs, share a single embedded file. Must be done
+    /// before rendering chapters, which rewrite the links by the asset's file name.
+    fn dedup_remote_assets(&mut self) -> Result<(), Error> {
+        // the downloads are compared by their SHA-256, streamed from the cached files
+        let mut by_content: HashMap<[u8; 32], Vec<String>> = HashMap::new();
+        let (downloaded_types, failed_links) = self.download_remote_assets()?;
+        // the failed ones are rendered by 'remote_failure_mode' instead of being embedded
+        for link in failed_links {
+            // the cause is logged when the download fails
+            self.warnings.borrow_mut().push(Warning::new(
+                WarningKind::FailedDownload,
+                format!("Failed to download remote asset '{link}'"),
+            ));
+            if let Some(Asset {
+                source: AssetKind::Remote(url),
+                mimetype,
+                ..
+            }) = self.assets.remove(&link)
+            {
+                self.failed_downloads.insert(url.to_string(), mimetype);
+            }
+        }
+        for (link, asset) in self.assets.iter() {
+            if !matches!(asset.source, AssetKind::Remote(_)) {
+                continue;
+            }
+            let mut content = self
+                .handler
+                .open(&asset.location_on_disk)
+                .map_err(|_| Error::AssetOpen)?;
+            let mut hasher = Sha256::new();
+            io::copy(&mut content, &mut hasher)?;
+            by_content
+                .entry(hasher.finalize().into())
+                .or_default()
+                .push(link.clone());
+        }
+        // the type of a redirected URL's content may differ from the one its link suggests
+        for (link, mimetype) in downloaded_types {
+            if let Some(asset) = self
+                .assets
+                .get_mut(&link)
+                .filter(|asset| self.config.mime_override(&asset.filename).is_none())
+            {
+                asset.set_mimetype(mimetype);
+            }
+        }
+
+        for links in by_content.into_values().filter(|links| links.len() > 1) {
+            // pick the same file for every run, regardless of the map's order
+            let Some(shared) = links
+                .iter()
+                .filter_map(|link| self.assets.get(link))
+                .min_by(|a, b| a.filename.cmp(&b.filename))
+                .cloned()
+            else {
+                continue;
+            };
+            for link in links {
+                if let Some(asset) = self.assets.get_mut(&link) {
+                    debug!(
+                        "Remote asset '{}' has the same content as {:?}",
+                        link, shared.filename
+                    );
+                    asset.filename = shared.filename.clone();
+                    asset.location_on_disk = shared.location_on_disk.clone();
+                }
+            }
+        }
+        self.index_remote_assets();
+        Ok(())
+    }
+
+    /// Encode the local images smaller than `inline_images_under_bytes` as `data:` URIs,
+    /// which replace their links in the chapters instead of embedding them as files.
+    fn inline_small_images(&mut self) -> Result<(), Error> {
+        let Some(limit) = self.config.inline_images_under_bytes else {
+            return Ok(());
+        };
+        let inlined = self.inline_images(self.assets.values(), Some(limit))?;
+        self.inlined_images = inlined;
+        Ok(())
+    }
+
+    /// `data:` URIs of the local images among the assets by their paths in `src`, only the
+    /// ones smaller than the `limit` of bytes if there is one.
+    fn inline_images<'i>(
+        &self,
+        assets: impl Iterator<Item = &'i Asset>,
+        limit: Option<u64>,
+    ) -> Result<HashMap<String, String>, Error> {
+        let mut inlined = HashMap::new();
+        for asset in assets {
+            if !matches!(asset.source, AssetKind::Local(_))
+                || asset.mimetype.type_() != mime_guess::mime::IMAGE
+            {
+                continue;
+            }
+            let size = std::fs::metadata(&asset.location_on_disk)?.len();
+            if limit.is_some_and(|limit| size >= limit) {
+                continue;
+            }
+            let mut content = Vec::new();
+            self.handler
+                .read(&asset.location_on_disk, &mut content)
+                .map_err(|_| Error::AssetOpen)?;
+            debug!("Inlining image {:?} of {} bytes", asset.filename, size);
+            inlined.insert(
+                utils::epub_path(&asset.filename),
+                format!("data:{};base64,{}", asset.mimetype, BASE64.encode(&content)),
+            );
+        }
+        Ok(inlined)
+    }
 
-        for (idx, item) in self.ctx.book.sections.iter().enumerate() {
-            let is_first = idx == 0;
-            if let BookItem::Chapter(ref ch) = *item {
-                trace!("Adding chapter \"{}\"", ch);
-                self.add_chapter(ch, Some(is_first))?;
+    fn generate_chapters(&mut self) -> Result<(), Error> {
+        info!("4. Generate chapters == ");
+        let total = self
+            .ctx
+            .book
+            .iter()
+            .filter(|item| matches!(item, BookItem::Chapter(_)))
+            .count();
+        self.chapter_count = (0, total);
+
+        // chapters after a part title are nested under it, if part titles are in the TOC
+        let mut top_level = 1;
+        for item in self.ctx.book.sections.iter() {
+            match *item {
+                BookItem::Chapter(ref ch) => {
+                    trace!("Adding chapter \"{}\"", ch);
+                    self.add_chapter(ch, top_level)?;
+                }
+                BookItem::PartTitle(ref title) if self.config.part_titles_in_toc => {
+                    debug!("Adding part title \"{}\" to the TOC", title);
+                    self.part_title = Some(title.clone());
+                    top_level = 2;
+                }
+                BookItem::PartTitle(ref title) => trace!("Skip part title: {}.", title),
+                BookItem::Separator => trace!("Skip separator."),
             }
         }
 
         Ok(())
     }
 
-    fn add_chapter(&mut self, ch: &Chapter, is_first: Option<bool>) -> Result<(), Error> {
+    /// Report the broken links to other chapters, which fail the build if `validate_links`
+    /// is enabled. All of them are listed at once, so they can be fixed in one go.
+    fn check_links(&self) -> Result<(), Error> {
+        let broken = self.broken_links.take();
+        if broken.is_empty() {
+            Ok(())
+        } else if self.config.validate_links {
+            Err(Error::BrokenLinks(broken))
+        } else {
+            self.warn(
+                WarningKind::BrokenLinks,
+                Error::BrokenLinks(broken).to_string(),
+            );
+            Ok(())
+        }
+    }
+
+    /// Log the warning and record it for the build's diagnostics.
+    fn warn(&self, kind: WarningKind, message: String) {
+        diagnostics::warn(&mut self.warnings.borrow_mut(), kind, message);
+    }
+
+    /// Add the "About this book" page as back matter, after all chapters.
+    fn add_about_page(&mut self) -> Result<(), Error> {
+        if !self.config.about_page {
+            return Ok(());
+        }
+        debug!("Adding the 'About this book' page");
+        let rendered = self.render_page(ABOUT_TITLE, &self.render_about_body(), None)?;
+        let content = EpubContent::new(ABOUT_PAGE, rendered.as_bytes())
+            .title(ABOUT_TITLE)
+            .reftype(ReferenceType::Colophon);
+        self.add_content(content)?;
+        Ok(())
+    }
+
+    /// Credits of the book from its metadata config, each entry is left out if not set.
+    fn render_about_body(&self) -> String {
+        let mut entries = String::new();
+        let mut add_entry = |term: &str, values: &[String]| {
+            if values.is_empty() {
+                return;
+            }
+            entries.push_str(&format!("<dt>{term}</dt>\n"));
+            for value in values {
+                entries.push_str(&format!("<dd>{}</dd>\n", utils::escape_html(value)));
+            }
+        };
+        let book = &self.ctx.config.book;
+        add_entry("Title", book.title.as_slice());
+        add_entry("Authors", &book.authors);
+        add_entry("Contributors", &self.config.contributors);
+        add_entry("Subjects", &self.config.subjects);
+        add_entry("License", self.config.rights_statement().as_slice());
+
+        format!(
+            "<div class=\"chapter about-page\">\n<h1>{ABOUT_TITLE}</h1>\n<dl>\n{entries}</dl>\n</div>\n"
+        )
+    }
+
+    /// Add the chapter and its sub-chapters, `level` is the chapter's depth in the book
+    /// hierarchy starting with 1 for top level chapters.
+    fn add_chapter(&mut self, ch: &Chapter, level: i32) -> Result<(), Error> {
         info!("Adding chapter = '{}'", &ch.name);
-        let rendered_result = self.render_chapter(ch);
+        self.check_deadline()?;
+        let (position, total) = (self.chapter_count.0 + 1, self.chapter_count.1);
+        self.chapter_count.0 = position;
+        self.report_progress(Stage::RenderingChapters, position, total);
+        if ch.path.is_none() {
+            if self.config.include_drafts {
+                return self.add_draft_placeholder(ch, level);
+            }
+            debug!("Draft chapter '{}' has no content, skipped", &ch.name);
+            self.warnings.borrow_mut().push(Warning::new(
+                WarningKind::SkippedDraft,
+                format!("Draft chapter '{}' has no content, skipped", ch.name),
+            ));
+            return Ok(());
+        }
+        let rendered_result = self.render_chapter_documents(ch);
         // let's skip chapter without content (drafts)
-        let rendered = match rendered_result {
+        let mut parts = match rendered_result {
             Ok(rendered_content) => rendered_content,
+            Err(error_msg) if self.config.strict_assets => {
+                error!("Failed to render chapter '{}': {}", &ch.name, error_msg);
+                return Err(error_msg.into());
+            }
             Err(error_msg) => {
-                warn!(
-                    "SKIPPED chapter '{}' due to error = {}",
-                    &ch.name, error_msg
+                self.warn(
+                    WarningKind::SkippedChapter,
+                    format!(
+                        "SKIPPED chapter '{}' due to error = {}",
+                        &ch.name, error_msg
+                    ),
                 );
                 return Ok(());
             }
@@ -178,126 +1215,466 @@ impl<'a> Generator<'a> {
             content_path
         );
         let path = content_path.with_extension("html").display().to_string();
-        let title = if self.config.no_section_label {
-            ch.name.clone()
-        } else if let Some(ref section_number) = ch.number {
-            format! {"{} {}", section_number, ch.name}
-        } else {
-            ch.name.clone()
-        };
+        let title = self.toc_title(ch);
+
+        if self.config.search_index {
+            // each part is indexed under its own document
+            for (i, part) in parts.iter().enumerate() {
+                let href = match i {
+                    0 => path.clone(),
+                    _ => content_path
+                        .with_file_name(&part.file_name)
+                        .display()
+                        .to_string(),
+                };
+                let title = part.heading.as_deref().unwrap_or(&ch.name);
+                self.search_index.add_document(&href, title, &part.text);
+            }
+        }
 
-        // If this is the first chapter, mark its type as Text (i.e. "bodymatter") for render_nav().
-        // This ensures at least one item in the nav.xhtml <nav epub:type="landmarks"><ol> list,
-        // otherwise epubcheck shows an error.
-        let mut content = match is_first {
-            Some(true) => EpubContent::new(path, rendered.as_bytes())
-                .title(title)
-                .reftype(epub_builder::ReferenceType::Text),
-            _ => EpubContent::new(path, rendered.as_bytes()).title(title),
-        };
+        let rest = parts.split_off(1);
+        let rendered = &parts[0].body;
+
+        // The start page is marked as Text (i.e. "bodymatter") for the
+        // <nav epub:type="landmarks"> list, otherwise epubcheck shows an error.
+        let mut content = EpubContent::new(path.clone(), rendered.as_bytes()).title(title);
+        if self.start_page.as_ref() == Some(content_path) {
+            content = content.reftype(ReferenceType::Text);
+        }
 
-        let level = ch.number.as_ref().map(|n| n.len() as i32 - 1).unwrap_or(0);
+        // epub-builder nests a TOC entry under the previous entry of a lower level.
+        // The depth in the hierarchy is used instead of the section number, as
+        // unnumbered chapters don't have one.
         content = content.level(level);
 
-        self.builder.add_content(content)?;
+        let content = self.with_part_title(content, &path, level);
+        self.add_content(content)?;
+        let mut documents = vec![path];
+        // the parts of a split chapter are nested under it in the TOC
+        for part in rest {
+            let part_path = content_path.with_file_name(&part.file_name);
+            let part_path = part_path.display().to_string();
+            let content = EpubContent::new(part_path.clone(), part.body.as_bytes())
+                .title(part.heading.unwrap_or_else(|| ch.name.clone()))
+                .level(level + 1);
+            self.add_content(content)?;
+            documents.push(part_path);
+        }
+        if self.spine_order.contains(content_path) {
+            self.spine_documents.insert(content_path.clone(), documents);
+        }
+        self.summary.chapters += 1;
+        if self.config.embed_sources {
+            self.embed_chapter_source(ch, content_path)?;
+        }
+
+        self.add_sub_chapters(ch, level)
+    }
 
-        // second pass to actually add the sub-chapters
+    /// Second pass to actually add the sub-chapters.
+    fn add_sub_chapters(&mut self, ch: &Chapter, level: i32) -> Result<(), Error> {
         for sub_item in &ch.sub_items {
             if let BookItem::Chapter(ref sub_ch) = *sub_item {
                 trace!("add sub-item = {:?}", sub_ch.name);
-                self.add_chapter(sub_ch, None)?;
+                self.add_chapter(sub_ch, level + 1)?;
             }
         }
+        Ok(())
+    }
 
+    /// Add the document to the book, recording its TOC entry and landmark for the
+    /// navigation documents and whether it contains MathML.
+    fn add_content(&mut self, content: EpubContent<&[u8]>) -> Result<(), Error> {
+        self.navigation.add_content(&content);
+        // EPUB 2 has no MathML, nor the manifest item property declaring it
+        if self.config.is_epub3() && content.content.windows(5).any(|tag| tag == b"<math") {
+            self.mathml_documents.push(content.toc.url.clone());
+        }
+        self.builder.add_content(content)?;
         Ok(())
     }
 
-    /// Render the chapter into its fully formed HTML representation.
-    fn render_chapter(&self, ch: &Chapter) -> Result<String, RenderError> {
-        let chapter_dir = if let Some(chapter_file_path) = &ch.path {
-            chapter_file_path.parent().ok_or_else(|| {
-                RenderError::from(RenderErrorReason::Other(format!(
-                    "No CSS found by a path = {:?}",
-                    ch.path
-                )))
-            })?
-        } else {
-            return Err(RenderError::from(RenderErrorReason::Other(format!(
-                "Draft chapter: '{}' could not be rendered.",
-                ch.name
-            ))));
+    /// Put the part title waiting for its first chapter in the TOC, with the chapter's entry
+    /// nested under it. A part title has no page of its own, so it links to the chapter.
+    fn with_part_title<R: Read>(
+        &mut self,
+        content: EpubContent<R>,
+        path: &str,
+        level: i32,
+    ) -> EpubContent<R> {
+        let Some(part_title) = self.part_title.take() else {
+            return content;
         };
+        let entry = TocElement::new(path, content.toc.title.clone()).level(level);
+        content.title(part_title).level(level - 1).child(entry)
+    }
+
+    /// Title of the chapter in the TOC, prefixed by its section number unless disabled.
+    fn toc_title(&self, ch: &Chapter) -> String {
+        if self.config.no_section_label {
+            ch.name.clone()
+        } else if let Some(ref section_number) = ch.number {
+            format! {"{} {}", section_number, ch.name}
+        } else {
+            ch.name.clone()
+        }
+    }
 
-        let mut body = String::with_capacity(3000); // big enough arbitrary size
+    /// Add a "Coming soon" page for a draft chapter, which has no content file of its own.
+    /// It's not a candidate for the "bodymatter" landmark.
+    fn add_draft_placeholder(&mut self, ch: &Chapter, level: i32) -> Result<(), Error> {
+        self.draft_count += 1;
+        let path = format!("draft-{}.html", self.draft_count);
+        debug!(
+            "Draft chapter '{}' is added as placeholder {}",
+            &ch.name, path
+        );
+        let rendered = self.render_page(&ch.name, &Self::render_draft_body(ch), Some(ch))?;
 
-        // if self.config.epub_version == Some(3) && self.config.footnote_backrefs {
-        // body.push_str(&self.render_with_footnote_backrefs(chapter_dir, ch));
-        // } else {
-        let parser = utils::create_new_pull_down_parser(&ch.content);
-        let mut quote_converter = QuoteConverterFilter::new(self.config.curly_quotes);
-        let ch_depth = chapter_dir.components().count();
+        let content = EpubContent::new(path.clone(), rendered.as_bytes())
+            .title(self.toc_title(ch))
+            .level(level);
+        let content = self.with_part_title(content, &path, level);
+        self.add_content(content)?;
+
+        self.add_sub_chapters(ch, level)
+    }
+
+    fn render_draft_body(ch: &Chapter) -> String {
+        format!(
+            "<div class=\"chapter draft-placeholder\">\n<h1>{}</h1>\n<p>Coming soon</p>\n</div>\n",
+            utils::escape_html(&ch.name)
+        )
+    }
 
-        // create 'Remote Assets' copy to be processed by AssetLinkFilter
-        let mut remote_assets: HashMap<String, Asset> = HashMap::new();
-        for (key, value) in self.assets.clone().into_iter() {
-            trace!("{} / {:?}", key, &value);
-            if let AssetKind::Remote(ref remote_url) = value.source {
-                trace!(
-                    "Adding remote_assets = '{}' / {:?}",
-                    remote_url.to_string(),
-                    &value
+    /// Add the chapter's markdown file as a resource, or its content if there is no such file
+    /// (e.g. a `README.md` renamed into `index.md` by mdbook).
+    fn embed_chapter_source(&mut self, ch: &Chapter, content_path: &Path) -> Result<(), Error> {
+        let source_file =
+            utils::src_dir(&self.ctx.root, &self.ctx.config.book.src).join(content_path);
+        let source = match std::fs::read(&source_file) {
+            Ok(source) => source,
+            Err(e) => {
+                debug!(
+                    "Embedding the content of '{}', its source {:?} is not readable: {}",
+                    ch.name, source_file, e
                 );
-                remote_assets.insert(remote_url.to_string(), value);
+                ch.content.clone().into_bytes()
             }
-        }
-        let asset_link_filter = AssetRemoteLinkFilter::new(&remote_assets, ch_depth);
+        };
+        let filename = utils::epub_path(&Path::new(SOURCES_FOLDER).join(content_path));
+        debug!("Embedding chapter source: {}", filename);
+        self.builder
+            .add_resource(filename, source.as_slice(), "text/markdown")?;
+        Ok(())
+    }
 
-        let mut footnote_filter =
-            if self.config.epub_version == Some(3) && self.config.footnote_backrefs {
-                FootnoteFilter::new(self.config.footnote_backrefs)
-            } else {
-                FootnoteFilter::new(false)
-            };
+    /// Render the chapter into the XHTML document which the EPUB contains for it, without
+    /// splitting it, e.g. for tools reusing the rendering without generating an EPUB. It's
+    /// rendered by the same filters, e.g. of footnotes, quotes and links to the assets, which
+    /// are found by the first call. Draft chapters have no content to render.
+    pub fn render_chapter_xhtml(&mut self, ch: &Chapter) -> Result<String, Error> {
+        if !self.assets_found {
+            self.find_assets()?;
+            self.assets_found = true;
+        }
+        let body = self.render_chapter_body(ch)?;
+        Ok(self.render_chapter_page(ch, &body)?)
+    }
 
-        let events = parser
-            .map(|event| quote_converter.apply(event))
-            .map(|event| asset_link_filter.apply(event))
-            .filter_map(|event| footnote_filter.apply(event));
+    /// Render the chapter into its fully formed HTML representation, without splitting it.
+    #[cfg(test)]
+    fn render_chapter(&self, ch: &Chapter) -> Result<String, RenderError> {
+        let body = self.render_chapter_body(ch)?;
+        self.render_chapter_page(ch, &body)
+    }
 
-        trace!("Found Rendering events map = [{:?}]", &events);
+    /// Render a page of the chapter's `body`, titled by its front matter's title if it has one.
+    fn render_chapter_page(&self, ch: &Chapter, body: &str) -> Result<String, RenderError> {
+        let (front_matter, _) = self.split_front_matter(ch);
+        let title = front_matter.get("title").unwrap_or(&ch.name);
+        self.render_page(title, body, Some(ch))
+    }
 
-        html::push_html(&mut body, events);
+    /// The chapter's front matter fields and content without it, if `front_matter` is enabled.
+    fn split_front_matter<'c>(&self, ch: &'c Chapter) -> (BTreeMap<String, String>, &'c str) {
+        if self.config.front_matter {
+            utils::split_front_matter(&ch.content)
+        } else {
+            (BTreeMap::new(), &ch.content)
+        }
+    }
 
-        if !footnote_filter.is_empty() {
+    /// Render a page of the `body` by the template. The stylesheet is linked relative to
+    /// the `chapter`'s file, pages without one are placed in the root folder.
+    fn render_page(
+        &self,
+        title: &str,
+        body: &str,
+        chapter: Option<&Chapter>,
+    ) -> Result<String, RenderError> {
+        let chapter_path = chapter.and_then(|ch| ch.path.as_deref());
+        let chapter_dir = chapter_path.and_then(Path::parent).unwrap_or(Path::new(""));
+        let mut names = vec![STYLESHEET.to_string()];
+        if self.config.separate_stylesheets {
+            names.extend(
+                self.config
+                    .additional_css
+                    .iter()
+                    .map(|css| Self::stylesheet_filename(css)),
+            );
+        }
+        let stylesheets: Vec<String> = names
+            .iter()
+            .map(|name| {
+                chapter_dir
+                    .components()
+                    .map(|_| "..")
+                    .chain(iter::once(name.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect();
+
+        let ctx = TemplateContext {
+            epub_version_3: self.config.is_epub3(),
+            title,
+            body,
+            stylesheet: stylesheets[0].clone(),
+            stylesheets,
+            chapter_number: chapter
+                .and_then(|ch| ch.number.as_ref())
+                .map(ToString::to_string),
+            chapter_path: chapter_path.map(utils::epub_path),
+            front_matter: chapter
+                .map(|ch| self.split_front_matter(ch).0)
+                .unwrap_or_default(),
+        };
+
+        self.hbs.render(self.template_name(chapter_path), &ctx)
+    }
+
+    /// Name of the template of the chapter with the path in `src`, the default `index` one
+    /// if it doesn't match any chapter template's pattern.
+    fn template_name(&self, chapter_path: Option<&Path>) -> &str {
+        let Some(path) = chapter_path.map(utils::epub_path) else {
+            return "index";
+        };
+        self.chapter_templates
+            .iter()
+            .find(|(pattern, _)| utils::glob_match(pattern, &path))
+            .map_or("index", |(_, name)| name.as_str())
+    }
+
+    /// Render the chapter's documents, several ones if it's split at its headings by
+    /// `split-on-heading-level`, linked to each other.
+    fn render_chapter_documents(&self, ch: &Chapter) -> Result<Vec<ChapterPart>, RenderError> {
+        let split_level = self
+            .config
+            .split_on_heading_level
+            .and_then(|level| HeadingLevel::try_from(level as usize).ok());
+        let mut parts = self.render_chapter_parts(ch, split_level)?;
+        split::link_anchors(&mut parts);
+        split::add_cross_links(&mut parts, &ch.name);
+
+        for part in parts.iter_mut() {
+            part.body = self.render_chapter_page(ch, &part.body)?;
+        }
+        Ok(parts)
+    }
+
+    /// Render the chapter's content, without the surrounding document.
+    fn render_chapter_body(&self, ch: &Chapter) -> Result<String, RenderError> {
+        let mut parts = self.render_chapter_parts(ch, None)?;
+        Ok(parts.swap_remove(0).body)
+    }
+
+    /// Render the chapter's content split into parts at its headings of the level, without
+    /// the surrounding documents.
+    fn render_chapter_parts(
+        &self,
+        ch: &Chapter,
+        split_level: Option<HeadingLevel>,
+    ) -> Result<Vec<ChapterPart>, RenderError> {
+        let chapter_dir = if let Some(chapter_file_path) = &ch.path {
+            chapter_file_path.parent().ok_or_else(|| {
+                RenderError::from(RenderErrorReason::Other(format!(
+                    "No CSS found by a path = {:?}",
+                    ch.path
+                )))
+            })?
+        } else {
+            return Err(RenderError::from(RenderErrorReason::Other(format!(
+                "Draft chapter: '{}' could not be rendered.",
+                ch.name
+            ))));
+        };
+
+        // if self.config.is_epub3() && self.config.footnote_backrefs {
+        // body.push_str(&self.render_with_footnote_backrefs(chapter_dir, ch));
+        // } else {
+        let (_, content) = self.split_front_matter(ch);
+        let parser = utils::create_new_pull_down_parser(content);
+        let mut admonition_filter = AdmonitionFilter::new(self.config.admonitions);
+        let mut figure_filter =
+            FigureFilter::new(self.config.figure_captions, self.config.is_epub3());
+        let mut quote_converter = QuoteConverterFilter::new(self.config.curly_quotes)
+            .with_language(self.ctx.config.book.language.as_deref());
+        let mut alt_text_filter = AltTextFilter::new(self.config.require_alt_text);
+        let mut internal_link_filter = InternalLinkFilter::new(&self.chapter_pages, chapter_dir);
+        let ch_depth = chapter_dir.components().count();
+
+        let mut asset_link_filter = AssetRemoteLinkFilter::new(&self.remote_assets, ch_depth)
+            .with_inlined(&self.inlined_images, chapter_dir)
+            .with_root_relative(self.config.root_relative_links)
+            .with_failed(&self.failed_downloads, self.config.remote_failure_mode);
+
+        let mut footnote_filter = if self.config.is_epub3() && self.config.footnote_backrefs {
+            FootnoteFilter::new(self.config.footnote_backrefs)
+        } else {
+            FootnoteFilter::new(false)
+        };
+
+        let details_filter = DetailsFilter::new(self.config.expand_details);
+        let mut caption_numbering_filter =
+            CaptionNumberingFilter::new(self.config.number_captions, self.caption_numbers.get());
+        // the edition is serialized as its year, e.g. "2021"
+        let edition = self
+            .ctx
+            .config
+            .rust
+            .edition
+            .and_then(|e| serde_json::to_value(e).ok())
+            .and_then(|e| e.as_str().map(String::from));
+        let mut playground_filter = PlaygroundFilter::new(self.config.playground_links, edition);
+        let mut hidden_lines_filter = HiddenLinesFilter::new(self.config.hide_boilerplate);
+        let mut code_block_filter = CodeBlockFilter::new(
+            self.config.code_block_max_lines,
+            self.config.code_block_overflow,
+            // MathML is only supported by EPUB 3
+            self.config.math_code_blocks && self.config.is_epub3(),
+        )
+        .with_mermaid(self.config.mermaid_command.clone(), ch_depth)
+        .with_grouping(self.config.group_code_blocks);
+
+        let events = parser
+            .flat_map(|event| admonition_filter.apply(event))
+            // the end of the chapter is marked for a trailing image's caption
+            .map(Some)
+            .chain(iter::once(None))
+            .flat_map(|event| figure_filter.apply(event))
+            .map(|event| quote_converter.apply(event))
+            .flat_map(|event| alt_text_filter.apply(event))
+            .map(|event| internal_link_filter.apply(event))
+            .flat_map(|event| asset_link_filter.apply(event))
+            .map(|event| details_filter.apply(event))
+            .map(TaskListFilter::apply)
+            .flat_map(|event| caption_numbering_filter.apply(event))
+            .filter_map(|event| footnote_filter.apply(event))
+            .flat_map(|event| playground_filter.apply(event))
+            .flat_map(|event| hidden_lines_filter.apply(event))
+            .flat_map(|event| code_block_filter.apply(event));
+
+        trace!("Found Rendering events map = [{:?}]", &events);
+
+        let page = ch
+            .path
+            .as_deref()
+            .unwrap_or(Path::new(""))
+            .with_extension("html");
+        let page = page.file_name().unwrap_or_default().to_string_lossy();
+        let mut parts = split::render_parts(events, split_level, &page);
+        if !alt_text_filter.missing().is_empty() {
+            let message = format!(
+                "Chapter '{}' has images without alt text: {}",
+                ch.name,
+                alt_text_filter.missing().join(", ")
+            );
+            if self.config.strict_assets {
+                return Err(RenderError::from(RenderErrorReason::Other(message)));
+            }
+            self.warn(WarningKind::MissingAltText, message);
+        }
+        self.broken_links.borrow_mut().extend(
+            internal_link_filter
+                .broken()
+                .iter()
+                .map(|link| format!("'{link}' in chapter '{}'", ch.name)),
+        );
+        self.caption_numbers.set(caption_numbering_filter.numbers());
+        self.diagrams
+            .borrow_mut()
+            .extend(code_block_filter.take_diagrams());
+
+        // the footnotes are listed at the end of the chapter's last part
+        let body = &mut parts.last_mut().expect("a chapter has a part").body;
+        if !footnote_filter.is_empty() {
             footnote_filter.retain();
             footnote_filter.sort_by_cached_key();
             body.push_str("<div class=\"footnotes\" epub:type=\"footnotes\">\n");
             // let events = parser.filter_map(|event| footnote_filter.apply(event));
             let events = footnote_filter.get_events();
-            html::push_html(&mut body, events);
+            html::push_html(body, events);
             // body.push_str("</ol>\n");
             body.push_str("</div>\n");
         }
 
-        trace!("Chapter content after Events processing = [{:?}]", body);
-
-        let stylesheet_path = chapter_dir
-            .components()
-            .map(|_| "..")
-            .chain(iter::once("stylesheet.css"))
-            .collect::<Vec<_>>()
-            .join("/");
+        trace!("Chapter content after Events processing = [{:?}]", parts);
+
+        let epub_version_3 = self.config.is_epub3();
+        for part in parts.iter_mut() {
+            let mut body = std::mem::take(&mut part.body);
+
+            // semantic wrapper around the whole chapter content, e.g. <section epub:type="chapter">
+            if let Some(ref element) = self.config.chapter_wrapper {
+                let wrapper_type = self
+                    .config
+                    .chapter_wrapper_type
+                    .as_deref()
+                    .unwrap_or("chapter");
+                let attribute = if epub_version_3 { "epub:type" } else { "class" };
+                body = format!("<{element} {attribute}=\"{wrapper_type}\">\n{body}</{element}>\n");
+            }
 
-        let epub_version_3 = self.config.epub_version == Some(3);
+            // per chapter class names, so chapters can be styled individually by 'additional-css'
+            if let Some(ref path) = ch.path {
+                let slug = utils::chapter_slug(path, self.config.transliterate_slugs);
+                let divider = if self.config.section_dividers && utils::is_heading_only(&ch.content)
+                {
+                    debug!("Chapter '{}' is rendered as a section divider", ch.name);
+                    if epub_version_3 {
+                        " section-divider\" epub:type=\"part"
+                    } else {
+                        " section-divider"
+                    }
+                } else {
+                    ""
+                };
+                let start = if self.config.chapter_page_breaks && ch.parent_names.is_empty() {
+                    " chapter-start"
+                } else {
+                    ""
+                };
+                body = format!(
+                    "<div class=\"chapter chapter-{slug}{start}{divider}\">\n{body}</div>\n"
+                );
+            }
+            part.body = body;
+        }
 
-        let ctx = json!({
-            "epub_version_3": epub_version_3,
-            "title": ch.name,
-            "body": body,
-            "stylesheet": stylesheet_path
-        });
+        Ok(parts)
+    }
 
-        self.hbs.render("index", &ctx)
+    /// Add the diagrams rendered while generating the chapters.
+    fn embed_diagrams(&mut self) -> Result<(), Error> {
+        let mut diagrams: Vec<_> = self.diagrams.take().into_iter().collect();
+        diagrams.sort();
+        for (filename, svg) in diagrams {
+            debug!("Embedding diagram: {} ({} bytes)", filename, svg.len());
+            self.builder
+                .add_resource(&filename, svg.as_slice(), "image/svg+xml")?;
+        }
+        Ok(())
     }
 
     /// Generate the stylesheet and add it to the document.
@@ -306,31 +1683,126 @@ impl<'a> Generator<'a> {
 
         let stylesheet = self.generate_stylesheet()?;
         self.builder.stylesheet(stylesheet.as_slice())?;
+        for (name, full_path) in self.separate_stylesheets()? {
+            debug!("Embedding stylesheet {:?} as {}", full_path, name);
+            let content = File::open(&full_path).map_err(|_| Error::CssOpen(full_path))?;
+            self.builder.add_resource(name, content, "text/css")?;
+        }
+
+        for font in self.config.fonts.iter() {
+            let full_path = self.font_location(font)?;
+            // validated on creating the generator
+            let mt = utils::font_mime_type(font).unwrap_or_default();
+            debug!("Embedding font {:?} as {}", full_path, mt);
+            let content = File::open(&full_path).map_err(|_| Error::AssetOpen)?;
+            self.builder
+                .add_resource(Self::font_filename(font), content, mt)?;
+        }
 
         Ok(())
     }
 
+    // Font files are looked up by the path itself first, then relative to the book root
+    fn font_location(&self, font: &Path) -> Result<PathBuf, Error> {
+        utils::canonicalize(font)
+            .or_else(|_| utils::canonicalize(self.ctx.root.join(font)))
+            .map_err(|e| {
+                Error::AssetFileNotFound(format!("Font file was not found: {font:?}, error = {e}"))
+            })
+    }
+
+    /// File names and paths of the `additional-css` stylesheets which are embedded as
+    /// files of their own, if `separate-stylesheets` is enabled.
+    fn separate_stylesheets(&self) -> Result<Vec<(String, PathBuf)>, Error> {
+        if !self.config.separate_stylesheets {
+            return Ok(Vec::new());
+        }
+        let names = self
+            .config
+            .additional_css
+            .iter()
+            .map(|css| Self::stylesheet_filename(css));
+        Ok(names.zip(self.additional_css_paths()?).collect())
+    }
+
+    // Embedded stylesheet path, next to the book's 'stylesheet.css'
+    fn stylesheet_filename(css: &Path) -> String {
+        css.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    // Embedded font file path, relative to the stylesheet
+    fn font_filename(font: &Path) -> String {
+        let name = font.file_name().unwrap_or_default().to_string_lossy();
+        format!("fonts/{name}")
+    }
+
     fn additional_assets(&mut self) -> Result<(), Error> {
         info!(
             "6. Embedding, downloading additional assets == [{:?}]",
             self.assets.len()
         );
 
-        // TODO: have a list of Asset URLs and try to download all of them (in parallel?)
-        // to a temporary location.
-        let mut count = 0;
-        for asset in self.assets.values() {
+        // remote assets are downloaded in parallel already, only cached files are read here
+        let (mut local_count, mut remote_count) = (0, 0);
+        // the same external asset can be referenced by different relative links
+        let mut embedded: HashSet<&PathBuf> = HashSet::new();
+        let total = self.assets.len();
+        for (i, asset) in self.assets.values().enumerate() {
+            self.report_progress(Stage::EmbeddingAssets, i + 1, total);
+            if !embedded.insert(&asset.filename) {
+                debug!("Asset {:?} is already embedded", asset.filename);
+                continue;
+            }
+            if matches!(asset.source, AssetKind::Local(_))
+                && self
+                    .inlined_images
+                    .contains_key(&utils::epub_path(&asset.filename))
+            {
+                debug!("Asset {:?} is inlined into the chapters", asset.filename);
+                continue;
+            }
+            self.check_deadline()?;
             self.handler.download(asset)?;
+            self.check_deadline()?;
             debug!("Adding asset : {:?}", asset);
-            let mut content = Vec::new();
-            self.handler
-                .read(&asset.location_on_disk, &mut content)
+            // streamed into the EPUB, big media files aren't held in memory
+            let content = self
+                .handler
+                .open(&asset.location_on_disk)
                 .map_err(|_| Error::AssetOpen)?;
             let mt = asset.mimetype.to_string();
-            self.builder.add_resource(&asset.filename, &*content, mt)?;
-            count += 1;
+            // the OPF requires '/' separators on every platform
+            self.builder
+                .add_resource(utils::epub_path(&asset.filename), content, mt)?;
+            match asset.source {
+                AssetKind::Remote(_) => remote_count += 1,
+                AssetKind::Local(_) | AssetKind::External(_) => local_count += 1,
+            }
+        }
+        debug!(
+            "Embedded '{}' local and '{}' remote additional assets",
+            local_count, remote_count
+        );
+        let placeholder_shown = matches!(
+            self.config.remote_failure_mode,
+            RemoteFailureMode::EmbedPlaceholder | RemoteFailureMode::Both
+        );
+        if placeholder_shown && !self.failed_downloads.is_empty() {
+            debug!(
+                "Adding the placeholder of {} remote images",
+                self.failed_downloads.len()
+            );
+            self.builder.add_resource(
+                REMOTE_PLACEHOLDER,
+                REMOTE_PLACEHOLDER_SVG.as_bytes(),
+                "image/svg+xml",
+            )?;
         }
-        debug!("Embedded '{}' additional assets", count);
+        self.summary.local_assets = local_count;
+        self.summary.remote_assets = remote_count;
         Ok(())
     }
 
@@ -338,40 +1810,11 @@ impl<'a> Generator<'a> {
         info!("7. Embedding additional resources ==");
 
         let mut count = 0;
-        for path in self.config.additional_resources.iter() {
-            debug!("Embedding resource: {:?}", path);
-
-            let full_path: PathBuf;
-            if let Ok(full_path_internal) = path.canonicalize() {
-                // try process by 'path only' first
-                debug!("Found resource by a path = {:?}", full_path_internal);
-                full_path = full_path_internal; // OK
-            } else {
-                debug!("Failed to find resource by path, trying to compose 'root + src + path'...");
-                // try process by using 'root + src + path'
-                let full_path_composed = self
-                    .ctx
-                    .root
-                    .join(self.ctx.config.book.src.clone())
-                    .join(path);
-                debug!("Try embed resource by a path = {:?}", full_path_composed);
-                if let Ok(full_path_src) = full_path_composed.canonicalize() {
-                    full_path = full_path_src; // OK
-                } else {
-                    // try process by using 'root + path' finally
-                    let mut error = format!(
-                        "Failed to find resource file by 'root + src + path' = {full_path_composed:?}"
-                    );
-                    warn!("{:?}", error);
-                    debug!("Failed to find resource, trying to compose by 'root + path' only...");
-                    let full_path_composed = self.ctx.root.join(path);
-                    error = format!(
-                        "Failed to find resource file by a root + path = {full_path_composed:?}"
-                    );
-                    full_path = full_path_composed.canonicalize().expect(&error);
-                }
-            }
-            let mt = mime_guess::from_path(&full_path).first_or_octet_stream();
+        for (path, full_path) in self.find_additional_resources()? {
+            let mt = self
+                .config
+                .mime_override(&full_path)
+                .unwrap_or_else(|| utils::mime_type(&full_path));
 
             let content = File::open(&full_path).map_err(|_| Error::AssetOpen)?;
             debug!(
@@ -387,64 +1830,185 @@ impl<'a> Generator<'a> {
         Ok(())
     }
 
-    fn add_cover_image(&mut self) -> Result<(), Error> {
-        info!("4. Adding cover image ==");
+    /// The `additional_resources` by their paths inside the EPUB and on disk. Glob patterns,
+    /// e.g. `fonts/*.woff2`, are expanded relative to the book root.
+    fn find_additional_resources(&self) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+        let mut resources = Vec::new();
+        for path in self.config.additional_resources.iter() {
+            let pattern = path.to_string_lossy();
+            if utils::is_glob(&pattern) {
+                let files = utils::glob_files(&self.ctx.root, &pattern)?;
+                if files.is_empty() {
+                    warn!("No additional resources match {:?}", pattern);
+                }
+                for file in files {
+                    debug!("Resource {:?} matches {:?}", file, pattern);
+                    resources.push((PathBuf::from(&file), self.ctx.root.join(&file)));
+                }
+                continue;
+            }
+            debug!("Embedding resource: {:?}", path);
+            let src_dir = utils::src_dir(&self.ctx.root, &self.ctx.config.book.src);
+            let full_path = Self::resolve_resource(
+                "Additional resource",
+                path,
+                &[src_dir, self.ctx.root.clone()],
+            )?;
+            resources.push((path.clone(), full_path));
+        }
+        Ok(resources)
+    }
 
-        if let Some(ref path) = self.config.cover_image {
-            let full_path: PathBuf;
-            if let Ok(full_path_internal) = path.canonicalize() {
-                debug!("Found resource by a path = {:?}", full_path_internal);
-                full_path = full_path_internal;
-            } else {
-                debug!("Failed to find resource, trying to compose path...");
-                let full_path_composed = self
-                    .ctx
-                    .root
-                    .join(self.ctx.config.book.src.clone())
-                    .join(path);
-                debug!("Try cover image by a path = {:?}", full_path_composed);
-                let error = format!(
-                    "Failed to find cover image by full path-name = {full_path_composed:?}"
-                );
-                full_path = full_path_composed.canonicalize().expect(&error);
+    /// Paths of the `additional_css` stylesheets, relative to the current directory or the
+    /// book root.
+    fn additional_css_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        self.config
+            .additional_css
+            .iter()
+            .map(|css| {
+                Self::resolve_resource("Stylesheet", css, std::slice::from_ref(&self.ctx.root))
+            })
+            .collect()
+    }
+
+    /// Path of the `cover_image`, relative to the current directory or the book's `src`.
+    fn cover_image_path(&self, path: &Path) -> Result<PathBuf, Error> {
+        let src_dir = utils::src_dir(&self.ctx.root, &self.ctx.config.book.src);
+        Self::resolve_resource("Cover image", path, &[src_dir])
+    }
+
+    /// Canonical path of a file configured by its `path`, relative to the current directory
+    /// or else to one of the `folders`.
+    fn resolve_resource(kind: &str, path: &Path, folders: &[PathBuf]) -> Result<PathBuf, Error> {
+        let mut tried = vec![path.to_path_buf()];
+        tried.extend(folders.iter().map(|folder| folder.join(path)));
+        for candidate in tried.iter() {
+            if let Ok(full_path) = utils::canonicalize(candidate) {
+                debug!("Found {} by a path = {:?}", kind, full_path);
+                return Ok(full_path);
             }
-            let mt = mime_guess::from_path(&full_path).first_or_octet_stream();
+            debug!("{} isn't found by a path = {:?}", kind, candidate);
+        }
+        Err(Error::ResourceNotFound {
+            kind: kind.to_string(),
+            path: path.to_path_buf(),
+            tried,
+        })
+    }
+
+    /// Check that the configured files, i.e. the cover image, additional resources and
+    /// stylesheets, exist before any chapter is rendered.
+    fn check_resources(&self) -> Result<(), Error> {
+        if let Some(ref path) = self.config.cover_image {
+            self.cover_image_path(path)?;
+        }
+        self.find_additional_resources()?;
+        self.additional_css_paths()?;
+        Ok(())
+    }
+
+    /// Embed the inverted index over chapter texts as a JSON resource.
+    fn embed_search_index(&mut self) -> Result<(), Error> {
+        if !self.config.search_index {
+            return Ok(());
+        }
+        info!("8. Embedding search index ==");
+        if self.search_index.is_empty() {
+            debug!("No chapters were indexed, skip embedding search index");
+            return Ok(());
+        }
+        let index = self.search_index.to_json();
+        self.builder
+            .add_resource(SEARCH_INDEX_FILE, index.as_bytes(), "application/json")?;
+        Ok(())
+    }
+
+    fn add_cover_image(&mut self) -> Result<(), Error> {
+        info!("3. Adding cover image ==");
+
+        if let Some(path) = self.config.cover_image.clone() {
+            let full_path = self.cover_image_path(&path)?;
+            let mt = self
+                .config
+                .mime_override(&full_path)
+                .unwrap_or_else(|| utils::mime_type(&full_path));
 
             let content = File::open(&full_path).map_err(|_| Error::AssetOpen)?;
             debug!("Adding cover image: {:?} / {:?} ", path, mt.to_string());
+            // epub-builder marks it by the `cover` meta and the `cover-image` manifest
+            // property, which readers look up by its href
             self.builder
-                .add_cover_image(path, content, mt.to_string())?;
+                .add_cover_image(utils::epub_path(&path), content, mt.to_string())?;
+            self.add_cover_page(&path)?;
+        } else if self.config.generate_cover {
+            let title = self.ctx.config.book.title.clone().unwrap_or_default();
+            let authors = self.ctx.config.book.authors.join(", ");
+            let [width, height] = self.config.cover_size;
+            let png = cover::generate_cover(&title, &authors, width, height)?;
+            debug!("Adding generated cover image: {} bytes", png.len());
+            self.builder
+                .add_cover_image("cover.png", png.as_slice(), "image/png")?;
+            self.add_cover_page(Path::new("cover.png"))?;
         }
 
         Ok(())
     }
 
-    /// Concatenate all provided stylesheets into one long stylesheet.
+    // XHTML page showing the cover image, referenced by the "cover" landmark
+    fn add_cover_page(&mut self, image: &Path) -> Result<(), Error> {
+        let image = utils::epub_path(image);
+        let body = format!(
+            "<img src=\"{}\" alt=\"Cover\" />",
+            utils::escape_html(&image)
+        );
+        let page = self.render_page("Cover", &body, None)?;
+        self.add_content(
+            EpubContent::new(COVER_PAGE_FILE, page.as_bytes())
+                .title("Cover")
+                .reftype(ReferenceType::Cover),
+        )?;
+        Ok(())
+    }
+
+    /// Concatenate all provided stylesheets into one long stylesheet, without the
+    /// `additional-css` ones if they are embedded separately.
     fn generate_stylesheet(&self) -> Result<Vec<u8>, Error> {
         let mut stylesheet = Vec::new();
 
-        if self.config.use_default_css {
-            stylesheet.extend(DEFAULT_CSS.as_bytes());
+        match self.config.stylesheet_mode() {
+            CssMode::Full => stylesheet.extend(DEFAULT_CSS.as_bytes()),
+            CssMode::Minimal => stylesheet.extend(MINIMAL_CSS.as_bytes()),
+            CssMode::None => {}
+        }
+        // before the additional stylesheets, so they can still override it
+        if self.config.constrain_image_height {
+            stylesheet.extend(CONSTRAIN_IMAGE_HEIGHT_CSS.as_bytes());
+        }
+        if self.config.chapter_page_breaks {
+            stylesheet.extend(CHAPTER_PAGE_BREAKS_CSS.as_bytes());
         }
 
-        for additional_css in &self.config.additional_css {
-            debug!("generating stylesheet: {:?}", &additional_css);
-            let full_path: PathBuf;
-            if let Ok(full_path_internal) = additional_css.canonicalize() {
-                debug!("Found stylesheet by a path = {:?}", full_path_internal);
-                full_path = full_path_internal;
-            } else {
-                debug!("Failed to find stylesheet, trying to compose path...");
-                let full_path_composed = self.ctx.root.join(additional_css);
-                debug!("Try stylesheet by a path = {:?}", full_path_composed);
-                let error =
-                    format!("Failed to find stylesheet by full path-name = {full_path_composed:?}");
-                full_path = full_path_composed.canonicalize().expect(&error);
-            }
+        let concatenated = if self.config.separate_stylesheets {
+            Vec::new()
+        } else {
+            self.additional_css_paths()?
+        };
+        for full_path in concatenated {
+            debug!("generating stylesheet: {:?}", &full_path);
             let mut f = File::open(&full_path).map_err(|_| Error::CssOpen(full_path.clone()))?;
             f.read_to_end(&mut stylesheet)
                 .map_err(|_| Error::StylesheetRead)?;
         }
+
+        for font in &self.config.fonts {
+            let family = font.file_stem().unwrap_or_default().to_string_lossy();
+            let font_face = format!(
+                "\n@font-face {{\n  font-family: \"{}\";\n  src: url(\"{}\");\n}}\n",
+                family,
+                Self::font_filename(font)
+            );
+            stylesheet.extend(font_face.as_bytes());
+        }
         debug!("found style(s) = [{}]", stylesheet.len());
         Ok(stylesheet)
     }
@@ -467,7 +2031,8 @@ mod tests {
     use crate::resources::asset::AssetKind;
     use crate::resources::retrieve::MockContentRetriever;
     use mime_guess::mime;
-    use std::path::Path;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Arc, Barrier};
     use tempfile::TempDir;
     use url::Url;
 
@@ -488,11 +2053,12 @@ mod tests {
         let ctx = RenderContext::from_json(json.as_bytes()).unwrap();
 
         let mut mock_client = MockContentRetriever::new();
-        mock_client.expect_download().times(3).returning(|_| Ok(()));
+        mock_client
+            .expect_download()
+            .times(3)
+            .returning(|_| Ok(None));
         // checks local path of assets
-        let book_source = PathBuf::from(&ctx.root)
-            .join(&ctx.config.book.src)
-            .canonicalize()
+        let book_source = utils::canonicalize(PathBuf::from(&ctx.root).join(&ctx.config.book.src))
             .expect(
                 format!(
                     "book source root is not found: {}",
@@ -506,10 +2072,10 @@ mod tests {
         let should_be_url = destination.as_path().join(hashed_filename);
         for should_be in [should_be_svg, should_be_png, should_be_url] {
             mock_client
-                .expect_read()
+                .expect_open()
                 .times(1)
-                .withf(move |path, _| path == should_be)
-                .returning(|_, _| Ok(()));
+                .withf(move |path| path == should_be)
+                .returning(|_| Ok(Box::new(std::io::empty())));
         }
 
         let mut g = Generator::new_with_handler(&ctx, mock_client).unwrap();
@@ -519,93 +2085,599 @@ mod tests {
     }
 
     #[test]
-    fn render_assets() {
-        let links = [
-            "local.webp",
-            "http://server/remote.svg",
-            "http://server/link.png",
-        ];
+    fn load_assets_from_asset_root() {
+        let link = "../theme/rust-logo.png";
+        let content = format!("# Chapter 1\n\n![Rust Logo]({link})\n");
         let tmp_dir = TempDir::new().unwrap();
-        let root = tmp_dir.path().join("mdbook-epub");
-        let mut assets = HashMap::new();
-        assets.insert(
-            links[0].to_string(),
-            Asset {
-                location_on_disk: root.as_path().join("src").join(links[0]),
-                filename: PathBuf::from(links[0]),
-                mimetype: "image/webp".parse::<mime::Mime>().unwrap(),
-                source: AssetKind::Local(PathBuf::from(links[0])),
-            },
-        );
-        let url = Url::parse(links[1]).unwrap();
-        let hashed_filename = utils::hash_link(&url);
-        let hashed_path = Path::new("cache").join(&hashed_filename);
-        assets.insert(
-            links[1].to_string(),
-            Asset {
-                location_on_disk: root.as_path().join("book").join(&hashed_path),
-                filename: hashed_path,
-                mimetype: "image/svg+xml".parse::<mime::Mime>().unwrap(),
-                source: AssetKind::Remote(url),
-            },
-        );
-        let markdown_str = format!(
-            "Chapter 1\n\
-            =====\n\n\
-            * [link]({})\n\
-            * ![Local Image]({})\n\
-            * <img alt=\"Remote Image\" src=\"{}\" >\n",
-            links[2], links[0], links[1]
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(&content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["asset-roots"] = json!(["theme"]);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let should_be =
+            utils::canonicalize(PathBuf::from(&ctx.root).join("theme").join("rust-logo.png"))
+                .unwrap();
+        let mut mock_client = MockContentRetriever::new();
+        mock_client
+            .expect_download()
+            .times(1)
+            .returning(|_| Ok(None));
+        mock_client
+            .expect_open()
+            .times(1)
+            .withf(move |path| path == should_be)
+            .returning(|_| Ok(Box::new(std::io::empty())));
+
+        let mut g = Generator::new_with_handler(&ctx, mock_client).unwrap();
+        g.find_assets().unwrap();
+        assert_eq!(g.assets.len(), 1);
+        assert_eq!(
+            g.assets.get(link).unwrap().filename,
+            Path::new("theme").join("rust-logo.png")
         );
 
-        let filter = AssetRemoteLinkFilter::new(&assets, 0);
-        let parser = utils::create_new_pull_down_parser(&markdown_str);
-        let events = parser.map(|ev| filter.apply(ev));
-        trace!("Events = {:?}", events);
-        let mut html_buf = String::new();
-        html::push_html(&mut html_buf, events);
+        if let BookItem::Chapter(ref ch) = ctx.book.sections[0] {
+            let rendered = g.render_chapter(ch).unwrap();
+            assert!(rendered.contains("<img src=\"theme/rust-logo.png\" alt=\"Rust Logo\""));
+        } else {
+            panic!();
+        }
+        g.additional_assets().unwrap();
+    }
+
+    #[test]
+    fn build_summary_counts_contents() {
+        let content =
+            "# Chapter 1\n\n![Logo](rust-logo.png)\n\n![Remote](https://mdbook.epub/logo.svg)\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let json = ctx_with_template(content, "src", destination.as_path()).to_string();
+        let ctx = RenderContext::from_json(json.as_bytes()).unwrap();
 
+        let mut mock_client = MockContentRetriever::new();
+        mock_client.expect_download().returning(|_| Ok(None));
+        mock_client.expect_read().returning(|path, buffer| {
+            buffer.extend_from_slice(path.to_string_lossy().as_bytes());
+            Ok(())
+        });
+        mock_client
+            .expect_open()
+            .returning(|path| Ok(Box::new(std::io::Cursor::new(path.display().to_string()))));
+
+        let g = Generator::new_with_handler(&ctx, mock_client).unwrap();
+        let mut epub = std::io::Cursor::new(Vec::new());
+        let summary = g.generate_with_summary(&mut epub).unwrap();
+        let epub = epub.into_inner();
         assert_eq!(
-            html_buf,
+            summary,
+            BuildSummary {
+                chapters: 1,
+                local_assets: 1,
+                remote_assets: 1,
+                bytes: epub.len() as u64,
+                warnings: Vec::new(),
+            }
+        );
+        assert_eq!(
+            summary.to_string(),
             format!(
-                "<h1>Chapter 1</h1>\n\
-                <ul>\n\
-                <li><a href=\"{}\">link</a></li>\n\
-                <li><img src=\"{}\" alt=\"Local Image\" /></li>\n\
-                <li><img alt=\"Remote Image\" src=\"cache/{}\" >\n\
-                </li>\n\
-                </ul>\n",
-                links[2], links[0], hashed_filename
+                "1 chapters, 1 local and 1 remote assets, {} bytes",
+                epub.len()
             )
         );
     }
 
     #[test]
-    fn render_remote_assets_in_sub_chapter() {
-        let link = "https://mdbook.epub/dummy.svg";
+    fn progress_reported_for_stages() {
+        let content =
+            "# Chapter 1\n\n![Logo](rust-logo.png)\n\n![Remote](https://mdbook.epub/logo.svg)\n";
         let tmp_dir = TempDir::new().unwrap();
-        let dest_dir = tmp_dir.path().join("mdbook-epub");
-        let ch1_1 = json!({
-            "Chapter": {
-                "name": "subchapter",
-                "content": format!("# Subchapter\n\n![Image]({link})"),
-                "number": [1,1],
-                "sub_items": [],
-                "path": "chapter_1/subchapter.md",
-                "parent_names": ["Chapter 1"]
-            }
-        });
-        let ch1 = json!({
-            "Chapter": {
-                "name": "Chapter 1",
-                "content": format!("# Chapter 1\n\n![Image]({link})"),
-                "number": [1],
-                "sub_items": [ch1_1],
-                "path": "chapter_1/index.md",
-                "parent_names": []
-            }
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        let mut draft = json["book"]["sections"][0].clone();
+        draft["Chapter"]["name"] = json!("Draft");
+        draft["Chapter"]["path"] = json!(null);
+        json["book"]["sections"][0]["Chapter"]["sub_items"] = json!([draft]);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut mock_client = MockContentRetriever::new();
+        mock_client.expect_download().returning(|_| Ok(None));
+        mock_client.expect_read().returning(|_, _| Ok(()));
+        mock_client
+            .expect_open()
+            .returning(|_| Ok(Box::new(std::io::empty())));
+        let reported = Mutex::new(Vec::new());
+        Generator::new_with_handler(&ctx, mock_client)
+            .unwrap()
+            .with_progress(|progress| reported.lock().unwrap().push(progress))
+            .generate(Vec::new())
+            .unwrap();
+
+        let progress = |stage, position, total| Progress {
+            stage,
+            position,
+            total,
+        };
+        assert_eq!(
+            reported.into_inner().unwrap(),
+            [
+                progress(Stage::DownloadingAssets, 1, 1),
+                progress(Stage::RenderingChapters, 1, 2),
+                progress(Stage::RenderingChapters, 2, 2),
+                progress(Stage::EmbeddingAssets, 1, 2),
+                progress(Stage::EmbeddingAssets, 2, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn download_progress_counts_finished_downloads() {
+        let content = "# Chapter 1\n\n![A](https://mdbook.epub/a.svg)\n\n\
+            ![B](https://mdbook.epub/b.svg)\n\n![C](https://mdbook.epub/c.svg)\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["download-concurrency"] = json!(1);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let mut mock_client = MockContentRetriever::new();
+        let before_download = Arc::clone(&reported);
+        let mut started = 0;
+        // a download isn't reported until it's finished
+        mock_client.expect_download().times(3).returning(move |_| {
+            assert_eq!(before_download.lock().unwrap().len(), started);
+            started += 1;
+            Ok(None)
         });
-        let ch2 = json!({
+        let mut g = Generator::new_with_handler(&ctx, mock_client)
+            .unwrap()
+            .with_progress(|progress| reported.lock().unwrap().push(progress.position));
+        g.find_assets().unwrap();
+        let downloads = g.download_remote_assets().unwrap();
+        assert!(downloads.1.is_empty());
+        assert_eq!(*reported.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn build_summary_collects_warnings() {
+        let content = "# Chapter 1\n\n![](chapter_1.md) [Gone](gone.md)\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["require-alt-text"] = json!(true);
+        json["book"]["sections"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"Chapter": {
+                "name": "Draft", "content": "", "number": null, "sub_items": [],
+                "path": null, "parent_names": []
+            }}));
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let summary = Generator::new(&ctx)
+            .unwrap()
+            .generate_with_summary(std::io::Cursor::new(Vec::new()))
+            .unwrap();
+        let kinds: Vec<_> = summary.warnings.iter().map(|w| w.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                WarningKind::MissingAltText,
+                WarningKind::SkippedDraft,
+                WarningKind::BrokenLinks
+            ]
+        );
+        assert_eq!(
+            summary.warnings[0].message,
+            "Chapter 'Chapter 1' has images without alt text: chapter_1.md"
+        );
+    }
+
+    #[test]
+    fn generation_timeout() {
+        let content = "# Chapter 1\n\n![Image](https://mdbook.epub/slow.png)\n\n\
+            ![Image](https://mdbook.epub/slower.png)\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["generation-timeout-secs"] = json!(1);
+        json["config"]["output"]["epub"]["download-concurrency"] = json!(1);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        // the first download hangs longer than the limit, which aborts it
+        let downloads = Arc::new(AtomicUsize::new(0));
+        let started_downloads = Arc::clone(&downloads);
+        let mut mock_client = MockContentRetriever::new();
+        mock_client.expect_download().returning(move |_| {
+            started_downloads.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_secs(3));
+            Ok(None)
+        });
+        mock_client.expect_read().never();
+
+        let started = Instant::now();
+        let result = Generator::generate_with_timeout_and_handler(
+            &ctx,
+            mock_client,
+            |_| {},
+            std::io::Cursor::new(Vec::new()),
+        );
+        assert!(matches!(result, Err(Error::Timeout(1))), "{result:?}");
+        assert!(started.elapsed() < Duration::from_secs(3));
+        // the abandoned worker is cancelled, it doesn't start the second download
+        std::thread::sleep(Duration::from_secs(4));
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn dedup_identical_remote_assets() {
+        let content = "# Chapter 1\n\n\
+            ![Logo](https://mdbook.epub/logo.png)\n\n\
+            ![Same logo](https://mdbook.epub/logo.png?v=2)\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let json = ctx_with_template(content, "src", destination.as_path()).to_string();
+        let ctx = RenderContext::from_json(json.as_bytes()).unwrap();
+
+        let mut mock_client = MockContentRetriever::new();
+        mock_client
+            .expect_download()
+            .times(2)
+            .returning(|_| Ok(None));
+        mock_client
+            .expect_open()
+            .times(2)
+            .returning(|_| Ok(Box::new(std::io::Cursor::new(b"same image"))));
+
+        let mut g = Generator::new_with_handler(&ctx, mock_client).unwrap();
+        g.find_assets().unwrap();
+        assert_eq!(g.assets.len(), 2);
+        g.dedup_remote_assets().unwrap();
+
+        let filenames: HashSet<&PathBuf> = g.assets.values().map(|a| &a.filename).collect();
+        assert_eq!(filenames.len(), 1);
+        let filename = filenames.into_iter().next().unwrap().display().to_string();
+        if let BookItem::Chapter(ref ch) = ctx.book.sections[0] {
+            let rendered = g.render_chapter(ch).unwrap();
+            assert_eq!(rendered.matches(&format!("src=\"{filename}\"")).count(), 2);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn remote_failure_modes() {
+        let content = "![Logo](https://mdbook.epub/logo.png)\n\n\
+            <img src=\"https://mdbook.epub/banner.png\" alt=\"Banner\">\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        let render = |json: &serde_json::Value| -> Result<String, Error> {
+            let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+            let mut mock_client = MockContentRetriever::new();
+            // 'fail' skips the remaining downloads after the first failure
+            mock_client
+                .expect_download()
+                .times(1..=2)
+                .returning(|asset| Err(Error::AssetFileNotFound(format!("{:?}", asset.source))));
+            mock_client.expect_read().never();
+            let mut g = Generator::new_with_handler(&ctx, mock_client).unwrap();
+            g.find_assets().unwrap();
+            g.dedup_remote_assets()?;
+            assert!(g.assets.is_empty());
+            assert!(g.remote_assets.is_empty());
+            let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+                panic!();
+            };
+            Ok(g.render_chapter_body(ch).unwrap())
+        };
+
+        assert!(matches!(render(&json), Err(Error::AssetFileNotFound(_))));
+
+        json["config"]["output"]["epub"]["remote-failure-mode"] = json!("embed-placeholder");
+        let rendered = render(&json).unwrap();
+        assert!(rendered.contains("<img src=\"remote-placeholder.svg\" alt=\"Logo\" />"));
+        assert!(rendered.contains("<img src=\"remote-placeholder.svg\" alt=\"Banner\">"));
+
+        json["config"]["output"]["epub"]["remote-failure-mode"] = json!("keep-url");
+        let rendered = render(&json).unwrap();
+        assert!(rendered.contains("<img src=\"https://mdbook.epub/logo.png\" alt=\"Logo\" />"));
+        assert!(rendered.contains("<img src=\"https://mdbook.epub/banner.png\" alt=\"Banner\">"));
+
+        json["config"]["output"]["epub"]["remote-failure-mode"] = json!("both");
+        let rendered = render(&json).unwrap();
+        assert!(rendered.contains(
+            "<object data=\"https://mdbook.epub/logo.png\" type=\"image/png\">\
+            <img src=\"remote-placeholder.svg\" alt=\"Logo\" /></object>"
+        ));
+        assert!(rendered.contains(
+            "<object data=\"https://mdbook.epub/banner.png\" type=\"image/png\">\
+            <img src=\"remote-placeholder.svg\" alt=\"Banner\"></object>"
+        ));
+    }
+
+    #[test]
+    fn download_concurrency_is_limited() {
+        let content = (1..=6)
+            .map(|i| format!("![Image {i}](https://mdbook.epub/{i}.png)\n\n"))
+            .collect::<String>();
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(&content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["download-concurrency"] = json!(2);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        // mockall runs the calls of an expectation one at a time, so it can't be used here
+        struct CountingRetriever {
+            running: Arc<AtomicUsize>,
+            max_running: Arc<AtomicUsize>,
+            // each pair of downloads waits for the other, so they run at the same time
+            barrier: Barrier,
+        }
+        impl ContentRetriever for CountingRetriever {
+            fn download(&self, _asset: &Asset) -> Result<Option<Mime>, Error> {
+                let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_running.fetch_max(running, Ordering::SeqCst);
+                self.barrier.wait();
+                std::thread::sleep(Duration::from_millis(20));
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                Ok(None)
+            }
+            fn retrieve(&self, _url: &str) -> Result<Box<dyn Read + Send + Sync>, Error> {
+                unreachable!()
+            }
+        }
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let retriever = CountingRetriever {
+            running: running.clone(),
+            max_running: max_running.clone(),
+            barrier: Barrier::new(2),
+        };
+
+        let mut g = Generator::new_with_handler(&ctx, retriever).unwrap();
+        g.find_assets().unwrap();
+        assert_eq!(g.assets.len(), 6);
+        let (_, failed_links) = g.download_remote_assets().unwrap();
+        assert!(failed_links.is_empty());
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+
+        json["config"]["output"]["epub"]["download-concurrency"] = json!(0);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(Generator::new(&ctx), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn redirected_remote_asset_typed_by_response() {
+        let content = "# Chapter 1\n\n![Logo](https://short.link/logo)\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let json = ctx_with_template(content, "src", destination.as_path()).to_string();
+        let ctx = RenderContext::from_json(json.as_bytes()).unwrap();
+
+        let mut mock_client = MockContentRetriever::new();
+        mock_client
+            .expect_download()
+            .times(1)
+            .returning(|_| Ok(Some("image/svg+xml".parse().unwrap())));
+        mock_client
+            .expect_open()
+            .times(1)
+            .returning(|_| Ok(Box::new(std::io::empty())));
+
+        let mut g = Generator::new_with_handler(&ctx, mock_client).unwrap();
+        g.find_assets().unwrap();
+        g.dedup_remote_assets().unwrap();
+
+        let asset = g.assets.values().next().unwrap();
+        assert_eq!(asset.mimetype.essence_str(), "image/svg+xml");
+        assert_eq!(asset.filename.extension().unwrap(), "svg");
+        // cached by the original link
+        let hashed_filename = utils::hash_link(&"https://short.link/logo".parse::<Url>().unwrap());
+        assert_eq!(asset.location_on_disk, destination.join(hashed_filename));
+        let filename = asset.filename.display().to_string();
+        if let BookItem::Chapter(ref ch) = ctx.book.sections[0] {
+            let rendered = g.render_chapter(ch).unwrap();
+            assert!(rendered.contains(&format!("src=\"{filename}\"")));
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn mime_overrides_take_precedence() {
+        let content =
+            "# Chapter 1\n\n![Logo](rust-logo.svg)\n\n![Remote](https://mdbook.epub/logo.svg)\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["mime-overrides"] =
+            json!({"svg": "image/svg+xml; charset=utf-8"});
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut mock_client = MockContentRetriever::new();
+        mock_client
+            .expect_download()
+            .returning(|_| Ok(Some("image/png".parse().unwrap())));
+        mock_client
+            .expect_open()
+            .returning(|_| Ok(Box::new(std::io::empty())));
+        let mut g = Generator::new_with_handler(&ctx, mock_client).unwrap();
+        g.find_assets().unwrap();
+        g.dedup_remote_assets().unwrap();
+
+        // the remote asset's type isn't taken from the response either
+        assert_eq!(g.assets.len(), 2);
+        for asset in g.assets.values() {
+            assert_eq!(asset.mimetype.to_string(), "image/svg+xml; charset=utf-8");
+            assert_eq!(asset.filename.extension().unwrap(), "svg");
+        }
+
+        json["config"]["output"]["epub"]["mime-overrides"] = json!({"svg": "not a type"});
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(Generator::new(&ctx), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn render_data_uri_assets_untouched() {
+        let data_uri = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+        let content = format!(
+            "# Chapter 1\n\n\
+            ![Inline image]({data_uri})\n\n\
+            <img alt=\"Inline html image\" src=\"{data_uri}\" />\n"
+        );
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let json = ctx_with_template(&content, "src", destination.as_path()).to_string();
+        let ctx = RenderContext::from_json(json.as_bytes()).unwrap();
+
+        let mut g = Generator::new(&ctx).unwrap();
+        g.find_assets().unwrap();
+        assert!(g.assets.is_empty());
+
+        if let BookItem::Chapter(ref ch) = ctx.book.sections[0] {
+            let rendered = g.render_chapter(ch).unwrap();
+            assert!(rendered.contains(&format!("<img src=\"{data_uri}\" alt=\"Inline image\" />")));
+            assert!(rendered.contains(&format!(
+                "<img alt=\"Inline html image\" src=\"{data_uri}\" />"
+            )));
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn render_assets() {
+        let links = [
+            "local.webp",
+            "http://server/remote.svg",
+            "http://server/link.png",
+        ];
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("mdbook-epub");
+        let mut assets = HashMap::new();
+        assets.insert(
+            links[0].to_string(),
+            Asset {
+                location_on_disk: root.as_path().join("src").join(links[0]),
+                filename: PathBuf::from(links[0]),
+                mimetype: "image/webp".parse::<mime::Mime>().unwrap(),
+                source: AssetKind::Local(PathBuf::from(links[0])),
+            },
+        );
+        let url = Url::parse(links[1]).unwrap();
+        let hashed_filename = utils::hash_link(&url);
+        let hashed_path = Path::new("cache").join(&hashed_filename);
+        assets.insert(
+            links[1].to_string(),
+            Asset {
+                location_on_disk: root.as_path().join("book").join(&hashed_path),
+                filename: hashed_path,
+                mimetype: "image/svg+xml".parse::<mime::Mime>().unwrap(),
+                source: AssetKind::Remote(url),
+            },
+        );
+        let markdown_str = format!(
+            "Chapter 1\n\
+            =====\n\n\
+            * [link]({})\n\
+            * ![Local Image]({})\n\
+            * <img alt=\"Remote Image\" src=\"{}\" >\n",
+            links[2], links[0], links[1]
+        );
+
+        let mut filter = AssetRemoteLinkFilter::new(&assets, 0);
+        let parser = utils::create_new_pull_down_parser(&markdown_str);
+        let events = parser.flat_map(|ev| filter.apply(ev));
+        trace!("Events = {:?}", events);
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+
+        assert_eq!(
+            html_buf,
+            format!(
+                "<h1>Chapter 1</h1>\n\
+                <ul>\n\
+                <li><a href=\"{}\">link</a></li>\n\
+                <li><img src=\"{}\" alt=\"Local Image\" /></li>\n\
+                <li><img alt=\"Remote Image\" src=\"cache/{}\" >\n\
+                </li>\n\
+                </ul>\n",
+                links[2], links[0], hashed_filename
+            )
+        );
+    }
+
+    #[test]
+    fn render_picture_sources() {
+        let links = ["http://server/wide.webp", "http://server/narrow.png"];
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("mdbook-epub");
+        let mut assets = HashMap::new();
+        for link in links {
+            let url = Url::parse(link).unwrap();
+            let hashed_path = Path::new("cache").join(utils::hash_link(&url));
+            assets.insert(
+                link.to_string(),
+                Asset {
+                    location_on_disk: root.as_path().join("book").join(&hashed_path),
+                    filename: hashed_path,
+                    mimetype: utils::mime_type(Path::new(link)),
+                    source: AssetKind::Remote(url),
+                },
+            );
+        }
+        let markdown_str = format!(
+            "<picture>\n<source srcset=\"{} 2x\" type=\"image/webp\">\n\
+            <img src=\"local.png\" srcset=\"local.png 1x, {} 2x\" alt=\"Logo\">\n</picture>\n",
+            links[0], links[1]
+        );
+
+        let mut filter = AssetRemoteLinkFilter::new(&assets, 1);
+        let parser = utils::create_new_pull_down_parser(&markdown_str);
+        let events = parser.flat_map(|ev| filter.apply(ev));
+        let mut html_buf = String::new();
+        html::push_html(&mut html_buf, events);
+
+        let [wide, narrow] = links.map(|link| utils::hash_link(&Url::parse(link).unwrap()));
+        assert_eq!(
+            html_buf,
+            format!(
+                "<picture>\n<source srcset=\"../cache/{wide} 2x\" type=\"image/webp\">\n\
+                <img src=\"local.png\" srcset=\"local.png 1x, ../cache/{narrow} 2x\" alt=\"Logo\">\n\
+                </picture>\n"
+            )
+        );
+    }
+
+    #[test]
+    fn render_remote_assets_in_sub_chapter() {
+        let link = "https://mdbook.epub/dummy.svg";
+        let tmp_dir = TempDir::new().unwrap();
+        let dest_dir = tmp_dir.path().join("mdbook-epub");
+        let ch1_1 = json!({
+            "Chapter": {
+                "name": "subchapter",
+                "content": format!("# Subchapter\n\n![Image]({link})"),
+                "number": [1,1],
+                "sub_items": [],
+                "path": "chapter_1/subchapter.md",
+                "parent_names": ["Chapter 1"]
+            }
+        });
+        let ch1 = json!({
+            "Chapter": {
+                "name": "Chapter 1",
+                "content": format!("# Chapter 1\n\n![Image]({link})"),
+                "number": [1],
+                "sub_items": [ch1_1],
+                "path": "chapter_1/index.md",
+                "parent_names": []
+            }
+        });
+        let ch2 = json!({
             "Chapter": {
                 "name": "Chapter 2",
                 "content": format!("# Chapter 2\n\n![Image]({link})"),
@@ -627,7 +2699,7 @@ mod tests {
         assert_eq!(g.assets.len(), 1);
 
         let pat = |heading, prefix| {
-            format!("<h1>{heading}</h1>\n<p><img src=\"{prefix}811c431d49ec880b.svg\"")
+            format!(">{heading}</h1>\n<p><img src=\"{prefix}811c431d49ec880b.svg\"")
         };
         if let BookItem::Chapter(ref ch) = ctx.book.sections[0] {
             let rendered: String = g.render_chapter(ch).unwrap();
@@ -652,18 +2724,1065 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn find_assets_with_wrong_src_dir() {
+    fn epub_minor_versions() {
         let tmp_dir = TempDir::new().unwrap();
-        let json = ctx_with_template(
-            "# Chapter 1\n\n",
-            "nosuchsrc",
-            tmp_dir.path().join("mdbook-epub").as_path(),
-        )
-        .to_string();
-        let ctx = RenderContext::from_json(json.as_bytes()).unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        for (version, supported) in [
+            (json!(2), true),
+            (json!(3), true),
+            (json!(3.2), true),
+            (json!("3.1"), true),
+            (json!("2.0.1"), true),
+            (json!(4), false),
+            (json!("2.1"), false),
+        ] {
+            let mut json = ctx_with_template("", "src", destination.as_path());
+            json["config"]["output"]["epub"]["epub-version"] = version.clone();
+            let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+            let result = Generator::new(&ctx);
+            assert_eq!(result.is_ok(), supported, "epub-version = {version}");
+            if let Err(e) = result {
+                assert!(e
+                    .to_string()
+                    .starts_with("Unsupported epub version specified in book.toml"));
+            }
+        }
+    }
+
+    #[test]
+    fn render_chapter_with_wrapper() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n\nText", "src", destination.as_path());
+        json["config"]["output"]["epub"]["epub-version"] = json!(3);
+        json["config"]["output"]["epub"]["chapter-wrapper"] = json!("section");
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        if let BookItem::Chapter(ref ch) = ctx.book.sections[0] {
+            let rendered = g.render_chapter(ch).unwrap();
+            assert!(rendered.contains(
+                "<section epub:type=\"chapter\">\n<h1 id=\"chapter-1\">Chapter 1</h1>\n<p>Text</p>\n</section>"
+            ));
+        } else {
+            panic!();
+        }
+
+        json["config"]["output"]["epub"]["chapter-wrapper"] = json!("<div>");
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(Generator::new(&ctx), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn render_chapter_xhtml_with_assets() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let content = "# Chapter 1\n\n![Logo](rust-logo.png)\n\n\
+            ![Remote](https://example.com/logo.png)\n";
+        let json = ctx_with_template(content, "src", destination.as_path());
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+
         let mut g = Generator::new(&ctx).unwrap();
-        g.find_assets().unwrap();
+        let page = g.render_chapter_xhtml(ch).unwrap();
+        assert!(page.contains("<link rel=\"stylesheet\" href=\"stylesheet.css\" />"));
+        assert!(page.contains("<h1 id=\"chapter-1\">Chapter 1</h1>"));
+        assert!(page.contains("<img src=\"rust-logo.png\" alt=\"Logo\""));
+        // the remote image is linked by the file it's embedded as
+        assert!(!page.contains("https://example.com/logo.png"));
+        assert_eq!(g.render_chapter_xhtml(ch).unwrap(), page);
+        assert_eq!(crate::render_chapter(&ctx, ch).unwrap(), page);
+
+        let draft = Chapter {
+            path: None,
+            ..ch.clone()
+        };
+        assert!(g.render_chapter_xhtml(&draft).is_err());
+    }
+
+    #[test]
+    fn render_figure_captions() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let content = "# Chapter 1\n\n![Logo](rust-logo.png)\n*The logo*\n";
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["epub-version"] = json!(3);
+        json["config"]["output"]["epub"]["figure-captions"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let g = Generator::new(&ctx).unwrap();
+        let rendered = g.render_chapter(ch).unwrap();
+        assert!(rendered.contains(
+            "<figure>\n<img src=\"rust-logo.png\" alt=\"Logo\" />\n\
+            <figcaption>The logo</figcaption>\n</figure>"
+        ));
+
+        // an image at the end of the chapter isn't lost
+        let content = "# Chapter 1\n\n![Logo](rust-logo.png)\n";
+        json["book"]["sections"][0]["Chapter"]["content"] = json!(content);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let g = Generator::new(&ctx).unwrap();
+        let rendered = g.render_chapter(ch).unwrap();
+        assert!(rendered.contains("<p><img src=\"rust-logo.png\" alt=\"Logo\" /></p>"));
+        assert!(!rendered.contains("<figure>"));
+    }
+
+    #[test]
+    fn image_without_alt_text_reported() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(
+            "# Chapter 1\n\n![](chart.png)\n\n![](logo.png \"Logo\")",
+            "src",
+            destination.as_path(),
+        );
+        json["config"]["output"]["epub"]["require-alt-text"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        // only a warning by default
+        let rendered = Generator::new(&ctx).unwrap().render_chapter(ch).unwrap();
+        assert!(rendered.contains("<img src=\"logo.png\" alt=\"Logo\" title=\"Logo\" />"));
+
+        json["config"]["output"]["epub"]["strict-assets"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let error = Generator::new(&ctx)
+            .unwrap()
+            .render_chapter(ch)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Chapter 'Chapter 1' has images without alt text: chart.png"));
+    }
+
+    #[test]
+    fn small_images_inlined() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(
+            "![Logo](02_advanced/Epub_logo.svg)\n\n<img src=\"rust-logo.png\" alt=\"Logo\">\n\n\
+            ![Logo](rust-logo.svg)",
+            "src",
+            destination.as_path(),
+        );
+        json["config"]["output"]["epub"]["inline-images-under-bytes"] = json!(6000);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let mut g = Generator::new(&ctx).unwrap();
+        g.find_assets().unwrap();
+        g.inline_small_images().unwrap();
+        assert_eq!(
+            g.inlined_images.keys().collect::<HashSet<_>>(),
+            HashSet::from([
+                &"02_advanced/Epub_logo.svg".to_string(),
+                &"rust-logo.png".to_string()
+            ])
+        );
+
+        let rendered = g.render_chapter_body(ch).unwrap();
+        assert!(rendered.contains("<img src=\"data:image/svg+xml;base64,PD94bWwg"));
+        assert!(rendered.contains("<img src=\"data:image/png;base64,iVBORw0KGgo"));
+        assert!(rendered.contains("<img src=\"rust-logo.svg\" alt=\"Logo\" />"));
+    }
+
+    #[test]
+    fn render_chapter_with_slug_class() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let json = ctx_with_template("# Chapter 1\n\nText", "src", destination.as_path());
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        if let BookItem::Chapter(ref ch) = ctx.book.sections[0] {
+            let rendered = g.render_chapter(ch).unwrap();
+            assert!(rendered.contains(
+                "<div class=\"chapter chapter-chapter-1\">\n<h1 id=\"chapter-1\">Chapter 1</h1>\n<p>Text</p>\n</div>"
+            ));
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn render_heading_only_chapter_as_divider() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Part I\n\n  \n", "src", destination.as_path());
+        json["config"]["output"]["epub"]["section-dividers"] = json!(true);
+        json["config"]["output"]["epub"]["epub-version"] = json!(3);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        assert!(g.render_chapter(ch).unwrap().contains(
+            "<div class=\"chapter chapter-chapter-1 section-divider\" epub:type=\"part\">\n\
+            <h1 id=\"part-i\">Part I</h1>\n</div>"
+        ));
+
+        // chapters with content are no dividers
+        let mut chapter = ch.clone();
+        chapter.content = "# Part I\n\nIntroduction".to_string();
+        assert!(!g
+            .render_chapter(&chapter)
+            .unwrap()
+            .contains("section-divider"));
+
+        // disabled by default
+        json["config"]["output"]["epub"]["section-dividers"] = json!(false);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+        assert!(!g.render_chapter(ch).unwrap().contains("section-divider"));
+    }
+
+    #[test]
+    fn number_figures_across_chapters() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let figure = "![Ferris](rust-logo.png \"Ferris the crab\")\n";
+        let mut json = ctx_with_template(figure, "src", destination.as_path());
+        let mut second_chapter = json["book"]["sections"][0].clone();
+        second_chapter["Chapter"]["path"] = json!("chapter_2.md");
+        json["book"]["sections"]
+            .as_array_mut()
+            .unwrap()
+            .push(second_chapter);
+        json["config"]["output"]["epub"]["number-captions"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        let rendered: Vec<String> = ctx
+            .book
+            .sections
+            .iter()
+            .map(|item| match item {
+                BookItem::Chapter(ref ch) => g.render_chapter(ch).unwrap(),
+                _ => panic!(),
+            })
+            .collect();
+        assert!(rendered[0]
+            .contains("<span class=\"caption\" id=\"figure-1\">Figure 1: Ferris the crab</span>"));
+        assert!(rendered[1]
+            .contains("<span class=\"caption\" id=\"figure-2\">Figure 2: Ferris the crab</span>"));
+    }
+
+    #[test]
+    fn stylesheet_declares_fonts() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n\n", "src", destination.as_path());
+        json["config"]["output"]["epub"]["use-default-css"] = json!(false);
+        json["config"]["output"]["epub"]["fonts"] = json!(["fonts/Serif.woff2"]);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        let stylesheet = String::from_utf8(g.generate_stylesheet().unwrap()).unwrap();
+        assert_eq!(
+            "\n@font-face {\n  font-family: \"Serif\";\n  src: url(\"fonts/Serif.woff2\");\n}\n",
+            stylesheet
+        );
+
+        json["config"]["output"]["epub"]["fonts"] = json!(["fonts/Serif.eot"]);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(Generator::new(&ctx), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn stylesheet_constrains_image_height() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n\n", "src", destination.as_path());
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let stylesheet = Generator::new(&ctx).unwrap().generate_stylesheet().unwrap();
+        assert!(!String::from_utf8(stylesheet)
+            .unwrap()
+            .contains("max-height: 100vh"));
+
+        json["config"]["output"]["epub"]["constrain-image-height"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let stylesheet = Generator::new(&ctx).unwrap().generate_stylesheet().unwrap();
+        let stylesheet = String::from_utf8(stylesheet).unwrap();
+        assert!(stylesheet.starts_with(DEFAULT_CSS));
+        assert!(stylesheet.ends_with(
+            "img {\n  max-height: 100vh;\n  object-fit: contain;\n  page-break-inside: avoid;\n}\n"
+        ));
+    }
+
+    #[test]
+    fn additional_resources_by_glob_pattern() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("book");
+        for file in [
+            "src/chapter_1.md",
+            "fonts/a.woff2",
+            "fonts/b.woff2",
+            "fonts/c.ttf",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let mut json = ctx_with_template("# Chapter 1\n", "src", &tmp_dir.path().join("epub"));
+        json["root"] = json!(root);
+        json["config"]["output"]["epub"]["additional-resources"] =
+            json!(["fonts/*.woff2", "fonts/c.ttf"]);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let resources = Generator::new(&ctx)
+            .unwrap()
+            .find_additional_resources()
+            .unwrap();
+        let root = utils::canonicalize(&root).unwrap();
+        assert_eq!(
+            resources,
+            [
+                (
+                    PathBuf::from("fonts/a.woff2"),
+                    ctx.root.join("fonts/a.woff2")
+                ),
+                (
+                    PathBuf::from("fonts/b.woff2"),
+                    ctx.root.join("fonts/b.woff2")
+                ),
+                // literal paths are still resolved as before
+                (
+                    PathBuf::from("fonts/c.ttf"),
+                    root.join("fonts").join("c.ttf")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_configured_files_fail_early() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let json = ctx_with_template("# Chapter 1\n", "src", destination.as_path());
+        for (key, value, kind) in [
+            ("cover-image", json!("missing-cover.png"), "Cover image"),
+            ("additional-css", json!(["missing.css"]), "Stylesheet"),
+            (
+                "additional-resources",
+                json!(["missing.ttf"]),
+                "Additional resource",
+            ),
+        ] {
+            let mut json = json.clone();
+            json["config"]["output"]["epub"][key] = value;
+            let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+            let result = Generator::new(&ctx);
+            match result {
+                Err(Error::ResourceNotFound { kind: k, tried, .. }) => {
+                    assert_eq!(k, kind);
+                    assert!(tried.len() > 1);
+                }
+                other => panic!("{key}: {other:?}"),
+            }
+        }
+
+        // existing files are found relative to the book's src or root
+        let mut json = json.clone();
+        json["config"]["output"]["epub"]["cover-image"] = json!("rust-logo.png");
+        json["config"]["output"]["epub"]["additional-css"] = json!(["src/rust-logo.svg"]);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(Generator::new(&ctx).is_ok());
+    }
+
+    #[test]
+    fn stylesheet_by_css_mode() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n\n", "src", destination.as_path());
+        let stylesheet = |json: &serde_json::Value| {
+            let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+            let stylesheet = Generator::new(&ctx).unwrap().generate_stylesheet().unwrap();
+            String::from_utf8(stylesheet).unwrap()
+        };
+        assert_eq!(stylesheet(&json), DEFAULT_CSS);
+
+        json["config"]["output"]["epub"]["css-mode"] = json!("minimal");
+        assert_eq!(stylesheet(&json), MINIMAL_CSS);
+        assert!(MINIMAL_CSS.contains("img,\nsvg {\n  max-width: 100%;"));
+        json["config"]["output"]["epub"]["css-mode"] = json!("none");
+        assert_eq!(stylesheet(&json), "");
+
+        // the mode takes precedence over 'use-default-css'
+        json["config"]["output"]["epub"]["use-default-css"] = json!(false);
+        json["config"]["output"]["epub"]["css-mode"] = json!("full");
+        assert_eq!(stylesheet(&json), DEFAULT_CSS);
+        json["config"]["output"]["epub"]
+            .as_object_mut()
+            .unwrap()
+            .remove("css-mode");
+        assert_eq!(stylesheet(&json), "");
+    }
+
+    #[test]
+    fn top_level_chapters_start_on_new_page() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n\nText", "src", destination.as_path());
+        json["config"]["output"]["epub"]["chapter-page-breaks"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+        let stylesheet = String::from_utf8(g.generate_stylesheet().unwrap()).unwrap();
+        assert!(stylesheet.contains("div.chapter-start {\n  page-break-before: always;"));
+
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        assert!(g
+            .render_chapter_body(ch)
+            .unwrap()
+            .starts_with("<div class=\"chapter chapter-chapter-1 chapter-start\">"));
+        let mut sub_chapter = ch.clone();
+        sub_chapter.parent_names = vec![String::from("Part")];
+        assert!(g
+            .render_chapter_body(&sub_chapter)
+            .unwrap()
+            .starts_with("<div class=\"chapter chapter-chapter-1\">"));
+    }
+
+    #[test]
+    fn single_html_contains_all_chapters() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n\nFirst", "src", destination.as_path());
+        let mut second_chapter = json["book"]["sections"][0].clone();
+        second_chapter["Chapter"]["name"] = json!("Chapter 2");
+        second_chapter["Chapter"]["content"] = json!("# Chapter 2\n\nSecond");
+        second_chapter["Chapter"]["path"] = json!("chapter_2.md");
+        json["book"]["sections"]
+            .as_array_mut()
+            .unwrap()
+            .push(second_chapter);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut output = Vec::new();
+        Generator::new(&ctx)
+            .unwrap()
+            .generate_single_html(&mut output)
+            .unwrap();
+        let html = String::from_utf8(output).unwrap();
+
+        assert!(html.contains("<title>DummyBook</title>"));
+        assert!(html.contains(DEFAULT_CSS));
+        let first = html
+            .find("<div class=\"chapter chapter-chapter-1\">\n<h1 id=\"chapter-1\">Chapter 1</h1>\n<p>First</p>")
+            .unwrap();
+        let second = html
+            .find("<div class=\"chapter chapter-chapter-2\">\n<h1 id=\"chapter-2\">Chapter 2</h1>\n<p>Second</p>")
+            .unwrap();
+        assert!(first < second);
+    }
+
+    #[test]
+    fn single_html_embeds_local_images() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let content = "![Logo](rust-logo.png)\n\n\
+            <img src=\"https://example.com/remote.png\" alt=\"Remote\">\n";
+        let json = ctx_with_template(content, "src", destination.as_path());
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut output = Vec::new();
+        Generator::new(&ctx)
+            .unwrap()
+            .generate_single_html(&mut output)
+            .unwrap();
+        let html = String::from_utf8(output).unwrap();
+
+        assert!(html.contains("<img src=\"data:image/png;base64,"));
+        assert!(!html.contains("src=\"rust-logo.png\""));
+        assert!(html.contains("<img src=\"https://example.com/remote.png\" alt=\"Remote\">"));
+    }
+
+    #[test]
+    fn percent_encoded_local_links() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("book");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/chapter_1.md"), "").unwrap();
+        std::fs::copy(
+            "tests/long_book_example/src/rust-logo.png",
+            root.join("src/my image.png"),
+        )
+        .unwrap();
+        let content = "![Logo](my%20image.png)\n\n<img src=\"my%20image.png\" alt=\"Logo\">\n";
+        let mut json = ctx_with_template(content, "src", &tmp_dir.path().join("epub"));
+        json["root"] = json!(root);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut g = Generator::new(&ctx).unwrap();
+        g.find_assets().unwrap();
+        assert_eq!(g.assets.len(), 1);
+        // embedded by the link's name, the file on disk is found by the decoded one
+        let asset = &g.assets["my image.png"];
+        assert_eq!(asset.filename, Path::new("my%20image.png"));
+        assert!(asset.location_on_disk.ends_with("src/my image.png"));
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let page = g.render_chapter(ch).unwrap();
+        assert!(page.contains("<img src=\"my%20image.png\" alt=\"Logo\""));
+
+        let mut output = Vec::new();
+        g.generate_single_html(&mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+        assert_eq!(html.matches("src=\"data:image/png;base64,").count(), 2);
+        assert!(!html.contains("my%20image.png"));
+
+        let mut epub = Vec::new();
+        Generator::new(&ctx).unwrap().generate(&mut epub).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(epub)).unwrap();
+        let mut opf = String::new();
+        zip.by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut opf)
+            .unwrap();
+        assert!(opf.contains("href=\"my%20image.png\""), "{opf}");
+        assert!(zip.by_name("OEBPS/my%20image.png").is_ok());
+    }
+
+    #[test]
+    fn mathml_documents_declared_in_manifest() {
+        let content = "# Chapter 1\n\n```math\nx^2\n```\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["epub-version"] = json!(3);
+        json["config"]["output"]["epub"]["math-code-blocks"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut epub = Vec::new();
+        Generator::new(&ctx).unwrap().generate(&mut epub).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(epub)).unwrap();
+        let mut opf = String::new();
+        zip.by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut opf)
+            .unwrap();
+        let items: Vec<&str> = opf
+            .lines()
+            .filter(|line| line.contains("properties=\"mathml\""))
+            .collect();
+        assert_eq!(items.len(), 1, "{opf}");
+        assert!(items[0].contains("href=\"chapter_1.html\""), "{opf}");
+    }
+
+    #[test]
+    fn root_relative_links() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("book");
+        std::fs::create_dir_all(root.join("src/part")).unwrap();
+        std::fs::create_dir_all(root.join("src/images")).unwrap();
+        std::fs::write(root.join("src/part/chapter_1.md"), "").unwrap();
+        std::fs::copy(
+            "tests/long_book_example/src/rust-logo.png",
+            root.join("src/images/logo.png"),
+        )
+        .unwrap();
+        let content = "![Logo](/images/logo.png)\n\n<img src=\"/images/logo.png\" alt=\"Logo\">\n";
+        let mut json = ctx_with_template(content, "src", &tmp_dir.path().join("epub"));
+        json["root"] = json!(root);
+        json["book"]["sections"][0]["Chapter"]["path"] = json!("part/chapter_1.md");
+        json["config"]["output"]["epub"]["root-relative-links"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut g = Generator::new(&ctx).unwrap();
+        g.find_assets().unwrap();
+        assert_eq!(g.assets.len(), 1);
+        assert_eq!(
+            g.assets["images/logo.png"].filename,
+            Path::new("images/logo.png")
+        );
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let page = g.render_chapter(ch).unwrap();
+        assert_eq!(page.matches("src=\"../images/logo.png\"").count(), 2);
+        assert!(!page.contains("\"/images/logo.png\""));
+
+        // an absolute path by default, which doesn't exist
+        json["config"]["output"]["epub"]["root-relative-links"] = json!(false);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let mut g = Generator::new(&ctx).unwrap();
+        assert!(matches!(g.find_assets(), Err(Error::MissingAssets(_))));
+    }
+
+    #[test]
+    fn draft_chapter_as_placeholder() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n\nFirst", "src", destination.as_path());
+        let mut draft = json["book"]["sections"][0].clone();
+        draft["Chapter"]["name"] = json!("Q&A");
+        draft["Chapter"]["content"] = json!("");
+        draft["Chapter"]["path"] = json!(null);
+        json["book"]["sections"].as_array_mut().unwrap().push(draft);
+        json["config"]["output"]["epub"]["include-drafts"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let BookItem::Chapter(ref ch) = ctx.book.sections[1] else {
+            panic!();
+        };
+        let placeholder = "<div class=\"chapter draft-placeholder\">\n<h1>Q&amp;A</h1>\n\
+            <p>Coming soon</p>\n</div>";
+        let g = Generator::new(&ctx).unwrap();
+        let page = g
+            .render_page(&ch.name, &Generator::render_draft_body(ch), Some(ch))
+            .unwrap();
+        assert!(page.contains(placeholder));
+        assert!(page.contains("<title>Q&amp;A</title>"));
+        assert!(page.contains("href=\"stylesheet.css\""));
+
+        let mut output = Vec::new();
+        g.generate_single_html(&mut output).unwrap();
+        assert!(String::from_utf8(output).unwrap().contains(placeholder));
+
+        // left out by default
+        json["config"]["output"]["epub"]["include-drafts"] = json!(false);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let mut output = Vec::new();
+        Generator::new(&ctx)
+            .unwrap()
+            .generate_single_html(&mut output)
+            .unwrap();
+        assert!(!String::from_utf8(output).unwrap().contains(placeholder));
+    }
+
+    #[test]
+    fn separate_stylesheets_linked_individually() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("Text", "src", destination.as_path());
+        let mut css = Vec::new();
+        for (name, rule) in [
+            ("base.css", "p { margin: 0; }"),
+            ("theme.css", "h1 { color: red; }"),
+        ] {
+            let path = tmp_dir.path().join(name);
+            std::fs::write(&path, rule).unwrap();
+            css.push(path);
+        }
+        json["book"]["sections"][0]["Chapter"]["path"] = json!("part/chapter_1.md");
+        json["config"]["output"]["epub"]["additional-css"] = json!(css);
+        json["config"]["output"]["epub"]["separate-stylesheets"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let page = g.render_chapter(ch).unwrap();
+        for link in ["../stylesheet.css", "../base.css", "../theme.css"] {
+            assert!(page.contains(&format!("<link rel=\"stylesheet\" href=\"{link}\" />")));
+        }
+        let stylesheet = String::from_utf8(g.generate_stylesheet().unwrap()).unwrap();
+        assert!(stylesheet.starts_with(DEFAULT_CSS) && !stylesheet.contains("color: red"));
+        // the single document has all of them inlined
+        let mut output = Vec::new();
+        g.generate_single_html(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("p { margin: 0; }h1 { color: red; }"));
+
+        // a stylesheet named like the book's one or another one is rejected
+        css.push(tmp_dir.path().join("themes").join("theme.css"));
+        json["config"]["output"]["epub"]["additional-css"] = json!(css);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(Generator::new(&ctx), Err(Error::InvalidConfig(_))));
+        json["config"]["output"]["epub"]["additional-css"] = json!(["stylesheet.css"]);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(Generator::new(&ctx), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn template_gets_chapter_number_and_path() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("Text", "src", destination.as_path());
+        json["book"]["sections"][0]["Chapter"]["number"] = json!([2, 1]);
+        json["book"]["sections"][0]["Chapter"]["path"] = json!("Part/chapter_1.md");
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let mut g = Generator::new(&ctx).unwrap();
+        g.hbs
+            .register_template_string(
+                "index",
+                "{{chapter_number}} {{uppercase title}} \
+                ({{#if chapter_path}}{{lowercase chapter_path}}{{/if}}) {{stylesheet}}",
+            )
+            .unwrap();
+
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        assert_eq!(
+            g.render_chapter(ch).unwrap(),
+            "2.1. CHAPTER 1 (part/chapter_1.md) ../stylesheet.css"
+        );
+        // generated pages don't belong to a chapter
+        assert_eq!(
+            g.render_page("About", "", None).unwrap(),
+            " ABOUT () stylesheet.css"
+        );
+    }
+
+    #[test]
+    fn chapter_front_matter_stripped() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(
+            "---\ntitle: Getting Started\nauthor: Jane Doe\n---\n\nText",
+            "src",
+            destination.as_path(),
+        );
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let mut g = Generator::new(&ctx).unwrap();
+        g.hbs
+            .register_template_string("index", "{{title}} by {{front_matter.author}}: {{{body}}}")
+            .unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        assert_eq!(
+            g.render_chapter(ch).unwrap(),
+            "Getting Started by Jane Doe: <div class=\"chapter chapter-chapter-1\">\n\
+            <p>Text</p>\n</div>\n"
+        );
+
+        json["config"]["output"]["epub"]["front-matter"] = json!(false);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+        let rendered = g.render_chapter_body(ch).unwrap();
+        assert!(rendered.contains("<hr />\n<h2 id=\"title-getting-started-author-jane-doe\">title: Getting Started\nauthor: Jane Doe</h2>"));
+    }
+
+    #[test]
+    fn start_page_by_config_or_first_content_chapter() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Part I\n", "src", destination.as_path());
+        for (name, path, content) in [
+            ("Chapter 2", "part/chapter_2.md", "# Chapter 2\n\nText"),
+            ("Part", "part/index.md", "# Part\n\nIntro"),
+        ] {
+            json["book"]["sections"]
+                .as_array_mut()
+                .unwrap()
+                .push(json!({"Chapter": {
+                    "name": name, "content": content, "number": null, "sub_items": [],
+                    "path": path, "parent_names": []
+                }}));
+        }
+        let start_page = |json: &serde_json::Value| {
+            let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+            Generator::new(&ctx).map(|g| g.start_page)
+        };
+
+        // the part divider is skipped
+        assert_eq!(
+            start_page(&json).unwrap(),
+            Some(PathBuf::from("part/chapter_2.md"))
+        );
+        json["config"]["output"]["epub"]["start-chapter"] = json!("part/README.md");
+        assert_eq!(
+            start_page(&json).unwrap(),
+            Some(PathBuf::from("part/index.md"))
+        );
+        json["config"]["output"]["epub"]["start-chapter"] = json!("chapter_1.md");
+        assert_eq!(
+            start_page(&json).unwrap(),
+            Some(PathBuf::from("chapter_1.md"))
+        );
+        json["config"]["output"]["epub"]["start-chapter"] = json!("missing.md");
+        assert!(matches!(start_page(&json), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn spine_order_by_chapter_paths() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n", "src", destination.as_path());
+        json["book"]["sections"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"Chapter": {
+                "name": "Preface", "content": "# Preface\n", "number": null, "sub_items": [],
+                "path": "preface/index.md", "parent_names": []
+            }}));
+        let spine_order = |json: &serde_json::Value| {
+            let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+            Generator::new(&ctx).map(|g| g.spine_order)
+        };
+
+        assert!(spine_order(&json).unwrap().is_empty());
+        json["config"]["output"]["epub"]["spine-order"] = json!(["preface/README.md"]);
+        assert_eq!(
+            spine_order(&json).unwrap(),
+            vec![PathBuf::from("preface/index.md")]
+        );
+        json["config"]["output"]["epub"]["spine-order"] = json!(["missing.md"]);
+        assert!(matches!(spine_order(&json), Err(Error::InvalidConfig(_))));
+        json["config"]["output"]["epub"]["spine-order"] = json!(["chapter_1.md", "chapter_1.md"]);
+        assert!(matches!(spine_order(&json), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn broken_chapter_links_reported() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(
+            "[Self](chapter_1.md#top) [Old](chapter_0.md)",
+            "src",
+            destination.as_path(),
+        );
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let rendered = g.render_chapter_body(ch).unwrap();
+        assert!(rendered.contains("<a href=\"chapter_1.html#top\">Self</a>"));
+        assert!(rendered.contains("<a href=\"chapter_0.md\">Old</a>"));
+        assert!(g.check_links().is_ok());
+
+        json["config"]["output"]["epub"]["validate-links"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+        g.render_chapter_body(ch).unwrap();
+        let Err(Error::BrokenLinks(links)) = g.check_links() else {
+            panic!("broken links not reported");
+        };
+        assert_eq!(links, ["'chapter_0.md' in chapter 'Chapter 1'"]);
+    }
+
+    #[test]
+    fn chapter_template_selected_by_path_pattern() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let appendix = tmp_dir.path().join("appendix.hbs");
+        std::fs::write(&appendix, "Appendix: {{title}}").unwrap();
+        let readme = tmp_dir.path().join("readme.hbs");
+        std::fs::write(&readme, "Appendix README: {{title}}").unwrap();
+        let mut json = ctx_with_template("Text", "src", destination.as_path());
+        json["config"]["output"]["epub"]["index-template"] = json!({
+            "appendix/**": appendix,
+            "appendix/README.md": readme,
+            "appendix/*.md": appendix,
+        });
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!();
+        };
+        let mut chapter = ch.clone();
+        chapter.path = Some(PathBuf::from("appendix/glossary.md"));
+        assert_eq!(g.render_chapter(&chapter).unwrap(), "Appendix: Chapter 1");
+        // the most specific pattern wins
+        chapter.path = Some(PathBuf::from("appendix/README.md"));
+        assert_eq!(
+            g.render_chapter(&chapter).unwrap(),
+            "Appendix README: Chapter 1"
+        );
+        // other chapters and pages fall back to the default template
+        assert!(g
+            .render_chapter(ch)
+            .unwrap()
+            .contains("<title>Chapter 1</title>"));
+        assert!(g
+            .render_page("About", "", None)
+            .unwrap()
+            .contains("<title>About</title>"));
+    }
+
+    #[test]
+    fn about_page_lists_credits() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1", "src", destination.as_path());
+        json["config"]["book"]["authors"] = json!(["Ferris"]);
+        let epub = &mut json["config"]["output"]["epub"];
+        epub["about-page"] = json!(true);
+        epub["contributors"] = json!(["Jane Doe (editor)", "Tom & Jerry"]);
+        epub["rights"] = json!("CC BY 4.0");
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+
+        assert_eq!(
+            g.render_about_body(),
+            "<div class=\"chapter about-page\">\n<h1>About this book</h1>\n<dl>\n\
+            <dt>Title</dt>\n<dd>DummyBook</dd>\n\
+            <dt>Authors</dt>\n<dd>Ferris</dd>\n\
+            <dt>Contributors</dt>\n<dd>Jane Doe (editor)</dd>\n<dd>Tom &amp; Jerry</dd>\n\
+            <dt>License</dt>\n<dd>CC BY 4.0</dd>\n</dl>\n</div>\n"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn find_assets_with_wrong_src_dir() {
+        let tmp_dir = TempDir::new().unwrap();
+        let json = ctx_with_template(
+            "# Chapter 1\n\n",
+            "nosuchsrc",
+            tmp_dir.path().join("mdbook-epub").as_path(),
+        )
+        .to_string();
+        let ctx = RenderContext::from_json(json.as_bytes()).unwrap();
+        let mut g = Generator::new(&ctx).unwrap();
+        g.find_assets().unwrap();
+    }
+
+    #[test]
+    fn chapter_split_into_linked_documents() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let content = "# Chapter 1\n\nIntro[^note]\n\n## Setup\n\nText\n\n## Usage\n\n\
+            [^note]: A note\n";
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["split-on-heading-level"] = json!(2);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        let g = Generator::new(&ctx).unwrap();
+        let BookItem::Chapter(ref ch) = ctx.book.sections[0] else {
+            panic!("expected a chapter");
+        };
+
+        let parts = g.render_chapter_documents(ch).unwrap();
+        let names: Vec<_> = parts.iter().map(|p| p.file_name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["chapter_1.html", "chapter_1-2.html", "chapter_1-3.html"]
+        );
+        assert_eq!(parts[1].heading.as_deref(), Some("Setup"));
+        // each part is a full document, its footnote reference points to the last one
+        assert!(parts
+            .iter()
+            .all(|p| p.body.contains("<title>Chapter 1</title>")));
+        assert!(parts[0].body.contains("<a href=\"chapter_1-3.html#note\">"));
+        assert!(parts[0]
+            .body
+            .contains("<a href=\"chapter_1-2.html\" rel=\"next\">Setup \u{2192}</a>"));
+        assert!(parts[2]
+            .body
+            .contains("<a href=\"chapter_1-2.html\" rel=\"prev\">\u{2190} Setup</a>"));
+        // the single HTML file isn't split
+        assert!(!g.render_chapter_body(ch).unwrap().contains("chapter-parts"));
+
+        json["config"]["output"]["epub"]["split-on-heading-level"] = json!(7);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+        assert!(matches!(Generator::new(&ctx), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn parts_indexed_under_their_documents() {
+        let content = "---\nauthor: Jane Doe\n---\n\n# Chapter 1\n\nIntro\n\n## Setup\n\n\
+            ```rust\n# fn boilerplate() {}\nfn main() {}\n```\n";
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template(content, "src", destination.as_path());
+        json["config"]["output"]["epub"]["split-on-heading-level"] = json!(2);
+        json["config"]["output"]["epub"]["search-index"] = json!(true);
+        let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+
+        let mut epub = Vec::new();
+        Generator::new(&ctx).unwrap().generate(&mut epub).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(epub)).unwrap();
+        let mut index = String::new();
+        zip.by_name("OEBPS/search_index.json")
+            .unwrap()
+            .read_to_string(&mut index)
+            .unwrap();
+        let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+        assert_eq!(index["documents"][0]["href"], "chapter_1.html");
+        assert_eq!(index["documents"][1]["href"], "chapter_1-2.html");
+        assert_eq!(index["documents"][1]["title"], "Setup");
+        assert_eq!(index["index"]["intro"], json!([0]));
+        assert_eq!(index["index"]["main"], json!([1]));
+        // neither the front matter nor the hidden lines are indexed
+        assert!(index["index"].get("jane").is_none(), "{index}");
+        assert!(index["index"].get("boilerplate").is_none(), "{index}");
+    }
+
+    #[test]
+    fn source_and_series_metadata() {
+        assert_eq!(Generator::package_metadata(&Config::default()), "");
+
+        let config = Config::builder()
+            .source("https://example.com/book?a=1&b=2")
+            .series("Rust & Friends")
+            .series_index(2u32)
+            .build();
+        assert_eq!(
+            Generator::package_metadata(&config),
+            "<dc:source>https://example.com/book?a=1&amp;b=2</dc:source>\n\
+            <meta name=\"calibre:series\" content=\"Rust &amp; Friends\"/>\n\
+            <meta name=\"calibre:series_index\" content=\"2\"/>"
+        );
+
+        let config = Config::builder().series("Rust").epub_version(3).build();
+        assert_eq!(
+            Generator::package_metadata(&config),
+            "<meta name=\"calibre:series\" content=\"Rust\"/>\n\
+            <meta property=\"belongs-to-collection\" id=\"series\">Rust</meta>\n\
+            <meta refines=\"#series\" property=\"collection-type\">series</meta>"
+        );
+        let config = Config::builder()
+            .series("Rust")
+            .series_index(3u32)
+            .epub_version(3)
+            .build();
+        assert!(Generator::package_metadata(&config)
+            .ends_with("<meta refines=\"#series\" property=\"group-position\">3</meta>"));
+        // an index without a series is ignored
+        let config = Config::builder().series_index(3u32).build();
+        assert_eq!(Generator::package_metadata(&config), "");
+    }
+
+    #[test]
+    fn identifier_always_present_and_stable() {
+        let tmp_dir = TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("mdbook-epub");
+        let mut json = ctx_with_template("# Chapter 1\n", "src", destination.as_path());
+        let book_uuid = |json: &serde_json::Value| {
+            let ctx = RenderContext::from_json(json.to_string().as_bytes()).unwrap();
+            let generator = Generator::new(&ctx).unwrap();
+            generator.book_uuid()
+        };
+
+        let uuid = book_uuid(&json).unwrap();
+        assert_eq!(uuid.get_version(), Some(uuid::Version::Custom));
+        assert_eq!(book_uuid(&json).unwrap(), uuid);
+        json["config"]["book"]["authors"] = json!(["Jane Doe"]);
+        let with_author = book_uuid(&json).unwrap();
+        assert_ne!(with_author, uuid);
+        // a book without a title or authors has one as well
+        json["config"]["book"]
+            .as_object_mut()
+            .unwrap()
+            .remove("title");
+        json["config"]["book"]["authors"] = json!([]);
+        assert!(!book_uuid(&json).unwrap().is_nil());
+
+        json["config"]["output"]["epub"]["identifier"] =
+            json!("urn:uuid:0f8fad5b-d9cb-469f-a165-70867728950e");
+        assert_eq!(
+            book_uuid(&json).unwrap().to_string(),
+            "0f8fad5b-d9cb-469f-a165-70867728950e"
+        );
+        json["config"]["output"]["epub"]["identifier"] = json!("978-3-16-148410-0");
+        assert!(matches!(book_uuid(&json), Err(Error::InvalidConfig(_))));
     }
 
     fn ctx_with_template(content: &str, source: &str, destination: &Path) -> serde_json::Value {
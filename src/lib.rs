@@ -8,25 +8,46 @@ extern crate serde_derive;
 extern crate serde_json;
 
 use std::fs::{create_dir_all, File};
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use ::mdbook;
 use ::semver;
 use ::thiserror::Error;
+use mdbook::book::{BookItem, Chapter};
 use mdbook::config::Config as MdConfig;
-use mdbook::renderer::RenderContext;
+use mdbook::renderer::{RenderContext, Renderer};
+use mdbook::MDBook;
 use semver::{Version, VersionReq};
 
 use errors::Error;
 
-pub use crate::config::Config;
+pub use crate::config::{
+    CodeBlockOverflow, Config, ConfigBuilder, CssMode, EpubVersionValue, RemoteFailureMode,
+};
+pub use crate::diagnostics::{Diagnostics, Warning, WarningKind};
+pub use crate::epubcheck::{epub_check, CheckMessage, Severity, EPUBCHECK_JAR};
 pub use crate::generator::Generator;
+pub use crate::progress::{Progress, Stage};
+pub use crate::resources::asset::{AssetInfo, AssetInfoKind};
 
+mod archive;
 mod config;
+mod cover;
+mod diagnostics;
+mod epubcheck;
 pub mod errors;
 mod filters;
 mod generator;
+mod loader;
+mod math;
+mod mermaid;
+mod nav;
+mod progress;
 mod resources;
+mod search_index;
+mod split;
 mod utils;
 
 /// The default stylesheet used to make the rendered document pretty.
@@ -51,33 +72,303 @@ fn version_check(ctx: &RenderContext) -> Result<(), Error> {
     }
 }
 
+/// The EPUB backend as seen by `mdbook`, e.g. to decide which preprocessors to run.
+struct EpubRenderer;
+
+impl Renderer for EpubRenderer {
+    fn name(&self) -> &str {
+        "epub"
+    }
+
+    fn render(&self, ctx: &RenderContext) -> mdbook::errors::Result<()> {
+        generate(ctx).map_err(|e| mdbook::errors::Error::msg(e.to_string()))
+    }
+}
+
+/// Load the book at `root` for the standalone mode like `MDBook::load` does. Chapters which
+/// aren't UTF-8 encoded, e.g. Latin-1 ones which `mdbook` fails to load, are decoded with
+/// their invalid characters replaced and a warning instead.
+pub fn load_book(root: &Path) -> Result<MDBook, Error> {
+    match MDBook::load(root) {
+        Ok(md) => Ok(md),
+        Err(e) if loader::is_encoding_error(&e) => {
+            warn!(
+                "{:#}, loading the book with the invalid characters replaced",
+                e
+            );
+            loader::load_lossy(root)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Prepare the `RenderContext` of a book loaded without `mdbook`, e.g. in standalone mode.
+/// The book's preprocessors are run like `mdbook build` does for the `epub` renderer: in
+/// the order of their `before`/`after` settings and only if their `renderers` include
+/// `epub`, so `{{#include}}` and the like are expanded the same way.
+pub fn standalone_render_context(
+    md: &MDBook,
+    destination: PathBuf,
+) -> Result<RenderContext, Error> {
+    let (mut book, _) = md.preprocess_book(&EpubRenderer)?;
+    // mdbook strips it of the chapters' files, but not of the files they include
+    book.for_each_mut(|item| {
+        if let BookItem::Chapter(ref mut ch) = *item {
+            if utils::strip_bom(&mut ch.content) {
+                debug!("Stripped the byte order mark at the start of '{}'", ch.name);
+            }
+        }
+    });
+    Ok(RenderContext::new(
+        md.root.clone(),
+        book,
+        md.config.clone(),
+        destination,
+    ))
+}
+
+/// Read the JSON `RenderContext` passed by `mdbook`, an error points to the offending field.
+pub fn parse_render_context<R: Read>(mut reader: R) -> Result<RenderContext, Error> {
+    let mut json = String::new();
+    reader.read_to_string(&mut json)?;
+    serde_json::from_str(&json).map_err(|source| Error::RenderContext {
+        path: utils::json_path_at(&json, source.line(), source.column()),
+        source,
+    })
+}
+
 /// Generate an `EPUB` version of the provided book.
 pub fn generate(ctx: &RenderContext) -> Result<(), Error> {
+    generate_to(ctx, &output_filename(&ctx.destination, &ctx.config))
+}
+
+/// Generate an `EPUB` version of the provided book into the `outfile`, creating
+/// its parent directory if needed.
+pub fn generate_to(ctx: &RenderContext, outfile: &Path) -> Result<(), Error> {
+    generate_with_diagnostics(ctx, outfile)?;
+    Ok(())
+}
+
+/// Generate an `EPUB` version of the provided book into the `outfile` like [`generate_to`],
+/// returning what it contains, the problems which didn't fail the build and how long it took.
+pub fn generate_with_diagnostics(
+    ctx: &RenderContext,
+    outfile: &Path,
+) -> Result<Diagnostics, Error> {
+    generate_with_progress(ctx, outfile, |_| {})
+}
+
+/// Generate an `EPUB` version of the provided book into the `outfile` like
+/// [`generate_with_diagnostics`], reporting the progress of the long running stages to the
+/// `progress` callback, e.g. for showing a progress bar.
+pub fn generate_with_progress(
+    ctx: &RenderContext,
+    outfile: &Path,
+    progress: impl Fn(Progress) + Send + Sync,
+) -> Result<Diagnostics, Error> {
+    let started = Instant::now();
     info!("Starting the EPUB generator");
     version_check(ctx)?;
 
-    let outfile = output_filename(&ctx.destination, &ctx.config);
+    let config = Config::from_render_context(ctx)?;
+    if !config.enable {
+        info!("The EPUB output is disabled by `enable = false`, skipping");
+        return Ok(Diagnostics::default());
+    }
+
     trace!("Output File: {}", outfile.display());
 
-    if !ctx.destination.exists() {
-        debug!(
-            "Creating destination directory '{}')",
-            ctx.destination.display()
-        );
-        create_dir_all(&ctx.destination)?;
+    if let Some(dir) = outfile.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if !dir.exists() {
+            debug!("Creating destination directory '{}')", dir.display());
+            create_dir_all(dir)?;
+        }
     }
 
-    let f = File::create(&outfile)?;
+    let f = File::create(outfile)?;
     debug!("Path to epub file: '{:?}'", f);
-    Generator::new(ctx)?.generate(f)?;
+    let summary = Generator::generate_with_timeout(ctx, progress, f)?;
+    info!(
+        "Generated the EPUB book: {}, written to '{}'",
+        summary,
+        outfile.display()
+    );
+
+    if config.single_html {
+        let html_file = outfile.with_extension("xhtml");
+        generate_single_html(ctx, &html_file)?;
+        info!(
+            "Wrote the single XHTML document to '{}'",
+            html_file.display()
+        );
+    }
+
+    Ok(Diagnostics::new(summary, started.elapsed()))
+}
+
+/// Find the assets which the `EPUB` of the provided book embeds, i.e. the images and
+/// other files linked by its chapters, sorted by their links. Remote assets aren't
+/// downloaded.
+pub fn discover_assets(ctx: &RenderContext) -> Result<Vec<AssetInfo>, Error> {
+    let config = Config::from_render_context(ctx)?;
+    let mut assets: Vec<AssetInfo> = resources::resource::find(ctx, &config)?
+        .into_iter()
+        .map(AssetInfo::from)
+        .collect();
+    assets.sort_by(|a, b| a.link.cmp(&b.link));
+    Ok(assets)
+}
+
+/// Render the provided book into a single `XHTML` file with all chapters and the
+/// stylesheet inlined, which is handy for previewing and diffing the rendering.
+pub fn generate_single_html(ctx: &RenderContext, outfile: &Path) -> Result<(), Error> {
+    info!("Starting the single XHTML generator");
+    version_check(ctx)?;
+
+    if let Some(dir) = outfile.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        create_dir_all(dir)?;
+    }
+
+    let f = File::create(outfile)?;
+    debug!("Path to xhtml file: '{:?}'", f);
+    Generator::new(ctx)?.generate_single_html(f)?;
 
     Ok(())
 }
 
-/// Calculate the output filename using the `mdbook` config.
+/// Render a chapter of the provided book into the `XHTML` document which the `EPUB`
+/// contains for it, by the book's `[output.epub]` config. Several chapters are rendered
+/// more quickly by [`Generator::render_chapter_xhtml`] of one generator.
+pub fn render_chapter(ctx: &RenderContext, chapter: &Chapter) -> Result<String, Error> {
+    version_check(ctx)?;
+    Generator::new(ctx)?.render_chapter_xhtml(chapter)
+}
+
+/// Calculate the output filename using the `mdbook` config. Books without a title are
+/// named by the `default-book-name` option, or `book.epub`. Characters which aren't
+/// allowed in file names, e.g. `:` of `Rust: The Book`, are replaced by `_`, the title
+/// in the metadata is kept as it is.
 pub fn output_filename(dest: &Path, config: &MdConfig) -> PathBuf {
-    match config.book.title {
-        Some(ref title) => dest.join(title).with_extension("epub"),
-        None => dest.join("book.epub"),
+    let name = match config.book.title {
+        Some(ref title) => title.as_str(),
+        None => config
+            .get("output.epub.default-book-name")
+            .and_then(|name| name.as_str())
+            .unwrap_or("book"),
+    };
+    dest.join(format!("{}.epub", utils::file_name_of(name)))
+}
+
+/// Log to stderr by the `RUST_LOG` environment variable, only errors if it isn't set.
+/// A logger which is set already, e.g. by an application embedding the crate, is kept
+/// and an error returned.
+pub fn init_logging() -> Result<(), log::SetLoggerError> {
+    let filters = std::env::var("RUST_LOG").unwrap_or_default();
+    init_logging_with(&filters, env_logger::Target::Stderr)
+}
+
+/// Log by the `filters` in the format of `RUST_LOG`, e.g. `info,mdbook_epub=debug`, into
+/// the `target`, e.g. a log file by [`env_logger::Target::Pipe`]. Only errors are logged
+/// without filters. Like [`init_logging`], it fails instead of panicking if a logger is
+/// set already.
+pub fn init_logging_with(
+    filters: &str,
+    target: env_logger::Target,
+) -> Result<(), log::SetLoggerError> {
+    env_logger::Builder::new()
+        .parse_filters(filters)
+        .target(target)
+        .try_init()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logging_initialized_once() {
+        let _ = init_logging_with("debug", env_logger::Target::Pipe(Box::new(std::io::sink())));
+        // the logger which is set already is kept
+        assert!(init_logging().is_err());
+    }
+
+    #[test]
+    fn output_filename_of_untitled_book() {
+        let mut config = MdConfig::default();
+        assert_eq!(
+            output_filename(Path::new("out"), &config),
+            PathBuf::from("out/book.epub")
+        );
+
+        config
+            .set("output.epub.default-book-name", "rust-handbook")
+            .unwrap();
+        assert_eq!(
+            output_filename(Path::new("out"), &config),
+            PathBuf::from("out/rust-handbook.epub")
+        );
+
+        // the title takes precedence
+        config.book.title = Some("My Book".to_string());
+        assert_eq!(
+            output_filename(Path::new("out"), &config),
+            PathBuf::from("out/My Book.epub")
+        );
+    }
+
+    #[test]
+    fn output_filename_of_title_invalid_as_file_name() {
+        let mut config = MdConfig::default();
+        config.book.title = Some("Rust: The Book".to_string());
+        assert_eq!(
+            output_filename(Path::new("out"), &config),
+            PathBuf::from("out/Rust_ The Book.epub")
+        );
+        config.book.title = Some("Input/Output 2.0?".to_string());
+        assert_eq!(
+            output_filename(Path::new("out"), &config),
+            PathBuf::from("out/Input_Output 2.0_.epub")
+        );
+    }
+
+    #[test]
+    fn standalone_book_with_bom_and_latin1_chapters() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let root = tmp_dir.path();
+        let src = root.join("src");
+        std::fs::create_dir_all(src.join("nested")).unwrap();
+        std::fs::write(root.join("book.toml"), "[book]\ntitle = \"Book\"\n").unwrap();
+        std::fs::write(
+            src.join("SUMMARY.md"),
+            "# Summary\n\n- [Intro](intro.md)\n- [Cafe](cafe.md)\n  - [Nested](nested/ch.md)\n",
+        )
+        .unwrap();
+        std::fs::write(src.join("intro.md"), "{{#include part.md}}\n").unwrap();
+        std::fs::write(src.join("part.md"), "\u{feff}# Intro\n").unwrap();
+        std::fs::write(src.join("cafe.md"), b"\xef\xbb\xbf# Caf\xe9\n").unwrap();
+        std::fs::write(src.join("nested/ch.md"), "# Nested\n").unwrap();
+
+        let md = load_book(root).unwrap();
+        let ctx = standalone_render_context(&md, root.join("book")).unwrap();
+        let chapters: Vec<&Chapter> = ctx
+            .book
+            .iter()
+            .filter_map(|item| match *item {
+                BookItem::Chapter(ref ch) => Some(ch),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[0].content, "# Intro\n");
+        assert_eq!(chapters[1].content, "# Caf\u{FFFD}\n");
+        assert_eq!(chapters[1].path, Some(PathBuf::from("cafe.md")));
+        assert_eq!(chapters[2].path, Some(PathBuf::from("nested/ch.md")));
+        assert_eq!(chapters[2].parent_names, vec!["Cafe".to_string()]);
+        assert_eq!(chapters[2].content, "# Nested\n");
+
+        // a book which mdbook loads is kept as it is
+        std::fs::write(src.join("cafe.md"), "# Café\n").unwrap();
+        let md = load_book(root).unwrap();
+        assert_eq!(md.book.iter().count(), 3);
     }
 }
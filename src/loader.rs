@@ -0,0 +1,104 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use mdbook::book::{self, BookItem, SummaryItem};
+use mdbook::config::Config as MdConfig;
+use mdbook::MDBook;
+
+use crate::errors::Error;
+use crate::utils;
+
+/// Chapters' paths in `src` by their parents' and their own name, the same named siblings
+/// in the order of `SUMMARY.md`.
+type Locations = HashMap<(Vec<String>, String), VecDeque<PathBuf>>;
+
+/// Whether `mdbook` failed loading the book because a file isn't UTF-8 encoded.
+pub(crate) fn is_encoding_error(error: &mdbook::errors::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::InvalidData)
+    })
+}
+
+/// Load the book like `MDBook::load`, but decode the `SUMMARY.md` and chapters which aren't
+/// UTF-8 encoded lossily. `mdbook` loads the chapters as drafts, whose content is read here.
+pub(crate) fn load_lossy(root: &Path) -> Result<MDBook, Error> {
+    let config_location = root.join("book.toml");
+    let mut config = if config_location.exists() {
+        MdConfig::from_disk(&config_location)?
+    } else {
+        MdConfig::default()
+    };
+    config.update_from_env();
+    let src_dir = root.join(&config.book.src);
+
+    let mut summary = book::parse_summary(&read_lossy(&src_dir.join("SUMMARY.md"))?)?;
+    let mut locations = Locations::new();
+    for items in [
+        &mut summary.prefix_chapters,
+        &mut summary.numbered_chapters,
+        &mut summary.suffix_chapters,
+    ] {
+        take_locations(items, &[], &mut locations);
+    }
+
+    let mut md = MDBook::load_with_config_and_summary(root, config, summary)?;
+    let mut result = Ok(());
+    md.book.for_each_mut(|item| {
+        let BookItem::Chapter(ref mut ch) = *item else {
+            return;
+        };
+        let key = (ch.parent_names.clone(), ch.name.clone());
+        let Some(location) = locations.get_mut(&key).and_then(VecDeque::pop_front) else {
+            return;
+        };
+        match read_lossy(&src_dir.join(&location)) {
+            Ok(content) => {
+                let path = location.strip_prefix(&src_dir).unwrap_or(&location);
+                ch.content = content;
+                ch.path = Some(path.to_path_buf());
+                ch.source_path = Some(path.to_path_buf());
+            }
+            Err(e) if result.is_ok() => result = Err(e),
+            Err(_) => {}
+        }
+    });
+    result.map(|_| md)
+}
+
+// Remove the chapters' locations from the summary, so they're loaded as drafts
+fn take_locations(items: &mut [SummaryItem], parent_names: &[String], locations: &mut Locations) {
+    for item in items {
+        let SummaryItem::Link(ref mut link) = *item else {
+            continue;
+        };
+        if let Some(location) = link.location.take() {
+            locations
+                .entry((parent_names.to_vec(), link.name.clone()))
+                .or_default()
+                .push_back(location);
+        }
+        let mut names = parent_names.to_vec();
+        names.push(link.name.clone());
+        take_locations(&mut link.nested_items, &names, locations);
+    }
+}
+
+// Read the text file, replacing the invalid characters if it isn't UTF-8 encoded
+fn read_lossy(path: &Path) -> Result<String, Error> {
+    let mut content = match String::from_utf8(fs::read(path)?) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!(
+                "'{}' isn't UTF-8 encoded, its invalid characters are replaced by '\u{FFFD}'",
+                path.display()
+            );
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    };
+    utils::strip_bom(&mut content);
+    Ok(content)
+}
@@ -0,0 +1,299 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::utils;
+
+const MATHML_NS: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// Render a (La)TeX formula into a display MathML element, which EPUB 3 reading systems
+/// support natively. Only a basic subset is supported: identifiers, numbers, operators,
+/// groups, sub/superscripts, `\frac`, `\sqrt`, `\text`, greek letters and common symbols.
+/// The original formula is kept as an annotation.
+pub(crate) fn render_mathml(tex: &str) -> String {
+    let mut parser = MathParser {
+        chars: tex.chars().peekable(),
+    };
+    let row = parser.parse_row(false);
+    format!(
+        "<math xmlns=\"{MATHML_NS}\" display=\"block\"><semantics><mrow>{row}</mrow>\
+        <annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>",
+        utils::escape_html(tex.trim())
+    )
+}
+
+/// An element with its optional scripts, e.g. `x_i^2`.
+#[derive(Default)]
+struct Node {
+    base: String,
+    sub: Option<String>,
+    sup: Option<String>,
+}
+
+impl Node {
+    fn render(self) -> String {
+        let base = if self.base.is_empty() {
+            "<mrow></mrow>".to_string()
+        } else {
+            self.base
+        };
+        match (self.sub, self.sup) {
+            (Some(sub), Some(sup)) => format!("<msubsup>{base}{sub}{sup}</msubsup>"),
+            (Some(sub), None) => format!("<msub>{base}{sub}</msub>"),
+            (None, Some(sup)) => format!("<msup>{base}{sup}</msup>"),
+            (None, None) => base,
+        }
+    }
+}
+
+struct MathParser<'s> {
+    chars: Peekable<Chars<'s>>,
+}
+
+impl MathParser<'_> {
+    // Elements up to the end of the formula or the closing brace of the current group
+    fn parse_row(&mut self, in_group: bool) -> String {
+        let mut nodes: Vec<Node> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                None => break,
+                Some('}') => {
+                    self.chars.next();
+                    if in_group {
+                        break;
+                    }
+                }
+                Some(&c @ ('^' | '_')) => {
+                    self.chars.next();
+                    let script = self.parse_atom();
+                    if nodes.is_empty() {
+                        nodes.push(Node::default());
+                    }
+                    let node = nodes.last_mut().unwrap();
+                    let slot = if c == '^' {
+                        &mut node.sup
+                    } else {
+                        &mut node.sub
+                    };
+                    *slot = Some(script);
+                }
+                Some(_) => {
+                    let base = self.parse_atom();
+                    if !base.is_empty() {
+                        nodes.push(Node {
+                            base,
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+        }
+        nodes.into_iter().map(Node::render).collect()
+    }
+
+    // A single element, an empty string for things which are not rendered (e.g. spacing)
+    fn parse_atom(&mut self) -> String {
+        self.skip_whitespace();
+        let Some(c) = self.chars.next() else {
+            return String::new();
+        };
+        match c {
+            '{' => format!("<mrow>{}</mrow>", self.parse_row(true)),
+            '\\' => self.parse_command(),
+            '0'..='9' | '.' => {
+                let mut number = String::from(c);
+                while let Some(&d) = self.chars.peek() {
+                    if !(d.is_ascii_digit() || d == '.') {
+                        break;
+                    }
+                    number.push(d);
+                    self.chars.next();
+                }
+                format!("<mn>{number}</mn>")
+            }
+            c if c.is_alphabetic() => format!("<mi>{c}</mi>"),
+            c => format!("<mo>{}</mo>", utils::escape_html(&c.to_string())),
+        }
+    }
+
+    fn parse_command(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            name.push(c);
+            self.chars.next();
+        }
+        if name.is_empty() {
+            // escaped character, e.g. '\{', or spacing like '\,' and '\\'
+            return match self.chars.next() {
+                Some(c @ ('{' | '}' | '|' | '%' | '$' | '#' | '&')) => {
+                    format!("<mo>{}</mo>", utils::escape_html(&c.to_string()))
+                }
+                _ => String::new(),
+            };
+        }
+
+        match name.as_str() {
+            "frac" => {
+                let numerator = self.parse_atom();
+                let denominator = self.parse_atom();
+                format!("<mfrac>{numerator}{denominator}</mfrac>")
+            }
+            "sqrt" => format!("<msqrt>{}</msqrt>", self.parse_atom()),
+            "text" | "mathrm" => format!("<mtext>{}</mtext>", self.parse_raw_group()),
+            "sin" | "cos" | "tan" | "log" | "ln" | "exp" | "lim" | "max" | "min" | "det" => {
+                format!("<mi>{name}</mi>")
+            }
+            "left" | "right" | "quad" | "qquad" => String::new(),
+            _ => match symbol(&name) {
+                Some((symbol, true)) => format!("<mo>{symbol}</mo>"),
+                Some((symbol, false)) => format!("<mi>{symbol}</mi>"),
+                None => {
+                    warn!("Unsupported math command '\\{name}' is rendered as text");
+                    format!("<mtext>\\{name}</mtext>")
+                }
+            },
+        }
+    }
+
+    // Content of a '{...}' group as plain text
+    fn parse_raw_group(&mut self) -> String {
+        self.skip_whitespace();
+        if self.chars.peek() != Some(&'{') {
+            return String::new();
+        }
+        self.chars.next();
+        let mut depth = 1;
+        let mut text = String::new();
+        for c in self.chars.by_ref() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            text.push(c);
+        }
+        utils::escape_html(&text)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+}
+
+// Unicode symbol of a command and whether it's an operator
+fn symbol(name: &str) -> Option<(&'static str, bool)> {
+    let symbol = match name {
+        "alpha" => ("α", false),
+        "beta" => ("β", false),
+        "gamma" => ("γ", false),
+        "delta" => ("δ", false),
+        "epsilon" => ("ε", false),
+        "zeta" => ("ζ", false),
+        "eta" => ("η", false),
+        "theta" => ("θ", false),
+        "iota" => ("ι", false),
+        "kappa" => ("κ", false),
+        "lambda" => ("λ", false),
+        "mu" => ("μ", false),
+        "nu" => ("ν", false),
+        "xi" => ("ξ", false),
+        "pi" => ("π", false),
+        "rho" => ("ρ", false),
+        "sigma" => ("σ", false),
+        "tau" => ("τ", false),
+        "upsilon" => ("υ", false),
+        "phi" => ("φ", false),
+        "chi" => ("χ", false),
+        "psi" => ("ψ", false),
+        "omega" => ("ω", false),
+        "Gamma" => ("Γ", false),
+        "Delta" => ("Δ", false),
+        "Theta" => ("Θ", false),
+        "Lambda" => ("Λ", false),
+        "Xi" => ("Ξ", false),
+        "Pi" => ("Π", false),
+        "Sigma" => ("Σ", false),
+        "Phi" => ("Φ", false),
+        "Psi" => ("Ψ", false),
+        "Omega" => ("Ω", false),
+        "infty" => ("∞", false),
+        "partial" => ("∂", false),
+        "nabla" => ("∇", false),
+        "times" => ("×", true),
+        "cdot" => ("⋅", true),
+        "div" => ("÷", true),
+        "pm" => ("±", true),
+        "mp" => ("∓", true),
+        "le" | "leq" => ("≤", true),
+        "ge" | "geq" => ("≥", true),
+        "ne" | "neq" => ("≠", true),
+        "approx" => ("≈", true),
+        "equiv" => ("≡", true),
+        "to" | "rightarrow" => ("→", true),
+        "leftarrow" => ("←", true),
+        "Rightarrow" => ("⇒", true),
+        "Leftrightarrow" => ("⇔", true),
+        "sum" => ("∑", true),
+        "prod" => ("∏", true),
+        "int" => ("∫", true),
+        "in" => ("∈", true),
+        "notin" => ("∉", true),
+        "subset" => ("⊂", true),
+        "cup" => ("∪", true),
+        "cap" => ("∩", true),
+        "forall" => ("∀", true),
+        "exists" => ("∃", true),
+        "cdots" => ("⋯", true),
+        "ldots" | "dots" => ("…", true),
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(tex: &str) -> String {
+        MathParser {
+            chars: tex.chars().peekable(),
+        }
+        .parse_row(false)
+    }
+
+    #[test]
+    fn render_formula_with_annotation() {
+        assert_eq!(
+            render_mathml("E = mc^2\n"),
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><semantics>\
+            <mrow><mi>E</mi><mo>=</mo><mi>m</mi><msup><mi>c</mi><mn>2</mn></msup></mrow>\
+            <annotation encoding=\"application/x-tex\">E = mc^2</annotation></semantics></math>"
+        );
+    }
+
+    #[test]
+    fn render_fractions_scripts_and_symbols() {
+        assert_eq!(
+            row(r"\frac{a+1}{\sqrt{x}}"),
+            "<mfrac><mrow><mi>a</mi><mo>+</mo><mn>1</mn></mrow>\
+            <mrow><msqrt><mrow><mi>x</mi></mrow></msqrt></mrow></mfrac>"
+        );
+        assert_eq!(
+            row(r"\sum_{i=0}^{n} x_i \le \alpha"),
+            "<msubsup><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>0</mn></mrow><mrow><mi>n</mi></mrow></msubsup>\
+            <msub><mi>x</mi><mi>i</mi></msub><mo>≤</mo><mi>α</mi>"
+        );
+        assert_eq!(
+            row(r"a < b \text{ if } \foo"),
+            "<mi>a</mi><mo>&lt;</mo><mi>b</mi><mtext> if </mtext><mtext>\\foo</mtext>"
+        );
+    }
+}
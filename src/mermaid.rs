@@ -0,0 +1,57 @@
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::process::Command;
+
+use crate::errors::Error;
+
+/// Folder the rendered diagrams are embedded into.
+pub(crate) const MERMAID_FOLDER: &str = "mermaid";
+
+/// Embedded file name of a diagram, derived from its source so the same diagram
+/// is embedded only once.
+pub(crate) fn diagram_filename(source: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    source.trim().hash(&mut hasher);
+    format!("{MERMAID_FOLDER}/{:016x}.svg", hasher.finish())
+}
+
+/// Render a Mermaid diagram into SVG by the configured command, which is called like the
+/// Mermaid CLI, e.g. `mmdc -i diagram.mmd -o diagram.svg`.
+pub(crate) fn render_svg(command: &str, source: &str) -> Result<Vec<u8>, Error> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| Error::MermaidRender("mermaid-command is empty".to_string()))?;
+
+    let name = diagram_filename(source).replace(['/', '.'], "-");
+    let temp_dir = std::env::temp_dir();
+    let input = temp_dir.join(format!("mdbook-epub-{}-{name}.mmd", std::process::id()));
+    let output = input.with_extension("svg");
+    fs::write(&input, source)?;
+
+    debug!(
+        "Rendering mermaid diagram by '{}' into {:?}",
+        command, output
+    );
+    let result = Command::new(program)
+        .args(words)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .output();
+    let svg = match result {
+        Ok(out) if out.status.success() => fs::read(&output).map_err(Error::from),
+        Ok(out) => Err(Error::MermaidRender(format!(
+            "'{command}' failed with {}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ))),
+        Err(e) => Err(Error::MermaidRender(format!(
+            "unable to run '{command}': {e}"
+        ))),
+    };
+    let _ = fs::remove_file(&input);
+    let _ = fs::remove_file(&output);
+    svg
+}
@@ -0,0 +1,236 @@
+use std::io::Read;
+
+use epub_builder::{EpubContent, ReferenceType, TocElement};
+use handlebars::Handlebars;
+
+use crate::errors::Error;
+use crate::utils;
+
+/// Template of the navigation documents, `nav.xhtml` and the inline TOC `toc.xhtml`.
+pub(crate) const NAV_TEMPLATE: &str = include_str!("nav.hbs");
+
+/// Navigation documents written instead of epub-builder's ones, whose template can't be
+/// changed, by the archive.
+pub(crate) const NAV_FILES: &[(&str, bool)] =
+    &[("OEBPS/nav.xhtml", true), ("OEBPS/toc.xhtml", false)];
+
+/// The book's table of contents and landmarks, recorded like epub-builder records them
+/// when the documents are added to it.
+#[derive(Debug, Default)]
+pub(crate) struct Navigation {
+    /// Title of the navigation documents.
+    toc_name: String,
+    toc: Vec<TocElement>,
+    landmarks: Vec<Landmark>,
+}
+
+#[derive(Debug, Serialize)]
+struct Landmark {
+    epub_type: &'static str,
+    href: String,
+    title: String,
+}
+
+/// Data the navigation documents are rendered with by the `nav.hbs` template.
+#[derive(Debug, Serialize)]
+pub(crate) struct NavContext<'a> {
+    pub(crate) epub_version_3: bool,
+    /// Name of the generator, no `<meta name="generator">` is written if it's empty.
+    pub(crate) generator: &'a str,
+    /// Label the `<nav>` elements with an `aria-label` and the links with a `title`.
+    pub(crate) accessible: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct NavTemplate<'a> {
+    #[serde(flatten)]
+    context: &'a NavContext<'a>,
+    toc_name: &'a str,
+    /// The rendered TOC list.
+    toc: String,
+    landmarks: &'a [Landmark],
+}
+
+impl Navigation {
+    /// Record the TOC entry of the inline TOC titled by the `toc_name` and its landmark,
+    /// like epub-builder's `inline_toc`.
+    pub(crate) fn add_inline_toc(&mut self, toc_name: &str) {
+        self.toc_name = toc_name.to_string();
+        self.add_entry(TocElement::new("toc.xhtml", toc_name));
+        self.landmarks.push(Landmark {
+            epub_type: "toc",
+            href: "toc.xhtml".to_string(),
+            title: toc_name.to_string(),
+        });
+    }
+
+    /// Record the TOC entry and the landmark of the document added to the book.
+    pub(crate) fn add_content<R: Read>(&mut self, content: &EpubContent<R>) {
+        if let Some(epub_type) = content.reftype.map(landmark_type) {
+            if !content.toc.title.is_empty() {
+                self.landmarks.push(Landmark {
+                    epub_type,
+                    href: content.toc.url.clone(),
+                    title: content.toc.title.clone(),
+                });
+            }
+        }
+        if !content.toc.title.is_empty() {
+            self.add_entry(content.toc.clone());
+        }
+    }
+
+    // Add the entry to the last entry with a lower level, like epub-builder's `Toc::add`
+    fn add_entry(&mut self, entry: TocElement) {
+        match self.toc.last_mut() {
+            Some(last) if entry.level > last.level => last.add(entry),
+            _ => self.toc.push(entry),
+        }
+    }
+
+    /// Render the navigation document, with a numbered TOC list for `nav.xhtml`.
+    pub(crate) fn render(
+        &self,
+        hbs: &Handlebars,
+        context: &NavContext,
+        numbered: bool,
+    ) -> Result<String, Error> {
+        let landmarks: &[Landmark] = if context.epub_version_3 {
+            &self.landmarks
+        } else {
+            &[]
+        };
+        let data = NavTemplate {
+            context,
+            toc_name: &self.toc_name,
+            toc: render_list(&self.toc, numbered, context.accessible, 2),
+            landmarks,
+        };
+        Ok(hbs.render("nav", &data)?)
+    }
+}
+
+// Render the entries into a nested list, indented by the `depth`
+fn render_list(entries: &[TocElement], numbered: bool, titled: bool, depth: usize) -> String {
+    let list = if numbered { "ol" } else { "ul" };
+    let indent = "  ".repeat(depth);
+    let mut html = format!("{indent}<{list}>\n");
+    for entry in entries.iter().filter(|entry| !entry.title.is_empty()) {
+        let title = utils::escape_html(&entry.title);
+        let link = format!(
+            "<a href=\"{}\"{}>{}</a>",
+            utils::escape_html(&entry.url),
+            if titled {
+                format!(" title=\"{title}\"")
+            } else {
+                String::new()
+            },
+            title
+        );
+        if entry.children.is_empty() {
+            html.push_str(&format!("{indent}  <li>{link}</li>\n"));
+        } else {
+            html.push_str(&format!("{indent}  <li>\n{indent}    {link}\n"));
+            html.push_str(&render_list(&entry.children, numbered, titled, depth + 2));
+            html.push_str(&format!("\n{indent}  </li>\n"));
+        }
+    }
+    html.push_str(&format!("{indent}</{list}>"));
+    html
+}
+
+// The `epub:type` of the landmark, by epub-builder's names of the reference types
+fn landmark_type(reftype: ReferenceType) -> &'static str {
+    match reftype {
+        ReferenceType::Cover => "cover",
+        ReferenceType::Text => "bodymatter",
+        ReferenceType::Toc => "toc",
+        ReferenceType::Bibliography => "bibliography",
+        ReferenceType::Epigraph => "epigraph",
+        ReferenceType::Foreword => "foreword",
+        ReferenceType::Preface => "preface",
+        ReferenceType::Notes => "endnotes",
+        ReferenceType::Loi => "loi",
+        ReferenceType::Lot => "lot",
+        ReferenceType::Colophon => "colophon",
+        ReferenceType::TitlePage => "titlepage",
+        ReferenceType::Index => "index",
+        ReferenceType::Glossary => "glossary",
+        ReferenceType::Copyright => "copyright-page",
+        ReferenceType::Acknowledgements => "acknowledgements",
+        ReferenceType::Dedication => "dedication",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn navigation() -> Navigation {
+        let mut nav = Navigation::default();
+        nav.add_inline_toc("Contents");
+        nav.add_content(
+            &EpubContent::new("ch1.html", "".as_bytes())
+                .title("1. Tom & \"Jerry\"")
+                .reftype(ReferenceType::Text),
+        );
+        nav.add_content(
+            &EpubContent::new("ch1_1.html", "".as_bytes())
+                .title("Section")
+                .level(2),
+        );
+        // documents without a title aren't listed
+        nav.add_content(&EpubContent::new("hidden.html", "".as_bytes()));
+        nav
+    }
+
+    fn hbs() -> Handlebars<'static> {
+        let mut hbs = Handlebars::new();
+        hbs.register_template_string("nav", NAV_TEMPLATE).unwrap();
+        hbs
+    }
+
+    #[test]
+    fn accessible_labels_in_nav() {
+        let context = NavContext {
+            epub_version_3: true,
+            generator: "mdbook-epub",
+            accessible: true,
+        };
+        let nav = navigation().render(&hbs(), &context, true).unwrap();
+        assert!(nav.contains("<meta name=\"generator\" content=\"mdbook-epub\" />"));
+        assert!(nav.contains(
+            "<nav epub:type=\"toc\" id=\"toc\" aria-label=\"Table of contents\">\n    \
+            <h1 id=\"toc-title\">Contents</h1>\n    <ol>\n      \
+            <li><a href=\"toc.xhtml\" title=\"Contents\">Contents</a></li>\n      <li>\n        \
+            <a href=\"ch1.html\" title=\"1. Tom &amp; &quot;Jerry&quot;\">1. Tom &amp; &quot;Jerry&quot;</a>\n        \
+            <ol>\n          <li><a href=\"ch1_1.html\" title=\"Section\">Section</a></li>\n        \
+            </ol>\n      </li>\n    </ol>\n  </nav>"
+        ), "{nav}");
+        assert!(nav.contains(
+            "<nav epub:type=\"landmarks\" aria-label=\"Landmarks\">\n    <ol>\n      \
+            <li><a epub:type=\"toc\" href=\"toc.xhtml\" title=\"Contents\">Contents</a></li>\n      \
+            <li><a epub:type=\"bodymatter\" href=\"ch1.html\" title=\"1. Tom &amp; &quot;Jerry&quot;\">\
+            1. Tom &amp; &quot;Jerry&quot;</a></li>\n    </ol>\n  </nav>"
+        ), "{nav}");
+        assert!(!nav.contains("hidden.html"));
+    }
+
+    #[test]
+    fn nav_without_labels_or_generator() {
+        let context = NavContext {
+            epub_version_3: false,
+            generator: "",
+            accessible: false,
+        };
+        let nav = navigation().render(&hbs(), &context, false).unwrap();
+        assert!(!nav.contains("name=\"generator\""), "{nav}");
+        assert!(!nav.contains("aria-label"));
+        assert!(!nav.contains("title=\""));
+        // EPUB 2 has no landmarks
+        assert!(!nav.contains("<nav"));
+        assert!(
+            nav.contains("<div id=\"toc\">\n    <h1 id=\"toc-title\">Contents</h1>\n    <ul>\n")
+        );
+    }
+}
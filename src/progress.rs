@@ -0,0 +1,35 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Callback the [`Progress`] of a generation is reported to, which may be called from the
+/// threads downloading the assets.
+pub(crate) type ProgressCallback<'a> = Box<dyn Fn(Progress) + Send + Sync + 'a>;
+
+/// Long running stage of generating an EPUB, which [`Progress`] is reported for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Downloading the remote assets, which aren't cached yet.
+    DownloadingAssets,
+    /// Rendering the chapters, including the draft and skipped ones.
+    RenderingChapters,
+    /// Embedding the assets into the EPUB.
+    EmbeddingAssets,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Stage::DownloadingAssets => "Downloading assets",
+            Stage::RenderingChapters => "Rendering chapters",
+            Stage::EmbeddingAssets => "Embedding assets",
+        })
+    }
+}
+
+/// Progress of a stage, e.g. the third of ten chapters being rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub stage: Stage,
+    /// The item being worked on, starting with 1.
+    pub position: usize,
+    pub total: usize,
+}
@@ -13,6 +13,9 @@ use url::Url;
 pub(crate) enum AssetKind {
     Remote(Url),
     Local(PathBuf),
+    /// Local asset found in one of the additional asset roots outside of `src/` (e.g. `theme/`).
+    /// Keeps the original link, which is rewritten to the embedded file name on rendering.
+    External(PathBuf),
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -27,6 +30,46 @@ pub(crate) struct Asset {
     pub(crate) source: AssetKind,
 }
 
+/// An asset found in the book's chapters, which the EPUB embeds.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AssetInfo {
+    /// The link in the chapter, e.g. `images/logo.png` or `https://example.com/logo.png`.
+    pub link: String,
+    pub kind: AssetInfoKind,
+    /// Absolute path of the file, for remote assets the cache file they're downloaded to,
+    /// which may not exist yet.
+    pub path: PathBuf,
+    /// Media type guessed from the file name, e.g. `image/png`.
+    pub mime_type: String,
+}
+
+/// Where an [`AssetInfo`] comes from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AssetInfoKind {
+    /// A file in the book's `src` directory.
+    Local,
+    /// A file downloaded from its URL.
+    Remote,
+    /// A file in one of the `asset-roots` outside of `src`, e.g. `theme/`.
+    External,
+}
+
+impl From<(String, Asset)> for AssetInfo {
+    fn from((link, asset): (String, Asset)) -> Self {
+        let kind = match asset.source {
+            AssetKind::Local(_) => AssetInfoKind::Local,
+            AssetKind::Remote(_) => AssetInfoKind::Remote,
+            AssetKind::External(_) => AssetInfoKind::External,
+        };
+        AssetInfo {
+            link,
+            kind,
+            path: asset.location_on_disk,
+            mime_type: asset.mimetype.to_string(),
+        }
+    }
+}
+
 impl Asset {
     pub(crate) fn new<P, Q, K>(filename: P, absolute_location: Q, source: K) -> Self
     where
@@ -35,7 +78,7 @@ impl Asset {
         K: Into<AssetKind>,
     {
         let location_on_disk = absolute_location.into();
-        let mt = mime_guess::from_path(&location_on_disk).first_or_octet_stream();
+        let mt = utils::mime_type(&location_on_disk);
         let source = source.into();
         Self {
             location_on_disk,
@@ -46,6 +89,24 @@ impl Asset {
     }
 
     // Create Asset by using remote Url, destination path is used for composing path
+    /// Use the media type of the downloaded content, e.g. of a redirected URL, instead of
+    /// the one guessed from the URL. The embedded file gets a matching extension if it has
+    /// none of the type's extensions, it's still cached by the original URL.
+    pub(crate) fn set_mimetype(&mut self, mimetype: Mime) {
+        // the essence keeps the suffix of types like 'image/svg+xml'
+        let extensions =
+            mime_guess::get_mime_extensions_str(mimetype.essence_str()).unwrap_or_default();
+        let matching = self
+            .filename
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()));
+        if let (false, Some(extension)) = (matching, extensions.first()) {
+            self.filename.set_extension(extension);
+        }
+        self.mimetype = mimetype;
+    }
+
     pub(crate) fn from_url(url: Url, dest_dir: &Path) -> Result<Asset, Error> {
         trace!("Extract from URL: {:#?} into folder = {:?}", url, dest_dir);
         let filename = utils::hash_link(&url);
@@ -53,7 +114,9 @@ impl Asset {
         let full_filename = dest_dir.join(filename);
         // Will fetch assets to normalized path later. fs::canonicalize() only works for existed path.
         let absolute_location = utils::normalize_path(full_filename.as_path());
-        let filename = absolute_location.strip_prefix(dest_dir).unwrap();
+        let filename = absolute_location
+            .strip_prefix(&dest_dir)
+            .map_err(|_| Error::PathTraversal(url.to_string()))?;
         let asset = Asset::new(filename, &absolute_location, AssetKind::Remote(url));
         debug!("Created from URL: {:#?}", asset);
         Ok(asset)
@@ -64,16 +127,118 @@ impl Asset {
         link: &str,
         src_dir: &Path,
         chapter_path: &Path,
+        readme_filename: &str,
+        book_root: &Path,
     ) -> Result<Asset, Error> {
         debug!(
             "Composing asset path for {:?} + {:?} in chapter = {:?}",
             src_dir, link, chapter_path
         );
-        let chapter_path = src_dir.join(chapter_path);
+        if utils::is_drive_relative(link) {
+            return Err(Error::DriveRelativeLink(link.to_string()));
+        }
+        let (full_filename, absolute_location) =
+            Self::resolve_local_link(link, src_dir, chapter_path, readme_filename, book_root)?;
+        Self::local_asset(link, src_dir, &full_filename, &absolute_location)
+    }
+
+    // Create Asset by using local link with a leading '/', which is relative to the book's src
+    // instead of the chapter, if `root-relative-links` is enabled
+    pub(crate) fn from_root_relative(
+        link: &str,
+        src_dir: &Path,
+        book_root: &Path,
+    ) -> Result<Asset, Error> {
+        let relative = utils::root_relative_link(link).unwrap_or(link);
+        debug!("Composing asset path for {:?} + {:?}", src_dir, relative);
+        // resolved like a link of a chapter in the src folder itself, e.g. of `SUMMARY.md`
+        let (full_filename, absolute_location) =
+            Self::resolve_local_link(relative, src_dir, Path::new("SUMMARY.md"), "", book_root)?;
+        Self::local_asset(link, src_dir, &full_filename, &absolute_location)
+    }
+
+    fn local_asset(
+        link: &str,
+        src_dir: &Path,
+        full_filename: &Path,
+        absolute_location: &Path,
+    ) -> Result<Asset, Error> {
+        // Use filename as embedded file path with content from absolute_location.
+        let filename = full_filename.strip_prefix(src_dir)?;
+        // case-insensitive file systems resolve a link like 'IMG/Logo.PNG' to 'img/logo.png',
+        // but readers on case-sensitive systems won't find it
+        if let Some(on_disk) = utils::case_on_disk(src_dir, filename).filter(|f| f != filename) {
+            warn!(
+                "Asset link '{link}' doesn't match the case of the file '{}', it's not found by case-sensitive readers",
+                utils::epub_path(&on_disk)
+            );
+        }
+
+        let asset = Asset::new(
+            filename,
+            absolute_location,
+            AssetKind::Local(PathBuf::from(link)),
+        );
+        trace!(
+            "[{:#?}] = {:?} : {:?}",
+            asset.source,
+            asset.filename,
+            asset.location_on_disk
+        );
+        debug!("Created from local: {:#?}", asset);
+        Ok(asset)
+    }
+
+    // Create Asset by using local link pointing outside of the book's src into one of the
+    // additional asset roots. Returns None if the link doesn't resolve into any of the roots.
+    pub(crate) fn from_asset_root(
+        link: &str,
+        src_dir: &Path,
+        chapter_path: &Path,
+        readme_filename: &str,
+        book_root: &Path,
+        asset_roots: &[PathBuf],
+    ) -> Result<Option<Asset>, Error> {
+        let (_, absolute_location) =
+            Self::resolve_local_link(link, src_dir, chapter_path, readme_filename, book_root)?;
+        if !asset_roots
+            .iter()
+            .any(|root| absolute_location.starts_with(root))
+        {
+            debug!(
+                "Asset '{link}' is not inside of any asset root {:?}",
+                asset_roots
+            );
+            return Ok(None);
+        }
+        // Embedded file path is relative to the book root, e.g. 'theme/logo.png'
+        let filename = absolute_location.strip_prefix(book_root)?;
+
+        let asset = Asset::new(
+            filename,
+            &absolute_location,
+            AssetKind::External(PathBuf::from(link)),
+        );
+        debug!("Created from asset root: {:#?}", asset);
+        Ok(Some(asset))
+    }
+
+    // Compose the full file name of a local link relative to the chapter and find its
+    // absolute location on disk, which is never allowed to be outside of the book root
+    fn resolve_local_link(
+        link: &str,
+        src_dir: &Path,
+        chapter_path: &Path,
+        readme_filename: &str,
+        book_root: &Path,
+    ) -> Result<(PathBuf, PathBuf), Error> {
+        let chapter_dir = Self::chapter_dir(&src_dir.join(chapter_path), readme_filename)?;
+        // links usually have '/' separators, which are mixed with '\' on Windows otherwise
+        let native_link = utils::native_separators(link);
 
         // compose file name by it's link and chapter path
-        let stripped_path = Self::compute_asset_path_by_src_and_link(link, &chapter_path);
-        let normalized_link = utils::normalize_path(PathBuf::from(link).as_path());
+        let stripped_path = Self::compute_asset_path_by_src_and_link(&native_link, &chapter_dir);
+        let normalized_link = utils::normalize_path(PathBuf::from(&native_link).as_path());
         debug!(
             "Composing full_filename by '{:?}' + '{:?}'",
             &stripped_path,
@@ -81,33 +246,66 @@ impl Asset {
         );
         let full_filename = stripped_path.join(normalized_link); // compose final result
 
+        // the embedded file keeps the link's name, e.g. 'my%20image.png', while the file
+        // on disk is found by the decoded one
+        let disk_link = utils::native_separators(&utils::decode_link(link));
+        let disk_filename = Self::compute_asset_path_by_src_and_link(&disk_link, &chapter_dir)
+            .join(utils::normalize_path(Path::new(&disk_link)));
+
         debug!("Joined full_filename = {:?}", &full_filename.display());
-        let absolute_location = full_filename.canonicalize().map_err(|this_error| {
+        let absolute_location = utils::canonicalize(&disk_filename).map_err(|this_error| {
+            // e.g. 'IMG/Logo.PNG' for 'img/logo.png' on case-sensitive file systems
+            let hint = disk_filename
+                .strip_prefix(src_dir)
+                .ok()
+                .and_then(|filename| utils::case_on_disk(src_dir, filename))
+                .map(|on_disk| format!(", did you mean '{}'?", utils::epub_path(&on_disk)))
+                .unwrap_or_default();
             Error::AssetFileNotFound(format!(
-                "Asset was not found: '{link}' by '{}', error = {}",
-                &full_filename.display(),
+                "Asset was not found: '{link}' by '{}', error = {}{hint}",
+                &disk_filename.display(),
                 this_error
             ))
         })?;
+        // symlinks are resolved by canonicalizing, so they can't point outside either
+        if !absolute_location.starts_with(utils::canonicalize(book_root)?) {
+            return Err(Error::PathTraversal(link.to_string()));
+        }
         if !absolute_location.is_file() || absolute_location.is_symlink() {
             return Err(Error::AssetFile(absolute_location));
         }
-        // Use filename as embedded file path with content from absolute_location.
-        let filename = full_filename.strip_prefix(src_dir)?;
+        Ok((full_filename, absolute_location))
+    }
 
-        let asset = Asset::new(
-            filename,
-            &absolute_location,
-            AssetKind::Local(PathBuf::from(link)),
-        );
-        trace!(
-            "[{:#?}] = {:?} : {:?}",
-            asset.source,
-            asset.filename,
-            asset.location_on_disk
+    // Directory the chapter's relative links are resolved against.
+    // mdbook's index preprocessor renames the `readme_filename` chapters into `index.md`,
+    // which doesn't exist on disk, so such a chapter is resolved next to its README file.
+    pub(crate) fn chapter_dir(
+        chapter_path: &Path,
+        readme_filename: &str,
+    ) -> Result<PathBuf, Error> {
+        let parent = chapter_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        if chapter_path.is_file() || !chapter_path.ends_with("index.md") {
+            if !chapter_path.is_file() {
+                debug!("Chapter {:?} is not found on disk", chapter_path);
+            }
+            return Ok(parent);
+        }
+        let readme = parent.join(readme_filename);
+        if !readme.is_file() {
+            return Err(Error::AssetFileNotFound(format!(
+                "Chapter {:?} is not found and there is no '{}' next to it to resolve its links against",
+                chapter_path, readme_filename
+            )));
+        }
+        debug!(
+            "Chapter {:?} is a renamed '{}', resolve assets relative to {:?}",
+            chapter_path, readme_filename, parent
         );
-        debug!("Created from local: {:#?}", asset);
-        Ok(asset)
+        Ok(readme.parent().map(Path::to_path_buf).unwrap_or(parent))
     }
 
     // Analyses input 'link' and stripes chapter's path to shorter link
@@ -116,12 +314,6 @@ impl Asset {
     pub(crate) fn compute_asset_path_by_src_and_link(link: &str, chapter_dir: &PathBuf) -> PathBuf {
         let mut reassigned_asset_root: PathBuf = PathBuf::from(chapter_dir);
         let link_string = String::from(link);
-        // mdbook built-in link preprocessor have `README.md` renamed and `index.md` is not exist
-        // strip the converted filename in the path
-        if chapter_dir.ends_with("index.md") && !chapter_dir.exists() {
-            debug!("It seems a `README.md` chapter, adjust the chapter root.");
-            reassigned_asset_root.pop();
-        }
         // if chapter is a MD file or not exist, remove if from path
         if chapter_dir.is_file() {
             reassigned_asset_root.pop();
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
-use std::path::MAIN_SEPARATOR_STR;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
 
 use const_format::concatcp;
-use html_parser::{Dom, Element, Node};
+use html_parser::{Dom, Node};
 use mdbook::book::BookItem;
 use mdbook::renderer::RenderContext;
 use pulldown_cmark::{Event, Tag};
 use url::Url;
 
+use crate::config::Config;
+use crate::diagnostics::{self, Warning, WarningKind};
 use crate::resources::asset::{Asset, AssetKind};
 use crate::{utils, Error};
 
@@ -26,12 +28,35 @@ pub(crate) const UPPER_FOLDER_PATHS: &[&str] =
 pub(crate) const UPPER_FOLDER_PATHS: &[&str] =
     &["/", MAIN_SEPARATOR_STR, UPPER_PARENT, UPPER_PARENT_LINUX];
 
+/// Folder the `missing_asset_placeholder` is embedded into.
+const MISSING_ASSETS_FOLDER: &str = "missing-assets";
+
 /// Find all resources in book and put them into HashMap.
-/// The key is a link, value is a composed Asset
-pub(crate) fn find(ctx: &RenderContext) -> Result<HashMap<String, Asset>, Error> {
+/// The key is a link, value is a composed Asset.
+/// Assets outside of the book's source dir are skipped unless `strict_assets` is enabled.
+pub(crate) fn find(ctx: &RenderContext, config: &Config) -> Result<HashMap<String, Asset>, Error> {
+    find_with_warnings(ctx, config, &mut Vec::new())
+}
+
+/// Find all resources in book like [`find`], recording the skipped and replaced assets
+/// into the `warnings`.
+pub(crate) fn find_with_warnings(
+    ctx: &RenderContext,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) -> Result<HashMap<String, Asset>, Error> {
     let mut assets: HashMap<String, Asset> = HashMap::new();
     debug!("Finding resources by:\n{:?}", ctx.config);
-    let src_dir = ctx.root.join(&ctx.config.book.src).canonicalize()?;
+    let src_dir = utils::canonicalize(utils::src_dir(&ctx.root, &ctx.config.book.src))?;
+    let mut book_root = utils::canonicalize(&ctx.root)?;
+    // an absolute 'src' may be outside of the book root, its assets are still allowed
+    if !src_dir.starts_with(&book_root) {
+        book_root = src_dir.clone();
+    }
+    let asset_roots = asset_roots(ctx, config);
+    // all missing assets are reported at once, so they can be fixed in one go
+    let mut missing: Vec<String> = Vec::new();
+    let placeholder = missing_asset_placeholder(ctx, config)?;
 
     debug!(
         "Start iteration over a [{:?}] sections in src_dir = {:?}",
@@ -43,21 +68,95 @@ pub(crate) fn find(ctx: &RenderContext) -> Result<HashMap<String, Asset>, Error>
             BookItem::Chapter(ref ch) => {
                 let mut assets_count = 0;
                 debug!("Searching links and assets for: '{}'", ch);
-                if ch.path.is_none() {
+                let Some(ref chapter_path) = ch.path else {
                     debug!("'{}' is a draft chapter and should be no content.", ch.name);
                     continue;
-                }
+                };
                 for link in find_assets_in_markdown(&ch.content)? {
-                    let asset = if let Ok(url) = Url::parse(&link) {
+                    if utils::is_data_uri(&link) {
+                        trace!("Skip inline data URI asset in '{}'", ch.name);
+                        continue;
+                    }
+                    // 'C:img.png' is parsed as URL with the 'c' scheme
+                    let url = Url::parse(&link)
+                        .ok()
+                        .filter(|_| !utils::is_drive_relative(&link));
+                    let result = if let Some(url) = url {
                         Asset::from_url(url, &ctx.destination)
                     } else {
-                        let result = Asset::from_local(&link, &src_dir, ch.path.as_ref().unwrap());
+                        let mut result = if config.root_relative_links
+                            && utils::root_relative_link(&link).is_some()
+                        {
+                            Asset::from_root_relative(&link, &src_dir, &book_root)
+                        } else {
+                            Asset::from_local(
+                                &link,
+                                &src_dir,
+                                chapter_path,
+                                &config.readme_filename,
+                                &book_root,
+                            )
+                        };
+                        if let Err(
+                            ref e @ (Error::PathTraversal(_) | Error::DriveRelativeLink(_)),
+                        ) = result
+                        {
+                            if !config.strict_assets {
+                                let message = format!("{e}, it's ignored");
+                                diagnostics::warn(warnings, WarningKind::OutsideSrcAsset, message);
+                                continue;
+                            }
+                        }
                         if let Err(Error::AssetOutsideSrcDir(_)) = result {
-                            warn!("Asset '{link}' is outside source dir '{src_dir:?}' and ignored");
+                            if let Some(asset) = Asset::from_asset_root(
+                                &link,
+                                &src_dir,
+                                chapter_path,
+                                &config.readme_filename,
+                                &book_root,
+                                &asset_roots,
+                            )? {
+                                result = Ok(asset);
+                            }
+                        }
+                        if let Err(Error::AssetOutsideSrcDir(_)) = result {
+                            if config.strict_assets {
+                                return Err(Error::AssetFileNotFound(format!(
+                                    "Asset '{link}' is outside source dir '{src_dir:?}'"
+                                )));
+                            }
+                            let message = format!(
+                                "Asset '{link}' is outside source dir '{src_dir:?}' and ignored"
+                            );
+                            diagnostics::warn(warnings, WarningKind::OutsideSrcAsset, message);
                             continue;
                         };
                         result
-                    }?;
+                    };
+                    let asset = match result {
+                        Ok(asset) => asset,
+                        Err(e @ (Error::AssetFileNotFound(_) | Error::AssetFile(_))) => {
+                            let Some(ref placeholder) = placeholder else {
+                                debug!("Missing asset in '{}': {}", ch.name, e);
+                                missing.push(format!("'{link}' in chapter '{}'", ch.name));
+                                continue;
+                            };
+                            let message = format!(
+                                "Missing asset '{link}' in chapter '{}' is replaced by the placeholder: {e}",
+                                ch.name
+                            );
+                            diagnostics::warn(warnings, WarningKind::MissingAsset, message);
+                            // keyed by its link like external assets, which is rewritten
+                            let filename = Path::new(MISSING_ASSETS_FOLDER)
+                                .join(placeholder.file_name().unwrap_or_default());
+                            Asset::new(
+                                filename,
+                                placeholder,
+                                AssetKind::External(PathBuf::from(&link)),
+                            )
+                        }
+                        Err(e) => return Err(e),
+                    };
 
                     // that is CORRECT generation way
                     debug!(
@@ -70,8 +169,7 @@ pub(crate) fn find(ctx: &RenderContext) -> Result<HashMap<String, Asset>, Error>
                             let relative = asset.location_on_disk.strip_prefix(&src_dir);
                             match relative {
                                 Ok(relative_link_path) => {
-                                    let link_key: String =
-                                        String::from(relative_link_path.to_str().unwrap());
+                                    let link_key = relative_link_path.to_string_lossy().to_string();
                                     if let std::collections::hash_map::Entry::Vacant(e) =
                                         assets.entry(link_key.to_owned())
                                     {
@@ -85,16 +183,40 @@ pub(crate) fn find(ctx: &RenderContext) -> Result<HashMap<String, Asset>, Error>
                                         debug!("Skipped asset for '{}'", link_key);
                                     }
                                 }
+                                _ if config.strict_assets => {
+                                    return Err(Error::AssetFileNotFound(format!(
+                                        "Local asset '{link}' is outside of book's /src/ folder"
+                                    )));
+                                }
                                 _ => {
                                     // skip incorrect resource/image link outside of book /SRC/ folder
-                                    warn!("Sorry, we can't add 'Local asset' that is outside of book's /src/ folder, {:?}", &asset);
+                                    let message = format!("Sorry, we can't add 'Local asset' that is outside of book's /src/ folder, {:?}", &asset);
+                                    diagnostics::warn(
+                                        warnings,
+                                        WarningKind::OutsideSrcAsset,
+                                        message,
+                                    );
                                 }
                             }
                         }
+                        AssetKind::External(ref original_link) => {
+                            // asset from additional asset root is keyed by its original link,
+                            // so it can be rewritten in the chapter content later
+                            let link_key = original_link.to_string_lossy().to_string();
+                            if let std::collections::hash_map::Entry::Vacant(e) =
+                                assets.entry(link_key.to_owned())
+                            {
+                                debug!(
+                                    "Adding External asset by link '{}' : {:#?}",
+                                    link_key, &asset
+                                );
+                                e.insert(asset);
+                                assets_count += 1;
+                            }
+                        }
                         AssetKind::Remote(_) => {
                             // remote asset kind
-                            let link_key: String =
-                                String::from(asset.location_on_disk.to_str().unwrap());
+                            let link_key = asset.location_on_disk.to_string_lossy().to_string();
                             debug!("Adding Remote asset by link '{}' : {:#?}", link_key, &asset);
                             assets.insert(link_key, asset);
                             assets_count += 1;
@@ -110,26 +232,60 @@ pub(crate) fn find(ctx: &RenderContext) -> Result<HashMap<String, Asset>, Error>
             BookItem::PartTitle(ref title) => trace!("Skip part title: {}.", title),
         }
     }
+    if !missing.is_empty() {
+        return Err(Error::MissingAssets(missing));
+    }
+    for asset in assets.values_mut() {
+        if let Some(mimetype) = config.mime_override(&asset.filename) {
+            debug!(
+                "Media type of {:?} is overridden: {}",
+                asset.filename, mimetype
+            );
+            asset.mimetype = mimetype;
+        }
+    }
     debug!("Added '{}' links and assets in total", assets.len());
     Ok(assets)
 }
 
-// Look up resources in nested HTML element
-fn find_assets_in_nested_html_tags(element: &Element) -> Result<Vec<String>, Error> {
-    let mut found_asset = Vec::new();
-
-    if element.name == "img" {
-        if let Some(dest) = &element.attributes["src"] {
-            found_asset.push(dest.clone());
-        }
-    }
-    for item in &element.children {
-        if let Node::Element(ref nested_element) = item {
-            found_asset.extend(find_assets_in_nested_html_tags(nested_element)?.into_iter());
-        }
+// Canonical path of the image replacing missing assets, which isn't used in strict mode
+fn missing_asset_placeholder(
+    ctx: &RenderContext,
+    config: &Config,
+) -> Result<Option<PathBuf>, Error> {
+    let Some(ref placeholder) = config.missing_asset_placeholder else {
+        return Ok(None);
+    };
+    if config.strict_assets {
+        debug!("Missing asset placeholder is ignored in strict mode");
+        return Ok(None);
     }
+    utils::canonicalize(ctx.root.join(placeholder))
+        .map(Some)
+        .map_err(|e| {
+            Error::AssetFileNotFound(format!(
+                "Missing asset placeholder {placeholder:?} is not found: {e}"
+            ))
+        })
+}
 
-    Ok(found_asset)
+// Canonical paths of the additional asset roots configured relative to the book root
+fn asset_roots(ctx: &RenderContext, config: &Config) -> Vec<PathBuf> {
+    config
+        .asset_roots
+        .iter()
+        .filter_map(|root| match utils::canonicalize(ctx.root.join(root)) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                warn!(
+                    "Asset root '{}' is not found and ignored: {}",
+                    root.display(),
+                    e
+                );
+                None
+            }
+        })
+        .collect()
 }
 
 // Look up resources in chapter md content
@@ -154,8 +310,7 @@ fn find_assets_in_markdown(chapter_src_content: &str) -> Result<Vec<String>, Err
                 if let Ok(dom) = Dom::parse(&content) {
                     for item in dom.children {
                         if let Node::Element(ref element) = item {
-                            found_asset
-                                .extend(find_assets_in_nested_html_tags(element)?.into_iter());
+                            found_asset.extend(utils::image_links(element));
                         }
                     }
                 }
@@ -211,6 +366,23 @@ mod tests {
         assert_eq!(got, should_be);
     }
 
+    #[test]
+    fn test_find_picture_sources() {
+        let src = "<picture>\n  <source srcset=\"logo.avif 1x, https://example.com/logo@2x.avif 2x\" \
+            type=\"image/avif\">\n  <img src=\"logo.png\" srcset=\"logo-640.png 640w\" alt=\"Logo\">\n\
+            </picture>\n\n<p><img src=\"nested.png\" alt=\"Nested\"></p>\n";
+        assert_eq!(
+            find_assets_in_markdown(src).unwrap(),
+            [
+                "https://example.com/logo@2x.avif",
+                "logo-640.png",
+                "logo.avif",
+                "logo.png",
+                "nested.png"
+            ]
+        );
+    }
+
     #[test]
     fn find_local_asset() {
         let link = "./rust-logo.png";
@@ -233,7 +405,7 @@ mod tests {
         }]);
         let ctx = ctx_with_chapters(&chapters, &dest_dir).unwrap();
 
-        let mut assets = find(&ctx).unwrap();
+        let mut assets = find(&ctx, &Config::default()).unwrap();
         assert!(assets.len() == 2);
 
         fn assert_asset(a: Asset, link: &str, ctx: &RenderContext) {
@@ -244,11 +416,9 @@ mod tests {
             }
 
             let filename = link_as_path.as_path().to_str().unwrap();
-            let absolute_location = PathBuf::from(&ctx.root)
-                .join(&src_path)
-                .join(&link_as_path)
-                .canonicalize()
-                .expect("Asset Location is not found");
+            let absolute_location =
+                utils::canonicalize(PathBuf::from(&ctx.root).join(&src_path).join(&link_as_path))
+                    .expect("Asset Location is not found");
 
             let source = AssetKind::Local(PathBuf::from(link));
             let should_be = Asset::new(filename, absolute_location, source);
@@ -278,6 +448,193 @@ mod tests {
         );
     }
 
+    #[test]
+    fn find_assets_with_relative_or_absolute_src() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dest_dir = tmp_dir.path().join("mdbook-epub");
+        let chapters = json!([{
+            "Chapter": {
+            "name": "Chapter 1",
+            "content": "# Chapter 1\n\n![Image](rust-logo.png)\n![Image](assets/rust-logo.png)",
+            "number": [1],
+            "sub_items": [],
+            "path": "chapter_1.md",
+            "parent_names": []}
+        }]);
+        let mut ctx = ctx_with_chapters(&chapters, dest_dir.to_str().unwrap()).unwrap();
+        let relative = find(&ctx, &Config::default()).unwrap();
+        assert_eq!(relative.len(), 2);
+
+        // the absolute 'src' is outside of the book root
+        let src_dir = utils::canonicalize(ctx.root.join(&ctx.config.book.src)).unwrap();
+        ctx.config.book.src = src_dir;
+        ctx.root = tmp_dir.path().to_path_buf();
+        let absolute = find(&ctx, &Config::default()).unwrap();
+        assert_eq!(absolute, relative);
+        let asset = &absolute[&Path::new("assets")
+            .join("rust-logo.png")
+            .display()
+            .to_string()];
+        assert_eq!(asset.filename, Path::new("assets").join("rust-logo.png"));
+    }
+
+    #[test]
+    fn find_asset_outside_src_fails_in_strict_mode() {
+        let outside_link = "../third_party/wikimedia/Epub_logo_color.svg";
+
+        let tmp_dir = TempDir::new().unwrap();
+        let temp = tmp_dir.path().join("mdbook-epub");
+        let dest_dir = temp.as_path().to_string_lossy().to_string();
+        let chapters = json!([{
+            "Chapter": {
+            "name": "Chapter 1",
+            "content": format!("# Chapter 1\r\n\r\n![Image]({outside_link})"),
+            "number": [1],
+            "sub_items": [],
+            "path": "chapter_1.md",
+            "parent_names": []}
+        }]);
+        let ctx = ctx_with_chapters(&chapters, &dest_dir).unwrap();
+
+        // lenient mode skips the asset
+        assert!(find(&ctx, &Config::default()).unwrap().is_empty());
+
+        let config = Config {
+            strict_assets: true,
+            ..Default::default()
+        };
+        let result = find(&ctx, &config);
+        assert!(matches!(result, Err(Error::AssetFileNotFound(_))));
+    }
+
+    #[test]
+    fn report_all_missing_assets() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dest_dir = tmp_dir.path().to_string_lossy().to_string();
+        let chapters = json!([{
+            "Chapter": {
+            "name": "Chapter 1",
+            "content": "![Logo](rust-logo.png)\n![Missing](missing.png)\n![Dir](assets)",
+            "number": [1],
+            "sub_items": [],
+            "path": "chapter_1.md",
+            "parent_names": []}
+        }, {
+            "Chapter": {
+            "name": "Chapter 2",
+            "content": "<img src=\"no/such/image.svg\" />",
+            "number": [2],
+            "sub_items": [],
+            "path": "chapter_2.md",
+            "parent_names": []}
+        }]);
+        let ctx = ctx_with_chapters(&chapters, &dest_dir).unwrap();
+
+        let error = find(&ctx, &Config::default()).unwrap_err();
+        let Error::MissingAssets(ref missing) = error else {
+            panic!("{error:?}");
+        };
+        assert_eq!(
+            missing,
+            &[
+                "'assets' in chapter 'Chapter 1'",
+                "'missing.png' in chapter 'Chapter 1'",
+                "'no/such/image.svg' in chapter 'Chapter 2'",
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "3 assets are missing:\n  'assets' in chapter 'Chapter 1'\n  \
+            'missing.png' in chapter 'Chapter 1'\n  'no/such/image.svg' in chapter 'Chapter 2'"
+        );
+    }
+
+    #[test]
+    fn reject_link_outside_book_root() {
+        let book_root = utils::canonicalize(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/long_book_example"),
+        )
+        .unwrap();
+        let src_dir = book_root.join("src");
+        // the crate's manifest is next to the book, /etc/passwd is far above it
+        for link in [
+            "../../../Cargo.toml",
+            "../../../../../../../../../../etc/passwd",
+        ] {
+            let result = Asset::from_local(
+                link,
+                &src_dir,
+                Path::new("chapter_1.md"),
+                "README.md",
+                &book_root,
+            );
+            assert!(
+                matches!(result, Err(Error::PathTraversal(ref l)) if l == link),
+                "{link}: {result:?}"
+            );
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dest_dir = tmp_dir.path().to_string_lossy().to_string();
+        let chapters = json!([{
+            "Chapter": {
+            "name": "Chapter 1",
+            "content": "# Chapter 1\r\n\r\n![Secret](../../../Cargo.toml)",
+            "number": [1],
+            "sub_items": [],
+            "path": "chapter_1.md",
+            "parent_names": []}
+        }]);
+        let ctx = ctx_with_chapters(&chapters, &dest_dir).unwrap();
+        assert!(find(&ctx, &Config::default()).unwrap().is_empty());
+        let config = Config {
+            strict_assets: true,
+            ..Default::default()
+        };
+        assert!(matches!(find(&ctx, &config), Err(Error::PathTraversal(_))));
+    }
+
+    #[test]
+    fn reject_drive_relative_link() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dest_dir = tmp_dir.path().to_string_lossy().to_string();
+        let chapters = json!([{
+            "Chapter": {
+            "name": "Chapter 1",
+            "content": "# Chapter 1\r\n\r\n![Image](C:rust-logo.png)",
+            "number": [1],
+            "sub_items": [],
+            "path": "chapter_1.md",
+            "parent_names": []}
+        }]);
+        let ctx = ctx_with_chapters(&chapters, &dest_dir).unwrap();
+        assert!(find(&ctx, &Config::default()).unwrap().is_empty());
+        let config = Config {
+            strict_assets: true,
+            ..Default::default()
+        };
+        assert!(
+            matches!(find(&ctx, &config), Err(Error::DriveRelativeLink(ref l)) if l == "C:rust-logo.png")
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn reject_drive_relative_link_windows() {
+        let book_root = utils::canonicalize(r"tests\long_book_example").unwrap();
+        let src_dir = book_root.join("src");
+        let result = Asset::from_local(
+            r"C:assets\rust-logo.png",
+            &src_dir,
+            Path::new("chapter_1.md"),
+            "README.md",
+            &book_root,
+        );
+        assert!(matches!(result, Err(Error::DriveRelativeLink(_))));
+        // absolute drive paths are no drive-relative links
+        assert!(!utils::is_drive_relative(r"C:\book\src\rust-logo.png"));
+    }
+
     #[test]
     fn find_remote_asset() {
         let link = "https://www.rust-lang.org/static/images/rust-logo-blk.svg";
@@ -297,7 +654,7 @@ mod tests {
             "parent_names": []}}]);
         let ctx = ctx_with_chapters(&chapters, &dest_dir).unwrap();
 
-        let mut assets = find(&ctx).unwrap();
+        let mut assets = find(&ctx, &Config::default()).unwrap();
         assert!(assets.len() == 2);
 
         for (key, value) in assets.clone().into_iter() {
@@ -324,6 +681,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn missing_asset_replaced_by_placeholder() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dest_dir = tmp_dir.path().join("mdbook-epub");
+        let chapters = json!([{
+            "Chapter": {
+            "name": "Chapter 1",
+            "content": "![Gone](gone.png)\n![Image](rust-logo.png)",
+            "number": [1],
+            "sub_items": [],
+            "path": "chapter_1.md",
+            "parent_names": []}
+        }]);
+        let ctx = ctx_with_chapters(&chapters, dest_dir.to_str().unwrap()).unwrap();
+        let mut config = Config {
+            missing_asset_placeholder: Some(PathBuf::from("src/assets/rust-logo.png")),
+            ..Config::default()
+        };
+
+        let assets = find(&ctx, &config).unwrap();
+        assert_eq!(assets.len(), 2);
+        let placeholder = &assets["gone.png"];
+        assert_eq!(
+            placeholder.source,
+            AssetKind::External(PathBuf::from("gone.png"))
+        );
+        assert_eq!(
+            placeholder.filename,
+            Path::new("missing-assets").join("rust-logo.png")
+        );
+        assert!(placeholder
+            .location_on_disk
+            .ends_with("assets/rust-logo.png"));
+
+        // missing assets still fail the build in strict mode
+        config.strict_assets = true;
+        assert!(matches!(find(&ctx, &config), Err(Error::MissingAssets(_))));
+    }
+
     #[test]
     fn find_draft_chapter_without_error() {
         let tmp_dir = TempDir::new().unwrap();
@@ -338,7 +734,100 @@ mod tests {
             "path": null,
             "parent_names": []}}]);
         let ctx = ctx_with_chapters(&chapters, &dest_dir).unwrap();
-        assert!(find(&ctx).unwrap().is_empty());
+        assert!(find(&ctx, &Config::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn local_asset_link_with_mismatched_case() {
+        let src_dir = utils::canonicalize(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/long_book_example/src"),
+        )
+        .unwrap();
+        let result = Asset::from_local(
+            "../02_Advanced/EPUB_logo.svg",
+            &src_dir,
+            Path::new("01_getting_started/02_article.md"),
+            "README.md",
+            src_dir.parent().unwrap(),
+        );
+        match result {
+            // case-insensitive file systems find it, which is only warned about
+            Ok(asset) => assert_eq!(
+                utils::case_on_disk(&src_dir, &asset.filename),
+                Some(Path::new("02_advanced").join("Epub_logo.svg"))
+            ),
+            Err(Error::AssetFileNotFound(msg)) => {
+                assert!(
+                    msg.ends_with(", did you mean '02_advanced/Epub_logo.svg'?"),
+                    "{msg}"
+                )
+            }
+            Err(e) => panic!("{e:?}"),
+        }
+    }
+
+    #[test]
+    fn find_local_asset_in_readme_chapter() {
+        let src_dir = utils::canonicalize(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/long_book_example/src"),
+        )
+        .unwrap();
+        let should_be = src_dir.join("02_advanced").join("Epub_logo.svg");
+
+        // chapter is left as is, e.g. in standalone mode
+        let asset = Asset::from_local(
+            "Epub_logo.svg",
+            &src_dir,
+            Path::new("02_advanced/README.md"),
+            "README.md",
+            src_dir.parent().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(asset.location_on_disk, should_be);
+        assert_eq!(
+            asset.filename,
+            Path::new("02_advanced").join("Epub_logo.svg")
+        );
+
+        // chapter is renamed into 'index.md' by the mdbook's index preprocessor
+        let asset = Asset::from_local(
+            "Epub_logo.svg",
+            &src_dir,
+            Path::new("02_advanced/index.md"),
+            "README.md",
+            src_dir.parent().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(asset.location_on_disk, should_be);
+        assert_eq!(
+            asset.filename,
+            Path::new("02_advanced").join("Epub_logo.svg")
+        );
+    }
+
+    #[test]
+    fn chapter_dir_of_renamed_readme() {
+        let src_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/long_book_example/src");
+        let advanced_dir = src_dir.join("02_advanced");
+
+        assert_eq!(
+            Asset::chapter_dir(&advanced_dir.join("README.md"), "README.md").unwrap(),
+            advanced_dir
+        );
+        assert_eq!(
+            Asset::chapter_dir(&advanced_dir.join("index.md"), "README.md").unwrap(),
+            advanced_dir
+        );
+        // a chapter which isn't on disk, e.g. added by a preprocessor, is resolved next to it
+        assert_eq!(
+            Asset::chapter_dir(&advanced_dir.join("generated.md"), "README.md").unwrap(),
+            advanced_dir
+        );
+        // there's no README of the custom name to resolve the renamed chapter against
+        assert!(matches!(
+            Asset::chapter_dir(&advanced_dir.join("index.md"), "readme.markdown"),
+            Err(Error::AssetFileNotFound(_))
+        ));
     }
 
     #[test]
@@ -349,7 +838,9 @@ mod tests {
             Asset::from_local(
                 "a.png",
                 Path::new("tests\\dummy\\src"),
-                Path::new("ch\\a.md")
+                Path::new("ch\\a.md"),
+                "README.md",
+                Path::new("tests\\dummy")
             )
             .unwrap_err()
             .to_string()
@@ -365,7 +856,9 @@ mod tests {
             Asset::from_local(
                 "a.png",
                 Path::new("tests/long_book_example/src"),
-                Path::new("ch/a.md")
+                Path::new("ch/a.md"),
+                "README.md",
+                Path::new("tests/long_book_example")
             )
             .unwrap_err()
             .to_string()
@@ -374,16 +867,16 @@ mod tests {
 
     #[cfg(not(target_os = "windows"))]
     #[test]
-    #[should_panic(
-        expected = "Asset was not found: 'wikimedia' by 'tests/long_book_example/third_party/a.md/wikimedia', error = No such file or directory (os error 2)"
-    )]
+    #[should_panic(expected = "Asset was not a file")]
     fn find_asset_fail_when_it_is_a_dir() {
         panic!(
             "{}",
             Asset::from_local(
                 "wikimedia",
                 Path::new("tests/long_book_example"),
-                Path::new("third_party/a.md")
+                Path::new("third_party/a.md"),
+                "README.md",
+                Path::new("tests/long_book_example")
             )
             .unwrap_err()
             .to_string()
@@ -402,7 +895,9 @@ mod tests {
             Asset::from_local(
                 "wikimedia",
                 Path::new("tests\\dummy"),
-                Path::new("third_party\\a.md")
+                Path::new("third_party\\a.md"),
+                "README.md",
+                Path::new("tests\\dummy")
             )
             .unwrap_err()
             .to_string()
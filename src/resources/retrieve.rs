@@ -4,15 +4,19 @@ use std::{
     path::Path,
 };
 
+use mime_guess::Mime;
 #[cfg(test)]
 use mockall::automock;
+use url::Url;
 
 use crate::resources::asset::{Asset, AssetKind};
-use crate::Error;
+use crate::{utils, Error};
 
 #[cfg_attr(test, automock)]
 pub(crate) trait ContentRetriever {
-    fn download(&self, asset: &Asset) -> Result<(), Error> {
+    /// Download the remote asset into its cache file, if it's not cached yet. Returns the
+    /// media type of the downloaded content, if it's known from the (final) response.
+    fn download(&self, asset: &Asset) -> Result<Option<Mime>, Error> {
         if let AssetKind::Remote(url) = &asset.source {
             let dest = &asset.location_on_disk;
             if dest.is_file() {
@@ -22,31 +26,74 @@ pub(crate) trait ContentRetriever {
                     fs::create_dir_all(cache_dir)?;
                 }
                 debug!("Downloading asset : {}", url);
+                // no empty cache file is left behind by a failed request
+                let (mut resp, mimetype) = self.retrieve_typed(url.as_str())?;
                 let mut file = OpenOptions::new()
                     .create(true)
                     .truncate(true)
                     .write(true)
                     .open(dest)?;
-                let mut resp = self.retrieve(url.as_str())?;
                 io::copy(&mut resp, &mut file)?;
-                debug!("Downloaded asset by '{}'", url);
+                debug!("Downloaded asset by '{}' as {:?}", url, mimetype);
+                return Ok(mimetype);
             }
         }
-        Ok(())
+        Ok(None)
     }
     fn read(&self, path: &Path, buffer: &mut Vec<u8>) -> Result<(), Error> {
         File::open(path)?.read_to_end(buffer)?;
         Ok(())
     }
+    /// Open the file for streaming its content, e.g. into the EPUB, instead of reading it
+    /// into memory at once.
+    fn open(&self, path: &Path) -> Result<Box<dyn Read>, Error> {
+        Ok(Box::new(File::open(path)?))
+    }
     fn retrieve(&self, url: &str) -> Result<Box<(dyn Read + Send + Sync + 'static)>, Error>;
+    /// Content of the URL with its media type, if it's known.
+    fn retrieve_typed(
+        &self,
+        url: &str,
+    ) -> Result<(Box<dyn Read + Send + Sync + 'static>, Option<Mime>), Error> {
+        Ok((self.retrieve(url)?, None))
+    }
+}
+
+/// Media type of a response by its `Content-Type` header, or by the extension of its URL,
+/// which is the final one of a redirected request.
+pub(crate) fn response_type(content_type: Option<&str>, final_url: &str) -> Option<Mime> {
+    content_type
+        .and_then(|value| value.parse::<Mime>().ok())
+        .map(|mimetype| mimetype.essence_str().parse::<Mime>().unwrap_or(mimetype))
+        // servers tell this when they don't know the type either
+        .filter(|mimetype| *mimetype != mime_guess::mime::APPLICATION_OCTET_STREAM)
+        .or_else(|| {
+            let url = Url::parse(final_url).ok()?;
+            Some(utils::mime_type(Path::new(url.path())))
+                .filter(|mimetype| *mimetype != mime_guess::mime::APPLICATION_OCTET_STREAM)
+        })
 }
 
 pub(crate) struct ResourceHandler;
 impl ContentRetriever for ResourceHandler {
     fn retrieve(&self, url: &str) -> Result<Box<(dyn Read + Send + Sync + 'static)>, Error> {
+        Ok(self.retrieve_typed(url)?.0)
+    }
+
+    fn retrieve_typed(
+        &self,
+        url: &str,
+    ) -> Result<(Box<dyn Read + Send + Sync + 'static>, Option<Mime>), Error> {
+        // redirects are followed, so the response may come from a different URL or host
         let res = ureq::get(url).call()?;
         match res.status() {
-            200 => Ok(res.into_reader()),
+            200 => {
+                let mimetype = response_type(res.header("Content-Type"), res.get_url());
+                if res.get_url() != url {
+                    debug!("'{}' is redirected to '{}'", url, res.get_url());
+                }
+                Ok((res.into_reader(), mimetype))
+            }
             404 => Err(Error::AssetFileNotFound(format!(
                 "Missing remote resource: {url}"
             ))),
@@ -57,12 +104,13 @@ impl ContentRetriever for ResourceHandler {
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
     use tempfile::TempDir;
 
     use crate::errors::Error;
-    use crate::resources::asset::Asset;
+    use crate::resources::asset::{Asset, AssetKind};
 
-    use super::ContentRetriever;
+    use super::{response_type, ContentRetriever};
 
     type BoxRead = Box<(dyn std::io::Read + Send + Sync + 'static)>;
 
@@ -103,6 +151,8 @@ mod tests {
 
         assert!(r.is_err());
         assert!(matches!(r.unwrap_err(), Error::AssetFileNotFound(_)));
+        // a later build doesn't take an empty file for the cached asset
+        assert!(!a.location_on_disk.exists());
     }
 
     #[test]
@@ -142,6 +192,92 @@ mod tests {
         assert_eq!(buffer, "Downloaded content");
     }
 
+    #[test]
+    fn download_type_of_redirected_url() {
+        use std::collections::HashMap;
+
+        // short link -> other domain -> CDN, only the last one tells the content type
+        struct RedirectingHandler {
+            redirects: HashMap<&'static str, &'static str>,
+        }
+        impl ContentRetriever for RedirectingHandler {
+            fn retrieve(&self, url: &str) -> Result<BoxRead, Error> {
+                Ok(self.retrieve_typed(url)?.0)
+            }
+            fn retrieve_typed(
+                &self,
+                url: &str,
+            ) -> Result<(BoxRead, Option<mime_guess::Mime>), Error> {
+                let mut final_url = url;
+                while let Some(next) = self.redirects.get(final_url) {
+                    final_url = next;
+                }
+                let content_type = final_url
+                    .starts_with("https://cdn.")
+                    .then_some("image/svg+xml");
+                Ok((
+                    Box::new("<svg/>".as_bytes()),
+                    response_type(content_type, final_url),
+                ))
+            }
+        }
+        let cr = RedirectingHandler {
+            redirects: HashMap::from([
+                ("https://short.link/logo", "https://images.example.org/logo"),
+                (
+                    "https://images.example.org/logo",
+                    "https://cdn.example.org/a1b2",
+                ),
+            ]),
+        };
+        let a = temp_remote_asset("https://short.link/logo").unwrap();
+        assert_eq!(a.mimetype, mime_guess::mime::APPLICATION_OCTET_STREAM);
+        let mimetype = cr.download(&a).unwrap();
+        assert_eq!(mimetype.unwrap().essence_str(), "image/svg+xml");
+        // the cached file is still found by the original link
+        assert_eq!(std::fs::read(&a.location_on_disk).unwrap(), b"<svg/>");
+        assert_eq!(cr.download(&a).unwrap(), None);
+    }
+
+    #[test]
+    fn response_type_by_header_or_final_url() {
+        let url = "https://cdn.example.org/images/logo.svg?v=2";
+        let essence = |content_type, url| response_type(content_type, url).map(|m| m.to_string());
+        assert_eq!(
+            essence(Some("image/png; charset=binary"), url).as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(
+            essence(Some("application/octet-stream"), url).as_deref(),
+            Some("image/svg+xml")
+        );
+        assert_eq!(essence(None, url).as_deref(), Some("image/svg+xml"));
+        assert_eq!(
+            essence(Some("not a type"), "https://cdn.example.org/a1b2"),
+            None
+        );
+    }
+
+    #[test]
+    fn modern_image_types_by_extension() {
+        let webp = temp_remote_asset("https://cdn.example.org/photo.webp").unwrap();
+        assert_eq!(webp.mimetype.essence_str(), "image/webp");
+        let avif = temp_remote_asset("https://cdn.example.org/photo.avif?w=200").unwrap();
+        assert_eq!(avif.mimetype.essence_str(), "image/avif");
+        let local = Asset::new(
+            "photo.avif",
+            "/book/src/photo.avif",
+            AssetKind::Local(PathBuf::from("photo.avif")),
+        );
+        assert_eq!(local.mimetype.essence_str(), "image/avif");
+        assert_eq!(
+            response_type(None, "https://cdn.example.org/photo.jxl")
+                .unwrap()
+                .essence_str(),
+            "image/jxl"
+        );
+    }
+
     fn temp_remote_asset(url: &str) -> Result<Asset, Error> {
         let tmp_dir = TempDir::new().unwrap();
         let dest_dir = tmp_dir.path().join("mdbook-epub");
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// File name of the embedded search index inside the EPUB.
+pub(crate) const SEARCH_INDEX_FILE: &str = "search_index.json";
+
+/// Words shorter than that are not indexed.
+const MIN_TERM_LENGTH: usize = 2;
+
+/// A simple inverted index over chapter texts, which is embedded as a JSON resource
+/// for reading systems and tools supporting it.
+#[derive(Debug, Default)]
+pub(crate) struct SearchIndex {
+    documents: Vec<IndexedDocument>,
+    terms: BTreeMap<String, BTreeSet<usize>>,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexedDocument {
+    href: String,
+    title: String,
+}
+
+impl SearchIndex {
+    /// Index the plain text of a document rendered into `href`.
+    pub(crate) fn add_document(&mut self, href: &str, title: &str, text: &str) {
+        let doc_id = self.documents.len();
+        self.documents.push(IndexedDocument {
+            // EPUB internal paths always use forward slashes
+            href: href.replace('\\', "/"),
+            title: title.to_string(),
+        });
+
+        for term in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() >= MIN_TERM_LENGTH)
+        {
+            self.terms
+                .entry(term.to_lowercase())
+                .or_default()
+                .insert(doc_id);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Serialize the index as `{"documents": [{"href", "title"}], "index": {"term": [doc ids]}}`.
+    pub(crate) fn to_json(&self) -> String {
+        json!({
+            "documents": self.documents,
+            "index": self.terms,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn index_terms_by_document() {
+        let mut index = SearchIndex::default();
+        assert!(index.is_empty());
+        index.add_document(
+            "chapter_1.html",
+            "Chapter 1",
+            "Ownership Rust's ownership model, see Vec .",
+        );
+        index.add_document("chapter_2.html", "Chapter 2", "Borrowing and ownership");
+        assert!(!index.is_empty());
+
+        let json: Value = serde_json::from_str(&index.to_json()).unwrap();
+        assert_eq!(json["documents"][0]["href"], "chapter_1.html");
+        assert_eq!(json["documents"][1]["title"], "Chapter 2");
+        assert_eq!(json["index"]["ownership"], json!([0, 1]));
+        assert_eq!(json["index"]["rust"], json!([0]));
+        assert_eq!(json["index"]["vec"], json!([0]));
+        assert_eq!(json["index"]["borrowing"], json!([1]));
+        // too short words and punctuation are not indexed
+        assert!(json["index"].get("s").is_none());
+        assert!(json["index"].get(".").is_none());
+    }
+}
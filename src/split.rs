@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{html, CowStr, Event, HeadingLevel, Tag, TagEnd};
+
+use crate::utils;
+
+/// A document of a chapter, which is split into several ones at its headings of the
+/// `split-on-heading-level` to keep the documents small for reading systems.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ChapterPart {
+    /// File name of the part's document, e.g. `intro-2.html` for the second part of `intro.md`.
+    pub(crate) file_name: String,
+    /// Text of the heading the part starts with, `None` for the chapter's first part.
+    pub(crate) heading: Option<String>,
+    pub(crate) body: String,
+    /// Plain text of the part as it's rendered, for the search index.
+    pub(crate) text: String,
+}
+
+/// Render the chapter's events into its parts, starting a part at each heading of the
+/// `level` which isn't nested in another element, e.g. a block quote. The chapter isn't
+/// split without a level. The first part's file is `page`, the chapter's one.
+pub(crate) fn render_parts<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    level: Option<HeadingLevel>,
+    page: &str,
+) -> Vec<ChapterPart> {
+    let mut events: Vec<Event<'a>> = events.collect();
+    add_heading_ids(&mut events);
+    let mut parts: Vec<(Option<String>, Vec<Event<'a>>)> = vec![(None, Vec::new())];
+    let mut depth = 0usize;
+    let mut heading: Option<String> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading { level: l, .. }) if depth == 0 && Some(l) == level => {
+                // content before the first heading stays in the chapter's first part
+                if parts.last().is_some_and(|(_, events)| !events.is_empty()) {
+                    parts.push((None, Vec::new()));
+                }
+                heading = Some(String::new());
+            }
+            Event::Text(ref text) | Event::Code(ref text) => {
+                if let Some(ref mut heading) = heading {
+                    heading.push_str(text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) if depth == 1 => {
+                let split = parts.len() > 1;
+                if let (Some(text), Some(last)) = (heading.take(), parts.last_mut()) {
+                    if split {
+                        last.0 = Some(text.trim().to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if let Some((_, events)) = parts.last_mut() {
+            events.push(event);
+        }
+    }
+
+    let (stem, extension) = page.rsplit_once('.').unwrap_or((page, "html"));
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, (heading, events))| {
+            let text = plain_text(&events);
+            let mut body = String::with_capacity(3000); // big enough arbitrary size
+            html::push_html(&mut body, events.into_iter());
+            ChapterPart {
+                file_name: match i {
+                    0 => page.to_string(),
+                    _ => format!("{stem}-{}.{extension}", i + 1),
+                },
+                heading,
+                body,
+                text,
+            }
+        })
+        .collect()
+}
+
+// Collect the textual content of the events, skipping markup
+fn plain_text(events: &[Event<'_>]) -> String {
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::Text(t) | Event::Code(t) => {
+                text.push_str(t);
+                text.push(' ');
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Give the headings without an `{#id}` the anchor mdbook gives them, so the links to
+/// them, e.g. `chapter.md#usage`, keep working.
+fn add_heading_ids(events: &mut [Event<'_>]) {
+    let mut slugs = utils::Slugs::default();
+    let mut i = 0;
+    while i < events.len() {
+        let Event::Start(Tag::Heading { id: None, .. }) = events[i] else {
+            i += 1;
+            continue;
+        };
+        let end = events[i..]
+            .iter()
+            .position(|event| matches!(event, Event::End(TagEnd::Heading(_))))
+            .map_or(events.len(), |end| i + end);
+        // the slug is made of the rendered heading like in mdbook, e.g. `Vec&lt;T&gt;`
+        let mut heading = String::new();
+        html::push_html(&mut heading, events[i + 1..end].iter().cloned());
+        let slug = slugs.unique(&heading);
+        if let Event::Start(Tag::Heading { ref mut id, .. }) = events[i] {
+            if !slug.is_empty() {
+                *id = Some(CowStr::from(slug));
+            }
+        }
+        i = end;
+    }
+}
+
+/// Point the links to anchors in other parts of the chapter, e.g. of its footnotes,
+/// to the parts' documents.
+pub(crate) fn link_anchors(parts: &mut [ChapterPart]) {
+    if parts.len() < 2 {
+        return;
+    }
+    let mut anchors = HashMap::new();
+    for part in parts.iter() {
+        for id in attribute_values(&part.body, " id=\"") {
+            anchors
+                .entry(id.to_string())
+                .or_insert(part.file_name.clone());
+        }
+    }
+    for part in parts.iter_mut() {
+        let mut linked = String::with_capacity(part.body.len());
+        let mut rest = part.body.as_str();
+        while let Some(start) = rest.find("href=\"#") {
+            let (before, link) = rest.split_at(start + "href=\"".len());
+            linked.push_str(before);
+            let end = link.find('"').unwrap_or(link.len());
+            match anchors.get(&link[1..end]) {
+                Some(file_name) if *file_name != part.file_name => linked.push_str(file_name),
+                _ => {}
+            }
+            rest = link;
+        }
+        linked.push_str(rest);
+        part.body = linked;
+    }
+}
+
+/// Add links to the previous and next parts at the end of each part of a split chapter.
+pub(crate) fn add_cross_links(parts: &mut [ChapterPart], chapter_title: &str) {
+    if parts.len() < 2 {
+        return;
+    }
+    let links: Vec<(String, String)> = parts
+        .iter()
+        .map(|part| {
+            let title = part.heading.as_deref().unwrap_or(chapter_title);
+            (part.file_name.clone(), utils::escape_html(title))
+        })
+        .collect();
+    for (i, part) in parts.iter_mut().enumerate() {
+        let mut nav = Vec::new();
+        if let Some((file_name, title)) = i.checked_sub(1).map(|prev| &links[prev]) {
+            nav.push(format!(
+                "<a href=\"{file_name}\" rel=\"prev\">\u{2190} {title}</a>"
+            ));
+        }
+        if let Some((file_name, title)) = links.get(i + 1) {
+            nav.push(format!(
+                "<a href=\"{file_name}\" rel=\"next\">{title} \u{2192}</a>"
+            ));
+        }
+        part.body.push_str(&format!(
+            "<p class=\"chapter-parts\">{}</p>\n",
+            nav.join(" ")
+        ));
+    }
+}
+
+// Values of the attribute in the HTML, `prefix` is its name with the opening quote
+fn attribute_values<'h>(html: &'h str, prefix: &str) -> Vec<&'h str> {
+    html.match_indices(prefix)
+        .filter_map(|(i, _)| {
+            let value = &html[i + prefix.len()..];
+            value.find('"').map(|end| &value[..end])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{CowStr, Event};
+
+    fn parts_of(markdown: &str, level: Option<HeadingLevel>) -> Vec<ChapterPart> {
+        let events = utils::create_new_pull_down_parser(markdown);
+        render_parts(events, level, "chapter.html")
+    }
+
+    #[test]
+    fn chapter_split_at_headings_of_the_level() {
+        let markdown = "# Title\n\nIntro\n\n## First `one`\n\nText\n\n> ## Quoted\n\n\
+            ### Nested\n\n## Second\n\nMore\n";
+        let parts = parts_of(markdown, Some(HeadingLevel::H2));
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].file_name, "chapter.html");
+        assert_eq!(parts[0].heading, None);
+        assert_eq!(parts[0].body, "<h1 id=\"title\">Title</h1>\n<p>Intro</p>\n");
+        assert_eq!(parts[1].file_name, "chapter-2.html");
+        assert_eq!(parts[1].heading.as_deref(), Some("First one"));
+        assert!(parts[1]
+            .body
+            .starts_with("<h2 id=\"first-one\">First <code>one</code></h2>"));
+        assert!(parts[1].body.contains("<h2 id=\"quoted\">Quoted</h2>"));
+        assert!(parts[1].body.contains("<h3 id=\"nested\">Nested</h3>"));
+        assert_eq!(parts[2].file_name, "chapter-3.html");
+        assert_eq!(parts[2].heading.as_deref(), Some("Second"));
+
+        // a chapter starting with a heading of the level has no empty first part
+        let parts = parts_of("## Only\n\nText\n", Some(HeadingLevel::H2));
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].heading, None);
+        assert_eq!(parts_of(markdown, None).len(), 1);
+    }
+
+    #[test]
+    fn headings_given_unique_anchors() {
+        let markdown = "# Usage\n\n## `Vec<T>`\n\n## Usage\n\n## 🦀\n\n## Usage\n";
+        let body = parts_of(markdown, None).swap_remove(0).body;
+        assert_eq!(
+            body,
+            "<h1 id=\"usage\">Usage</h1>\n<h2 id=\"vect\"><code>Vec&lt;T&gt;</code></h2>\n\
+            <h2 id=\"usage-1\">Usage</h2>\n<h2>🦀</h2>\n\
+            <h2 id=\"usage-2\">Usage</h2>\n"
+        );
+    }
+
+    #[test]
+    fn anchors_and_cross_links_between_parts() {
+        let events = vec![
+            Event::Html(CowStr::from(
+                "<p><a href=\"#fn-1\">1</a> <a href=\"#top\">Top</a> \
+                <a href=\"#notes--more\">Notes</a></p>\n",
+            )),
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H2,
+                id: None,
+                classes: vec![],
+                attrs: vec![],
+            }),
+            Event::Text(CowStr::from("Notes & more")),
+            Event::End(TagEnd::Heading(HeadingLevel::H2)),
+            Event::Html(CowStr::from(
+                "<p id=\"fn-1\"><a href=\"#fn-1\">Note</a></p>\n",
+            )),
+        ];
+        let mut parts = render_parts(events.into_iter(), Some(HeadingLevel::H2), "ch.html");
+        link_anchors(&mut parts);
+        add_cross_links(&mut parts, "Chapter");
+
+        assert_eq!(
+            parts[0].body,
+            "<p><a href=\"ch-2.html#fn-1\">1</a> <a href=\"#top\">Top</a> \
+            <a href=\"ch-2.html#notes--more\">Notes</a></p>\n\
+            <p class=\"chapter-parts\"><a href=\"ch-2.html\" rel=\"next\">Notes &amp; more \u{2192}</a></p>\n"
+        );
+        assert_eq!(
+            parts[1].body,
+            "<h2 id=\"notes--more\">Notes &amp; more</h2>\n<p id=\"fn-1\"><a href=\"#fn-1\">Note</a></p>\n\
+            <p class=\"chapter-parts\"><a href=\"ch.html\" rel=\"prev\">\u{2190} Chapter</a></p>\n"
+        );
+    }
+}
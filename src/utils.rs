@@ -1,8 +1,14 @@
-use pulldown_cmark::{Options, Parser};
+use glob::{MatchOptions, Pattern};
+use html_parser::{Element, Node};
+use mime_guess::Mime;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
-use std::path::{Component, Path, PathBuf};
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR_STR};
 use url::Url;
-use urlencoding::encode;
+use urlencoding::{decode, encode};
+
+use crate::errors::Error;
 
 pub(crate) fn create_new_pull_down_parser(text: &str) -> Parser<'_> {
     let mut opts = Options::empty();
@@ -13,6 +19,65 @@ pub(crate) fn create_new_pull_down_parser(text: &str) -> Parser<'_> {
     Parser::new_ext(text, opts)
 }
 
+/// Canonicalize the path like [`std::fs::canonicalize`], but without the `\\?\` prefix
+/// it adds on Windows, so the result can be stripped of and joined with other paths.
+pub(crate) fn canonicalize<P: AsRef<Path>>(path: P) -> std::io::Result<PathBuf> {
+    std::fs::canonicalize(path).map(strip_verbatim_prefix)
+}
+
+/// The book's source folder. mdbook's `book.src` is relative to the book's `root`, unless
+/// it's an absolute path, which is used as it is.
+pub(crate) fn src_dir(root: &Path, src: &Path) -> PathBuf {
+    if src.is_absolute() {
+        src.to_path_buf()
+    } else {
+        root.join(src)
+    }
+}
+
+/// The `relative` path inside of `base` in the case of the files on disk, e.g. `img/logo.png`
+/// for `IMG/Logo.PNG`, if there is a file matching it regardless of the case.
+pub(crate) fn case_on_disk(base: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut on_disk = PathBuf::new();
+    for component in relative.components() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        // an exact match is preferred, e.g. if both 'a.png' and 'A.png' exist
+        let names: Vec<_> = std::fs::read_dir(base.join(&on_disk))
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.file_name()))
+            .collect();
+        let lowercase = name.to_string_lossy().to_lowercase();
+        let found = names.iter().find(|n| *n == name).or_else(|| {
+            names
+                .iter()
+                .find(|n| n.to_string_lossy().to_lowercase() == lowercase)
+        })?;
+        on_disk.push(found);
+    }
+    Some(on_disk)
+}
+
+/// Turn Windows "verbatim" paths like `\\?\C:\book` and `\\?\UNC\server\share` into
+/// their usual form `C:\book` and `\\server\share`. Other paths are returned as they are.
+pub(crate) fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    if !cfg!(windows) {
+        return path;
+    }
+    let Some(text) = path.to_str() else {
+        return path;
+    };
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{rest}"));
+    }
+    // only drive paths, other verbatim paths (e.g. '\\?\Volume{..}') need the prefix
+    match text.strip_prefix(r"\\?\") {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => PathBuf::from(rest),
+        _ => path,
+    }
+}
+
 // From cargo/util/paths.rs
 pub fn normalize_path(path: &Path) -> PathBuf {
     let mut components = path.components().peekable();
@@ -41,6 +106,162 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     ret
 }
 
+/// Link with the platform's path separators, so links written with `/` are handled the
+/// same way as native paths on Windows, e.g. `img/logo.png` becomes `img\logo.png`.
+pub(crate) fn native_separators(link: &str) -> String {
+    if cfg!(windows) {
+        link.replace('/', MAIN_SEPARATOR_STR)
+    } else {
+        link.to_string()
+    }
+}
+
+/// Remove the byte order mark at the start of the text, which some editors on Windows
+/// add to UTF-8 files. Returns whether there was one.
+pub(crate) fn strip_bom(text: &mut String) -> bool {
+    let bom = text.starts_with('\u{feff}');
+    if bom {
+        text.drain(..'\u{feff}'.len_utf8());
+    }
+    bom
+}
+
+/// Local link with its percent-encoded characters decoded, e.g. `my%20image.png` becomes
+/// `my image.png` like the file on disk. Links which don't decode into UTF-8 are kept.
+pub(crate) fn decode_link(link: &str) -> String {
+    decode(link)
+        .map(|decoded| decoded.into_owned())
+        .unwrap_or_else(|_| link.to_string())
+}
+
+/// The link without its leading `/`, if it's relative to the book's `src` folder like
+/// mdbook's HTML renderer treats it, e.g. `/images/logo.png`. Protocol-relative links
+/// like `//example.com/logo.png` aren't.
+pub(crate) fn root_relative_link(link: &str) -> Option<&str> {
+    link.strip_prefix('/').filter(|rest| !rest.starts_with('/'))
+}
+
+/// Windows drive-relative link like `C:img.png`, which is resolved against the current
+/// directory of the drive rather than the chapter. It's no URL either, despite the colon.
+pub(crate) fn is_drive_relative(link: &str) -> bool {
+    match link.as_bytes() {
+        [drive, b':', rest @ ..] => {
+            drive.is_ascii_alphabetic() && !matches!(rest.first(), Some(b'/' | b'\\'))
+        }
+        _ => false,
+    }
+}
+
+/// Path inside the EPUB, which always uses `/` as separator regardless of the platform.
+pub(crate) fn epub_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Options of the glob patterns, in which `*` and `?` don't match `/`.
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Compile the glob `pattern` for paths with `/` separators, in which `*` matches any
+/// characters but `/`, `**` any folders and `?` a single character but `/`.
+pub(crate) fn glob_pattern(pattern: &str) -> Result<Pattern, Error> {
+    Pattern::new(pattern)
+        .map_err(|e| Error::InvalidConfig(format!("invalid glob pattern '{pattern}': {e}")))
+}
+
+/// Whether the `path` (with `/` separators) matches the glob `pattern`.
+pub(crate) fn glob_match(pattern: &Pattern, path: &str) -> bool {
+    pattern.matches_with(path, GLOB_OPTIONS)
+}
+
+/// Whether the path is a glob pattern for [`glob_pattern`], e.g. `fonts/*.woff2`.
+pub(crate) fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+/// Files in the `root` folder matching the glob `pattern`, see [`glob_pattern`], by their
+/// paths relative to it with `/` separators in sorted order. Only the folder before the
+/// first wildcard is searched, e.g. `fonts` for `fonts/**/*.woff2`. Symlinks aren't
+/// followed and a pattern leaving the `root`, e.g. `../*.css`, is rejected.
+pub(crate) fn glob_files(root: &Path, pattern: &str) -> Result<Vec<String>, Error> {
+    let pattern = pattern.replace('\\', "/");
+    let pattern = pattern.trim_start_matches("./");
+    if pattern.starts_with('/') || pattern.split('/').any(|part| part == "..") {
+        return Err(Error::InvalidConfig(format!(
+            "the pattern '{pattern}' must be relative to the book root"
+        )));
+    }
+    let matcher = glob_pattern(pattern)?;
+    let base: PathBuf = pattern
+        .split('/')
+        .take_while(|part| !is_glob(part))
+        .collect();
+    let mut files = Vec::new();
+    let mut folders = vec![root.join(base)];
+    while let Some(folder) = folders.pop() {
+        if !folder.is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&folder)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_symlink() {
+                debug!("Symlink {:?} isn't followed for {:?}", path, pattern);
+            } else if path.is_dir() {
+                folders.push(path);
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                let relative = epub_path(relative);
+                if glob_match(&matcher, &relative) {
+                    files.push(relative);
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Split the leading YAML front matter off a chapter's content, e.g. `---\ntitle: Intro\n---`.
+/// Only flat `key: value` pairs are supported, the content is returned as it is if it
+/// doesn't start with such a block.
+pub(crate) fn split_front_matter(content: &str) -> (BTreeMap<String, String>, &str) {
+    let mut fields = BTreeMap::new();
+    let mut lines = content.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some("---") {
+        return (fields, content);
+    }
+    let mut offset = content.find('\n').map_or(content.len(), |i| i + 1);
+    for line in lines {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            return (fields, &content[offset..]);
+        }
+        if line.trim_start().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            return (BTreeMap::new(), content);
+        };
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return (BTreeMap::new(), content);
+        }
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|&q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+            .unwrap_or(value);
+        fields.insert(key.to_string(), value.to_string());
+    }
+    // the block isn't closed
+    (BTreeMap::new(), content)
+}
+
 /// Generate file name + extension from supplied remote URL.
 /// If url does not contain file extension because of 'parametrized url'
 /// then file's extension is generated as UUID4 value and file name
@@ -64,6 +285,268 @@ pub(crate) fn hash_link(url: &Url) -> String {
     format!("{:x}.{}", file_hash_value, ext)
 }
 
+/// Inline `data:` URI, e.g. a base64 encoded image. Its content is embedded in the link
+/// itself, so there is nothing to download or resolve on disk.
+pub(crate) fn is_data_uri(link: &str) -> bool {
+    link.get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// Image links of the HTML element and its children: the `src` and `srcset` candidates of
+/// `<img>` elements and the `srcset` candidates of `<source>` elements in a `<picture>`.
+pub(crate) fn image_links(element: &Element) -> Vec<String> {
+    let mut links = Vec::new();
+    if element.name == "img" || element.name == "source" {
+        let attribute = |name: &str| element.attributes.get(name).cloned().flatten();
+        if element.name == "img" {
+            links.extend(attribute("src"));
+        }
+        if let Some(srcset) = attribute("srcset") {
+            links.extend(srcset_links(&srcset).map(String::from));
+        }
+    }
+    for child in &element.children {
+        if let Node::Element(ref nested) = child {
+            links.extend(image_links(nested));
+        }
+    }
+    links
+}
+
+/// URLs of the image candidates in a `srcset`, e.g. `a.png` and `b.png` of
+/// `a.png 1x, b.png 2x`, without their width or density descriptors.
+pub(crate) fn srcset_links(srcset: &str) -> impl Iterator<Item = &str> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| candidate.split_whitespace().next())
+}
+
+/// Whether the markdown consists of a single heading only, e.g. a chapter introducing a part.
+pub(crate) fn is_heading_only(markdown: &str) -> bool {
+    let mut headings = 0;
+    let mut in_heading = false;
+    for event in create_new_pull_down_parser(markdown) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => {
+                headings += 1;
+                in_heading = true;
+            }
+            Event::End(TagEnd::Heading(_)) => in_heading = false,
+            _ if in_heading => {}
+            _ => return false,
+        }
+    }
+    headings == 1
+}
+
+/// Escape text for inserting into generated HTML.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// File name of the book's title, with the characters which aren't allowed in file names on
+/// common systems, e.g. `/` or `:`, replaced by `_`. Trailing dots and spaces, which
+/// Windows drops, are removed as well.
+pub(crate) fn file_name_of(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_end_matches(['.', ' ']);
+    if name.is_empty() {
+        "book".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// CSS friendly slug of a chapter by its path, e.g. `recipes/Pasta_Dishes.md` becomes
+/// `recipes-pasta-dishes`. Unicode symbols and emoji are transliterated into ASCII if
+/// `transliterate` is set (`Crème_🦀.md` becomes `creme-crab`), otherwise they are stripped.
+pub(crate) fn chapter_slug(chapter_path: &Path, transliterate: bool) -> String {
+    let path = chapter_path.with_extension("");
+    let mut path = path.to_string_lossy().to_string();
+    if transliterate {
+        path = deunicode::deunicode(&path);
+    }
+    let mut slug = String::new();
+    for c in path.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Anchor of a heading like mdbook gives it, e.g. `Hello, <em>World</em>!` becomes
+/// `hello-world`, so links to the headings of chapters keep working in the EPUB. Like
+/// GitHub's, the `heading`'s tags and escaped characters are stripped, whitespace becomes
+/// `-` and only alphanumeric characters, `-` and `_` are kept. Only ASCII letters are
+/// lowercased, as mdbook does.
+pub(crate) fn slugify(heading: &str) -> String {
+    let mut text = String::with_capacity(heading.len());
+    let mut rest = heading;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = match rest[start..].find('>') {
+            Some(end) => &rest[start + end + 1..],
+            None => {
+                // not a tag without its end
+                text.push_str(&rest[start..]);
+                ""
+            }
+        };
+    }
+    text.push_str(rest);
+    for escaped in ["&lt;", "&gt;", "&amp;", "&#39;", "&quot;"] {
+        text = text.replace(escaped, "");
+    }
+
+    text.trim()
+        .trim_start_matches('#')
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '_' || c == '-' => Some(c.to_ascii_lowercase()),
+            c if c.is_whitespace() => Some('-'),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Unique anchors of the headings of a chapter: a heading with the same slug as an earlier
+/// one gets a counter appended like in mdbook, so the second `Usage` becomes `usage-1`.
+#[derive(Debug, Default)]
+pub(crate) struct Slugs {
+    counts: HashMap<String, usize>,
+}
+
+impl Slugs {
+    /// The [`slugify`]'d `heading`, made unique among the ones before.
+    pub(crate) fn unique(&mut self, heading: &str) -> String {
+        let slug = slugify(heading);
+        let count = self.counts.entry(slug.clone()).or_insert(0);
+        let unique = match *count {
+            0 => slug,
+            count => format!("{slug}-{count}"),
+        };
+        *count += 1;
+        unique
+    }
+}
+
+/// MIME type of a font file by its extension. `mime_guess` doesn't know all the font types
+/// EPUB reading systems expect, so they are mapped explicitly.
+pub(crate) fn font_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "woff2" => Some("font/woff2"),
+        "woff" => Some("font/woff"),
+        "ttf" | "otf" => Some("application/font-sfnt"),
+        _ => None,
+    }
+}
+
+/// Media type of a file by its extension. Modern image formats are mapped explicitly, as
+/// older `mime_guess` versions don't know them and readers reject `octet-stream` images.
+pub(crate) fn mime_type(path: &Path) -> Mime {
+    let ext = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    let known = match ext.as_deref() {
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("jxl") => "image/jxl",
+        _ => return mime_guess::from_path(path).first_or_octet_stream(),
+    };
+    known.parse().expect("a valid media type")
+}
+
+/// Path of the JSON value at a `line` and `column` (both 1-based, as reported by
+/// `serde_json`) in a JSON-path like notation, e.g. `$.config.book.src` or `$.items[2]`.
+pub(crate) fn json_path_at(json: &str, line: usize, column: usize) -> String {
+    enum Frame {
+        Object {
+            key: Option<String>,
+            expect_key: bool,
+        },
+        Array {
+            index: usize,
+        },
+    }
+
+    let line_start: usize = json
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let offset = (line_start + column).min(json.len());
+
+    let mut stack: Vec<Frame> = Vec::new();
+    // the offset may be in the middle of a multibyte character
+    let mut chars = json
+        .char_indices()
+        .take_while(|(i, _)| *i < offset)
+        .map(|(_, c)| c);
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut string = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => string.extend(chars.next()),
+                        c => string.push(c),
+                    }
+                }
+                if let Some(Frame::Object { key, expect_key }) = stack.last_mut() {
+                    if *expect_key {
+                        *key = Some(string);
+                        *expect_key = false;
+                    }
+                }
+            }
+            '{' => stack.push(Frame::Object {
+                key: None,
+                expect_key: true,
+            }),
+            '[' => stack.push(Frame::Array { index: 0 }),
+            '}' | ']' => {
+                stack.pop();
+            }
+            ',' => match stack.last_mut() {
+                Some(Frame::Object { expect_key, .. }) => *expect_key = true,
+                Some(Frame::Array { index }) => *index += 1,
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    let mut path = String::from("$");
+    for frame in stack {
+        match frame {
+            Frame::Object { key: Some(key), .. } => {
+                path.push('.');
+                path.push_str(&key);
+            }
+            Frame::Object { key: None, .. } => {}
+            Frame::Array { index } => path.push_str(&format!("[{index}]")),
+        }
+    }
+    path
+}
+
 /// Source text is url encoded if it has a non ascii symbols. Otherwise, it is not changed.
 pub(crate) fn encode_non_ascii_symbols(source_text: &str) -> String {
     if !source_text.is_ascii() {
@@ -184,6 +667,272 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_is_data_uri() {
+        assert!(is_data_uri("data:image/png;base64,iVBORw0KGgo="));
+        assert!(is_data_uri("DATA:image/svg+xml,%3Csvg%3E"));
+        assert!(!is_data_uri("https://www.rust-lang.org/logo.svg"));
+        assert!(!is_data_uri("data.png"));
+        assert!(!is_data_uri(""));
+    }
+
+    #[test]
+    fn test_image_links() {
+        let html = "<picture>\n<source srcset=\"a.avif, https://example.com/b.webp 2x\" type=\"image/avif\">\n\
+            <img src=\"c.png\" srcset=\"c-320.png 320w,c-640.png  640w\" alt=\"C\"></picture>";
+        let dom = html_parser::Dom::parse(html).unwrap();
+        let Node::Element(ref picture) = dom.children[0] else {
+            panic!("expected an element");
+        };
+        assert_eq!(
+            image_links(picture),
+            [
+                "a.avif",
+                "https://example.com/b.webp",
+                "c.png",
+                "c-320.png",
+                "c-640.png"
+            ]
+        );
+        assert_eq!(srcset_links(" , x.png").collect::<Vec<_>>(), ["x.png"]);
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;",
+            escape_html("<b>Tom & \"Jerry\"</b>")
+        );
+    }
+
+    #[test]
+    fn test_src_dir() {
+        assert_eq!(
+            src_dir(Path::new("book"), Path::new("src")),
+            Path::new("book").join("src")
+        );
+        let absolute = std::env::temp_dir().join("src");
+        assert_eq!(src_dir(Path::new("book"), &absolute), absolute);
+    }
+
+    #[test]
+    fn test_case_on_disk() {
+        let src_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/long_book_example/src");
+        assert_eq!(
+            case_on_disk(&src_dir, Path::new("02_ADVANCED/epub_LOGO.svg")),
+            Some(Path::new("02_advanced").join("Epub_logo.svg"))
+        );
+        assert_eq!(
+            case_on_disk(&src_dir, Path::new("rust-logo.png")),
+            Some(PathBuf::from("rust-logo.png"))
+        );
+        assert_eq!(case_on_disk(&src_dir, Path::new("no-such-logo.png")), None);
+        assert_eq!(
+            case_on_disk(&src_dir, Path::new("../src/rust-logo.png")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let glob_match = |pattern, path| glob_match(&glob_pattern(pattern).unwrap(), path);
+        assert!(glob_match("appendix/*.md", "appendix/a.md"));
+        assert!(!glob_match("appendix/*.md", "appendix/sub/a.md"));
+        assert!(!glob_match("appendix/*.md", "appendix.md"));
+        assert!(glob_match("appendix/**", "appendix/sub/a.md"));
+        assert!(glob_match("**/README.md", "README.md"));
+        assert!(glob_match("**/README.md", "part/1/README.md"));
+        assert!(glob_match("chapter_?.md", "chapter_1.md"));
+        assert!(!glob_match("chapter_?.md", "chapter_10.md"));
+        assert!(glob_match("*", "chapter_1.md"));
+        assert!(glob_match("ünïcode/*", "ünïcode/é.md"));
+        assert!(matches!(
+            glob_pattern("a/***"),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_glob_files() {
+        let root = tempfile::TempDir::new().unwrap();
+        for file in [
+            "fonts/b.woff2",
+            "fonts/a.woff2",
+            "fonts/c.ttf",
+            "fonts/sub/d.woff2",
+        ] {
+            let path = root.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let files = |pattern| glob_files(root.path(), pattern).unwrap();
+        assert_eq!(files("fonts/*.woff2"), ["fonts/a.woff2", "fonts/b.woff2"]);
+        assert_eq!(files("./fonts/*.woff2"), ["fonts/a.woff2", "fonts/b.woff2"]);
+        assert_eq!(
+            files("fonts/**/*.woff2"),
+            ["fonts/a.woff2", "fonts/b.woff2", "fonts/sub/d.woff2"]
+        );
+        assert_eq!(files("**/?.ttf"), ["fonts/c.ttf"]);
+        assert!(files("missing/*.woff2").is_empty());
+        for pattern in ["../*.woff2", "fonts/../../*.woff2", "/fonts/*.woff2"] {
+            assert!(matches!(
+                glob_files(root.path(), pattern),
+                Err(Error::InvalidConfig(_))
+            ));
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.path().join("fonts"), root.path().join("linked"))
+                .unwrap();
+            std::os::unix::fs::symlink(
+                root.path().join("fonts/a.woff2"),
+                root.path().join("fonts/sub/e.woff2"),
+            )
+            .unwrap();
+            assert_eq!(files("**/d.woff2"), ["fonts/sub/d.woff2"]);
+            assert_eq!(files("fonts/sub/*.woff2"), ["fonts/sub/d.woff2"]);
+        }
+        assert!(is_glob("fonts/*.woff2"));
+        assert!(!is_glob("fonts/a.woff2"));
+    }
+
+    #[test]
+    fn test_split_front_matter() {
+        let (fields, body) = split_front_matter(
+            "---\r\ntitle: \"Intro: Part 1\"\r\n# a comment\r\nauthor: Jane\r\n---\r\n# Intro\n",
+        );
+        assert_eq!(fields["title"], "Intro: Part 1");
+        assert_eq!(fields["author"], "Jane");
+        assert_eq!(body, "# Intro\n");
+
+        // a thematic break and a paragraph, or an unclosed block
+        for content in [
+            "---\nSome text here\n---\n",
+            "---\ntitle: Intro\n",
+            "# ---\n",
+        ] {
+            let (fields, body) = split_front_matter(content);
+            assert!(fields.is_empty());
+            assert_eq!(body, content);
+        }
+    }
+
+    #[test]
+    fn test_file_name_of() {
+        assert_eq!(file_name_of("Rust: The Book"), "Rust_ The Book");
+        assert_eq!(file_name_of("A/B\\C*?\"<>|"), "A_B_C______");
+        assert_eq!(file_name_of("Tabs\tand dots..."), "Tabs_and dots");
+        assert_eq!(file_name_of(". "), "book");
+    }
+
+    #[test]
+    fn test_chapter_slug() {
+        assert_eq!("chapter-1", chapter_slug(Path::new("chapter_1.md"), true));
+        assert_eq!(
+            "recipes-pasta-dishes",
+            chapter_slug(Path::new("recipes/Pasta_Dishes.md"), true)
+        );
+        assert_eq!("sub-index", chapter_slug(Path::new("./sub/index.md"), true));
+        assert_eq!("", chapter_slug(Path::new(""), true));
+    }
+
+    #[test]
+    fn test_chapter_slug_with_emoji() {
+        let path = Path::new("Crème_brûlée 🦀.md");
+        assert_eq!("creme-brulee-crab", chapter_slug(path, true));
+        assert_eq!("cr-me-br-l-e", chapter_slug(path, false));
+        assert_eq!("", chapter_slug(Path::new("🦀.md"), false));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  ## Getting Started  "), "getting-started");
+        assert_eq!(
+            slugify("snake_case and kebab-case"),
+            "snake_case-and-kebab-case"
+        );
+        assert_eq!(slugify("Using <code>Vec&lt;T&gt;</code>"), "using-vect");
+        assert_eq!(slugify("<em>Tom</em> &amp; Jerry&#39;s"), "tom--jerrys");
+        assert_eq!(slugify("Version 1.2.3"), "version-123");
+        assert_eq!(slugify("a < b"), "a--b");
+        assert_eq!(slugify("🦀"), "");
+    }
+
+    #[test]
+    fn test_slugify_unicode() {
+        // mdbook only lowercases ASCII letters, but keeps all alphanumeric ones
+        assert_eq!(slugify("Crème Brûlée"), "crème-brûlée");
+        assert_eq!(slugify("Ärger über Öl"), "Ärger-über-Öl");
+        assert_eq!(slugify("Привет мир"), "Привет-мир");
+        assert_eq!(slugify("日本語の見出し"), "日本語の見出し");
+        assert_eq!(slugify("Rust 🦀 rocks"), "rust--rocks");
+    }
+
+    #[test]
+    fn test_unique_slugs() {
+        let mut slugs = Slugs::default();
+        assert_eq!(slugs.unique("Usage"), "usage");
+        assert_eq!(slugs.unique("Example"), "example");
+        assert_eq!(slugs.unique("usage"), "usage-1");
+        assert_eq!(slugs.unique("<em>Usage</em>"), "usage-2");
+        assert_eq!(slugs.unique("Usage!"), "usage-3");
+        assert_eq!(slugs.unique("🦀"), "");
+        assert_eq!(slugs.unique("🐙"), "-1");
+        // other chapters count on their own
+        assert_eq!(Slugs::default().unique("Usage"), "usage");
+    }
+
+    #[test]
+    fn test_slugs_match_mdbook() {
+        let headings = [
+            "Intro",
+            "Hello, <strong>World</strong>!",
+            "Intro",
+            "Über `Option&lt;T&gt;`",
+            "# Intro",
+            "Q&amp;A",
+            "  spaced   out  ",
+        ];
+        let mut slugs = Slugs::default();
+        let mut counter = HashMap::new();
+        for heading in headings {
+            assert_eq!(
+                slugs.unique(heading),
+                mdbook::utils::unique_id_from_content(heading, &mut counter),
+                "{heading}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(mime_type(Path::new("img/photo.webp")), "image/webp");
+        assert_eq!(mime_type(Path::new("img/photo.AVIF")), "image/avif");
+        assert_eq!(mime_type(Path::new("photo.jxl")), "image/jxl");
+        assert_eq!(mime_type(Path::new("photo.png")), "image/png");
+        assert_eq!(mime_type(Path::new("photo")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_font_mime_type() {
+        assert_eq!(
+            Some("font/woff2"),
+            font_mime_type(Path::new("fonts/Serif.woff2"))
+        );
+        assert_eq!(Some("font/woff"), font_mime_type(Path::new("Serif.WOFF")));
+        assert_eq!(
+            Some("application/font-sfnt"),
+            font_mime_type(Path::new("Serif.ttf"))
+        );
+        assert_eq!(
+            Some("application/font-sfnt"),
+            font_mime_type(Path::new("Serif.otf"))
+        );
+        assert_eq!(None, font_mime_type(Path::new("Serif.css")));
+        assert_eq!(None, font_mime_type(Path::new("Serif")));
+    }
+
     #[test]
     fn test_encoding_non_ascii_1() {
         let source = "..\\assets\\rust-logo.png";
@@ -255,4 +1004,123 @@ mod tests {
         let original = "https://github.com/sunface/rust-course/blob/main/assets/studyrust%E5%85%AC%E4%BC%97%E5%8F%B7.png?raw=true";
         assert_eq!(original, encoded_target);
     }
+
+    #[test]
+    fn test_json_path_at() {
+        use std::collections::HashMap;
+
+        let json =
+            "{\"book\": {\"sections\": [1, {\"a\\\"b\": null}]},\n\"config\": {\"src\": null}}";
+        assert_eq!("$.book.sections[1].a\"b", json_path_at(json, 1, 39));
+        assert_eq!("$.book.sections[0]", json_path_at(json, 1, 24));
+        assert_eq!("$", json_path_at("", 1, 0));
+        assert_eq!("$.book", json_path_at("{\"book\": \"€\"}", 1, 11));
+
+        // at the position of a real type error
+        let error = serde_json::from_str::<HashMap<String, HashMap<String, String>>>(
+            "{\"config\": {\n\"src\": null}}",
+        )
+        .unwrap_err();
+        assert_eq!(
+            "$.config.src",
+            json_path_at(
+                "{\"config\": {\n\"src\": null}}",
+                error.line(),
+                error.column()
+            )
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_without_verbatim_prefix() {
+        let path = canonicalize(env!("CARGO_MANIFEST_DIR")).unwrap();
+        assert!(path.is_absolute());
+        assert!(!path.to_string_lossy().starts_with(r"\\?\"));
+        assert_eq!(
+            PathBuf::from("/book/src"),
+            strip_verbatim_prefix(PathBuf::from("/book/src"))
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_strip_verbatim_prefix_windows() {
+        assert_eq!(
+            PathBuf::from(r"Z:\books\src"),
+            strip_verbatim_prefix(PathBuf::from(r"\\?\Z:\books\src"))
+        );
+        assert_eq!(
+            PathBuf::from(r"\\server\share\src"),
+            strip_verbatim_prefix(PathBuf::from(r"\\?\UNC\server\share\src"))
+        );
+        assert_eq!(
+            PathBuf::from(r"\\?\Volume{1234}\src"),
+            strip_verbatim_prefix(PathBuf::from(r"\\?\Volume{1234}\src"))
+        );
+        assert_eq!(
+            PathBuf::from(r"C:\books\src"),
+            strip_verbatim_prefix(PathBuf::from(r"C:\books\src"))
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_strip_prefix_of_canonical_path_windows() {
+        // src dir as configured, without the verbatim prefix
+        let src_dir =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(r"tests\long_book_example\src");
+        let asset = canonicalize(src_dir.join(r"assets\rust-logo.png")).unwrap();
+        assert_eq!(
+            Path::new(r"assets\rust-logo.png"),
+            asset.strip_prefix(&src_dir).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_is_heading_only() {
+        assert!(is_heading_only("# Part I\n"));
+        assert!(is_heading_only("\n\n## Part *II*\n\n   \n"));
+        assert!(!is_heading_only(""));
+        assert!(!is_heading_only("# Part I\n\nText"));
+        assert!(!is_heading_only("# Part I\n\n# Part II\n"));
+        assert!(!is_heading_only("Just text"));
+    }
+
+    #[test]
+    fn test_epub_path_uses_forward_slashes() {
+        let path = Path::new("assets").join("img").join("logo.png");
+        assert_eq!("assets/img/logo.png", epub_path(&path));
+        assert_eq!("logo.png", epub_path(Path::new("logo.png")));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_native_separators() {
+        assert_eq!("img/trpl20-01.png", native_separators("img/trpl20-01.png"));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_native_separators_windows() {
+        assert_eq!(r"img\trpl20-01.png", native_separators("img/trpl20-01.png"));
+        assert_eq!(
+            r"..\img\trpl20-01.png",
+            native_separators(r"../img\trpl20-01.png")
+        );
+        assert_eq!(
+            "img/trpl20-01.png",
+            epub_path(Path::new(&native_separators("img/trpl20-01.png")))
+        );
+    }
+
+    #[test]
+    fn test_is_drive_relative() {
+        assert!(is_drive_relative("C:img.png"));
+        assert!(is_drive_relative("d:assets\\img.png"));
+        assert!(!is_drive_relative("C:\\assets\\img.png"));
+        assert!(!is_drive_relative("C:/assets/img.png"));
+        assert!(!is_drive_relative("https://example.com/img.png"));
+        assert!(!is_drive_relative("img.png"));
+        assert!(!is_drive_relative("1:img.png"));
+    }
 }
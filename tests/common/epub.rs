@@ -7,25 +7,42 @@ use mdbook_epub::errors::Error;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use tempfile::TempDir;
 
 /// Convenience function for compiling the dummy book into an `EpubDoc`.
 pub fn generate_epub(epub_book_name: &str) -> Result<(EpubDoc<BufReader<File>>, PathBuf), Error> {
+    generate_epub_with_settings(epub_book_name, &[])
+}
+
+/// Same as `generate_epub()`, but overrides `[output.epub]` settings of the dummy book,
+/// e.g. `&[("search-index", json!(true))]`.
+pub fn generate_epub_with_settings(
+    epub_book_name: &str,
+    settings: &[(&str, serde_json::Value)],
+) -> Result<(EpubDoc<BufReader<File>>, PathBuf), Error> {
     debug!("generate_epub: {:?}...", epub_book_name);
-    let (ctx, _md, temp) = create_dummy_book(epub_book_name).unwrap();
+    let (mut ctx, _md, temp) = create_dummy_book(epub_book_name).unwrap();
+    for (key, value) in settings {
+        ctx.config.set(format!("output.epub.{key}"), value)?;
+    }
     debug!("temp dir = {:?}", &temp);
     mdbook_epub::generate(&ctx)?;
     let output_file = mdbook_epub::output_filename(temp.path(), &ctx.config);
     debug!("output_file = {:?}", &output_file.display());
 
-    match EpubDoc::new(&output_file) {
-        Ok(epub) => Ok((epub, output_file)),
-        Err(err) => {
-            error!("dummy book creation error = {:?}", err);
-            Err(Error::EpubDocCreate(output_file.display().to_string()))
+    let epub = open_epub(&output_file)?;
+    Ok((epub, output_file))
+}
+
+/// Open a generated book, keeping the reason why the `epub` crate couldn't read it.
+pub fn open_epub(path: &Path) -> Result<EpubDoc<BufReader<File>>, Error> {
+    EpubDoc::new(path).map_err(|err| {
+        error!("Failed to open generated book {:?} = {:?}", path, err);
+        Error::EpubDocOpen {
+            path: path.to_path_buf(),
+            source: Box::new(err),
         }
-    }
+    })
 }
 
 /// Use `MDBook::load()` to load the dummy book into memory, then set up the
@@ -56,49 +73,18 @@ pub fn epub_check(path: &Path) -> Result<(), Error> {
     init_logging();
     debug!("check epub book by path = '{}'...", &path.display());
 
-    // windows workaround
-    #[cfg(windows)]
-    let cmd = {
-        // On Windows run epubcheck via : java -jar
-        debug!("Windows environment detected");
-        let epubcheck_path =
-            std::env::var("EPUBCHECK_JAR").expect("EPUBCHECK_JAR environment variable not set");
-
-        debug!("Current directory: {:?}", std::env::current_dir().unwrap());
-        debug!("Epubcheck JAR path: {}", &epubcheck_path);
-        debug!(
-            "File exists: {}",
-            std::path::Path::new(&epubcheck_path).exists()
-        );
-
-        Command::new("java")
-            .args(&["-jar", &epubcheck_path, path.to_str().unwrap()])
-            .output()
-    };
-
-    #[cfg(not(windows))]
-    let cmd = Command::new("epubcheck").arg(path).output();
-
-    match cmd {
-        Ok(output) => {
-            // logging for debug
-            debug!("Command executed. Status: {}", output.status);
-            debug!("stdout: {}", String::from_utf8_lossy(&output.stdout));
-            debug!("stderr: {}", String::from_utf8_lossy(&output.stderr));
-
-            if output.status.success() {
-                Ok(())
-            } else {
-                let error_from_epubcheck = String::from_utf8_lossy(output.stderr.as_slice());
-                error!("Error running epubcheck: {:?}", &error_from_epubcheck);
-                Err(Error::EpubCheck(error_from_epubcheck.to_string()))
-            }
-        }
-        Err(err) => {
-            // failed to launch epubcheck, it's probably not installed
-            debug!("Failed to launch epubcheck, it's probably not installed here...");
-            Err(Error::EpubCheck(err.to_string()))
-        }
+    // run by 'java -jar' if EPUBCHECK_JAR is set, e.g. on Windows
+    let failures: Vec<String> = mdbook_epub::epub_check(path)?
+        .iter()
+        .filter(|message| message.severity.is_failure())
+        .map(ToString::to_string)
+        .collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        let error_from_epubcheck = failures.join("\n");
+        error!("Error running epubcheck: {:?}", &error_from_epubcheck);
+        Err(Error::EpubCheck(error_from_epubcheck))
     }
 }
 
@@ -110,9 +96,9 @@ pub fn output_epub_is_valid(epub_book_name: &str) {
 
     let output_file = mdbook_epub::output_filename(temp.path(), &ctx.config);
 
-    let got = EpubDoc::new(&output_file);
-
-    assert!(got.is_ok());
+    if let Err(err) = open_epub(&output_file) {
+        panic!("{err}");
+    }
 
     // also try to run epubcheck, if it's available
     epub_check(&output_file).unwrap();
@@ -1,10 +1,16 @@
 use log::debug;
+use mdbook::book::{BookItem, Chapter};
+use mdbook::MDBook;
 use serial_test::serial;
 use std::path::Path;
 mod common;
 use crate::common::epub::{create_dummy_book, output_epub_is_valid};
-use common::epub::generate_epub;
+use common::epub::{generate_epub, generate_epub_with_settings, open_epub};
 use common::init_logging::init_logging;
+use mdbook_epub::errors::Error;
+use mdbook_epub::AssetInfoKind;
+use serde_json::json;
+use std::error::Error as _;
 
 #[test]
 #[serial]
@@ -46,7 +52,7 @@ fn look_for_chapter_1_heading() {
     debug!("file = {:?}", &file);
     let content = file.unwrap();
     debug!("content = {:?}", content.len());
-    assert!(content.contains("<h1>Chapter 1</h1>"));
+    assert!(content.contains("<h1 id=\"chapter-1\">Chapter 1</h1>"));
 }
 
 #[test]
@@ -78,9 +84,10 @@ fn rendered_document_contains_all_chapter_files_and_assets() {
     ];
     let mut doc = generate_epub("long_book_example").unwrap();
     debug!("Number of internal epub resources = {:?}", doc.0.resources);
-    // number of internal epub resources for long_book_example test book
-    assert_eq!(12, doc.0.resources.len());
-    assert_eq!(3, doc.0.spine.len());
+    // number of internal epub resources for long_book_example test book, with the
+    // inline TOC document
+    assert_eq!(13, doc.0.resources.len());
+    assert_eq!(4, doc.0.spine.len());
     assert_eq!(doc.0.mdata("title").unwrap(), "LongBookExample");
     assert_eq!(doc.0.mdata("language").unwrap(), "en");
     debug!(
@@ -102,3 +109,496 @@ fn rendered_document_contains_all_chapter_files_and_assets() {
         assert!(!got.unwrap().is_empty());
     }
 }
+
+#[test]
+#[serial]
+fn search_index_is_embedded() {
+    init_logging();
+    debug!("search_index_is_embedded...");
+    let mut doc =
+        generate_epub_with_settings("long_book_example", &[("search-index", json!(true))]).unwrap();
+
+    let path = if cfg!(target_os = "linux") {
+        Path::new("OEBPS").join("search_index.json") // linux
+    } else {
+        Path::new("OEBPS/search_index.json").to_path_buf() // windows with 'forward slash' /
+    };
+    let content = doc.0.get_resource_str_by_path(path).unwrap();
+    debug!("search index = {:?}", content);
+    let index: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(index["documents"][0]["href"], "chapter_1.html");
+    assert!(index["index"]["chapter"].is_array());
+}
+
+#[test]
+#[serial]
+fn chapter_sources_are_embedded() {
+    init_logging();
+    debug!("chapter_sources_are_embedded...");
+    let mut doc =
+        generate_epub_with_settings("long_book_example", &[("embed-sources", json!(true))])
+            .unwrap();
+
+    for chapter in ["chapter_1.md", "01_getting_started/02_article.md"] {
+        let path = Path::new("OEBPS/sources").join(chapter);
+        let content = doc.0.get_resource_str_by_path(&path).unwrap();
+        let source =
+            std::fs::read_to_string(Path::new("tests/long_book_example/src").join(chapter))
+                .unwrap();
+        assert_eq!(source, content, "{path:?}");
+    }
+
+    // sources are not in the reading order
+    let mut plain_doc = generate_epub("long_book_example").unwrap();
+    assert_eq!(plain_doc.0.spine.len(), doc.0.spine.len());
+    assert!(plain_doc
+        .0
+        .get_resource_str_by_path("OEBPS/sources/chapter_1.md")
+        .is_none());
+}
+
+#[test]
+#[serial]
+fn standalone_mode_expands_includes() {
+    init_logging();
+    debug!("standalone_mode_expands_includes...");
+    let (_ctx, md, temp) = create_dummy_book("long_book_example").unwrap();
+    let ctx = mdbook_epub::standalone_render_context(&md, temp.path().to_path_buf()).unwrap();
+
+    let chapter = ctx
+        .book
+        .iter()
+        .find_map(|item| match item {
+            BookItem::Chapter(ch) if ch.name == "Chapter 1" => Some(ch),
+            _ => None,
+        })
+        .unwrap();
+    assert!(!chapter.content.contains("{{#rustdoc_include"));
+    assert!(chapter
+        .content
+        .contains("println!(\"The secret number is: {}\", secret_number);"));
+}
+
+#[test]
+#[serial]
+fn standalone_mode_runs_preprocessors_for_epub() {
+    init_logging();
+    debug!("standalone_mode_runs_preprocessors_for_epub...");
+    let (_ctx, md, temp) = create_dummy_book("long_book_example").unwrap();
+    let mut config = md.config.clone();
+    config
+        .set(
+            "preprocessor.missing.command",
+            "mdbook-no-such-preprocessor",
+        )
+        .unwrap();
+
+    // a preprocessor for other renderers is skipped like in 'mdbook build'
+    config
+        .set("preprocessor.missing.renderers", vec!["html"])
+        .unwrap();
+    let html_only = MDBook::load_with_config(&md.root, config.clone()).unwrap();
+    assert!(mdbook_epub::standalone_render_context(&html_only, temp.path().to_path_buf()).is_ok());
+
+    config
+        .set("preprocessor.missing.renderers", vec!["epub"])
+        .unwrap();
+    let for_epub = MDBook::load_with_config(&md.root, config).unwrap();
+    assert!(mdbook_epub::standalone_render_context(&for_epub, temp.path().to_path_buf()).is_err());
+}
+
+#[test]
+#[serial]
+fn malformed_epub_error_keeps_cause() {
+    init_logging();
+    debug!("malformed_epub_error_keeps_cause...");
+    let (ctx, _md, temp) = create_dummy_book("long_book_example").unwrap();
+    mdbook_epub::generate(&ctx).unwrap();
+    let output_file = mdbook_epub::output_filename(temp.path(), &ctx.config);
+    // cut off the zip's central directory
+    let content = std::fs::read(&output_file).unwrap();
+    std::fs::write(&output_file, &content[..content.len() / 2]).unwrap();
+
+    let err = open_epub(&output_file).err().unwrap();
+    assert!(matches!(err, Error::EpubDocOpen { ref path, .. } if path == &output_file));
+    assert!(err.source().is_some());
+    assert!(err.to_string().contains(&output_file.display().to_string()));
+}
+
+#[test]
+#[serial]
+fn disabled_output_is_skipped() {
+    init_logging();
+    debug!("disabled_output_is_skipped...");
+    let (mut ctx, _md, temp) = create_dummy_book("long_book_example").unwrap();
+    ctx.config.set("output.epub.enable", false).unwrap();
+
+    mdbook_epub::generate(&ctx).unwrap();
+    let output_file = mdbook_epub::output_filename(temp.path(), &ctx.config);
+    assert!(!output_file.exists());
+}
+
+#[test]
+#[serial]
+fn nav_toc_nests_sub_chapters() {
+    init_logging();
+    debug!("nav_toc_nests_sub_chapters...");
+    let mut doc = generate_epub("long_book_example").unwrap();
+
+    let path = if cfg!(target_os = "linux") {
+        Path::new("OEBPS").join("nav.xhtml") // linux
+    } else {
+        Path::new("OEBPS/nav.xhtml").to_path_buf() // windows with 'forward slash' /
+    };
+    let nav = doc.0.get_resource_str_by_path(path).unwrap();
+    debug!("nav = {:?}", nav);
+    let toc_start = nav.find("epub:type=\"toc\"").unwrap();
+    let toc = &nav[toc_start..];
+    let toc = &toc[..toc.find("</nav>").unwrap()];
+
+    // number of '<ol>' lists the TOC entry is nested in
+    let depth = |title: &str| {
+        let before = &toc[..toc.find(title).unwrap()];
+        before.matches("<ol").count() - before.matches("</ol>").count()
+    };
+    assert_eq!(depth("Chapter 1"), 1);
+    assert_eq!(depth("Getting started"), 2);
+    assert_eq!(depth("README.md tests"), 1);
+}
+
+#[test]
+#[serial]
+fn cover_image_is_recognized() {
+    init_logging();
+    debug!("cover_image_is_recognized...");
+    let (mut doc, _) = generate_epub_with_settings(
+        "long_book_example",
+        &[("cover-image", json!("assets/rust-logo.png"))],
+    )
+    .unwrap();
+
+    let opf = doc.get_resource_str_by_path("OEBPS/content.opf").unwrap();
+    assert!(opf.contains("<meta name=\"cover\""));
+    assert!(opf.contains("properties=\"cover-image\""));
+    let (cover, mime) = doc.get_cover().unwrap();
+    assert_eq!(mime, "image/png");
+    assert_eq!(
+        cover,
+        std::fs::read("tests/long_book_example/src/assets/rust-logo.png").unwrap()
+    );
+}
+
+#[test]
+#[serial]
+fn nav_has_landmarks() {
+    init_logging();
+    debug!("nav_has_landmarks...");
+    let mut doc =
+        generate_epub_with_settings("long_book_example", &[("generate-cover", json!(true))])
+            .unwrap();
+
+    let path = if cfg!(target_os = "linux") {
+        Path::new("OEBPS").join("nav.xhtml") // linux
+    } else {
+        Path::new("OEBPS/nav.xhtml").to_path_buf() // windows with 'forward slash' /
+    };
+    let nav = doc.0.get_resource_str_by_path(path).unwrap();
+    let landmarks_start = nav.find("epub:type=\"landmarks\"").unwrap();
+    let landmarks = &nav[landmarks_start..];
+    debug!("landmarks = {:?}", landmarks);
+    assert!(landmarks.contains("epub:type=\"cover\" href=\"cover.xhtml\""));
+    assert!(landmarks.contains("epub:type=\"toc\""));
+    assert!(landmarks.contains("epub:type=\"bodymatter\" href=\"chapter_1.html\""));
+}
+
+#[test]
+#[serial]
+fn bodymatter_landmark_skips_leading_part_title_and_draft() {
+    init_logging();
+    debug!("bodymatter_landmark_skips_leading_part_title_and_draft...");
+    let (mut ctx, _md, temp) = create_dummy_book("long_book_example").unwrap();
+    ctx.book.sections.insert(
+        0,
+        BookItem::Chapter(Chapter::new_draft("Coming soon", Vec::new())),
+    );
+    ctx.book
+        .sections
+        .insert(0, BookItem::PartTitle("Basics".to_string()));
+    mdbook_epub::generate(&ctx).unwrap();
+    let mut doc = open_epub(&mdbook_epub::output_filename(temp.path(), &ctx.config)).unwrap();
+
+    let nav = doc.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    let landmarks = &nav[nav.find("epub:type=\"landmarks\"").unwrap()..];
+    debug!("landmarks = {:?}", landmarks);
+    assert!(landmarks.contains("epub:type=\"bodymatter\" href=\"chapter_1.html\""));
+}
+
+#[test]
+#[serial]
+fn draft_chapter_as_placeholder_page() {
+    init_logging();
+    debug!("draft_chapter_as_placeholder_page...");
+    let (mut ctx, _md, temp) = create_dummy_book("long_book_example").unwrap();
+    ctx.book.sections.push(BookItem::Chapter(Chapter::new_draft(
+        "Appendix",
+        Vec::new(),
+    )));
+    ctx.config.set("output.epub.include-drafts", true).unwrap();
+    mdbook_epub::generate(&ctx).unwrap();
+    let mut doc = open_epub(&mdbook_epub::output_filename(temp.path(), &ctx.config)).unwrap();
+
+    let nav = doc.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert!(nav.contains("href=\"draft-1.html\">Appendix</a>"));
+    let page = doc.get_resource_str_by_path("OEBPS/draft-1.html").unwrap();
+    assert!(page.contains("<h1>Appendix</h1>\n<p>Coming soon</p>"));
+}
+
+#[test]
+#[serial]
+fn part_titles_in_nav_toc() {
+    init_logging();
+    debug!("part_titles_in_nav_toc...");
+    let mut doc =
+        generate_epub_with_settings("long_book_example", &[("part-titles-in-toc", json!(true))])
+            .unwrap();
+
+    let path = if cfg!(target_os = "linux") {
+        Path::new("OEBPS").join("nav.xhtml") // linux
+    } else {
+        Path::new("OEBPS/nav.xhtml").to_path_buf() // windows with 'forward slash' /
+    };
+    let nav = doc.0.get_resource_str_by_path(path).unwrap();
+    let toc_start = nav.find("epub:type=\"toc\"").unwrap();
+    let toc = &nav[toc_start..];
+    let toc = &toc[..toc.find("</nav>").unwrap()];
+    debug!("toc = {:?}", toc);
+
+    let depth = |title: &str| {
+        let before = &toc[..toc.find(title).unwrap()];
+        before.matches("<ol").count() - before.matches("</ol>").count()
+    };
+    assert_eq!(depth("Chapter 1"), 1);
+    // the part title links to its first chapter, which is nested under it
+    assert!(toc.contains("href=\"02_advanced/README.html\">Advanced</a>"));
+    assert_eq!(depth("Advanced"), 1);
+    assert_eq!(depth("README.md tests"), 2);
+}
+
+#[test]
+#[serial]
+fn epub_description_overrides_book_description() {
+    init_logging();
+    debug!("epub_description_overrides_book_description...");
+    let doc = generate_epub("long_book_example").unwrap();
+    // neither the book nor the EPUB have a description
+    assert!(doc.0.mdata("description").is_none());
+
+    let doc = generate_epub_with_settings(
+        "long_book_example",
+        &[("description", json!("A long book, for testing."))],
+    )
+    .unwrap();
+    assert_eq!(
+        doc.0.mdata("description").unwrap(),
+        "A long book, for testing."
+    );
+}
+
+#[test]
+#[serial]
+fn subjects_in_metadata() {
+    init_logging();
+    debug!("subjects_in_metadata...");
+    let doc = generate_epub("long_book_example").unwrap();
+    assert!(!doc.0.metadata.contains_key("subject"));
+
+    let doc = generate_epub_with_settings(
+        "long_book_example",
+        &[("subjects", json!(["Programming", "Rust", "Reference"]))],
+    )
+    .unwrap();
+    assert_eq!(
+        doc.0.metadata.get("subject").unwrap(),
+        &vec![
+            "Programming".to_string(),
+            "Rust".to_string(),
+            "Reference".to_string()
+        ]
+    );
+}
+
+#[test]
+#[serial]
+fn generator_name_in_metadata() {
+    init_logging();
+    debug!("generator_name_in_metadata...");
+    // epub-builder only writes the generator into the navigation document
+    let mut doc = generate_epub("long_book_example").unwrap();
+    let nav = doc.0.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert!(nav.contains("<meta name=\"generator\" content=\"mdbook-epub\" />"));
+
+    let mut doc = generate_epub_with_settings(
+        "long_book_example",
+        &[("generator-name", json!("Acme Publishing"))],
+    )
+    .unwrap();
+    let nav = doc.0.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert!(nav.contains("<meta name=\"generator\" content=\"Acme Publishing\" />"));
+
+    let mut doc =
+        generate_epub_with_settings("long_book_example", &[("generator-name", json!(""))]).unwrap();
+    let nav = doc.0.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert!(!nav.contains("name=\"generator\""));
+}
+
+#[test]
+#[serial]
+fn small_images_inlined_as_data_uris() {
+    init_logging();
+    debug!("small_images_inlined_as_data_uris...");
+    let (mut doc, _) = generate_epub_with_settings(
+        "long_book_example",
+        &[("inline-images-under-bytes", json!(6000))],
+    )
+    .unwrap();
+
+    // the PNG logos are 5758 bytes, the SVG one 6323 bytes
+    assert!(doc.get_resource_by_path("OEBPS/rust-logo.png").is_none());
+    assert!(doc
+        .get_resource_by_path("OEBPS/assets/rust-logo.png")
+        .is_none());
+    assert!(doc.get_resource_by_path("OEBPS/rust-logo.svg").is_some());
+    let chapter = doc
+        .get_resource_str_by_path("OEBPS/chapter_1.html")
+        .unwrap();
+    assert!(chapter.contains("<img src=\"data:image/png;base64,iVBORw0KGgo"));
+    assert!(!chapter.contains("src=\"rust-logo.png\""));
+    let readme = doc
+        .get_resource_str_by_path("OEBPS/02_advanced/README.html")
+        .unwrap();
+    assert!(readme.contains("<img src=\"data:image/svg+xml;base64,"));
+}
+
+#[test]
+#[serial]
+fn nav_has_accessible_labels() {
+    init_logging();
+    debug!("nav_has_accessible_labels...");
+    let (mut doc, _) =
+        generate_epub_with_settings("long_book_example", &[("accessible-nav", json!(true))])
+            .unwrap();
+
+    let nav = doc.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    debug!("nav = {:?}", nav);
+    assert!(nav.contains("epub:type=\"toc\""));
+    assert!(nav.contains("aria-label=\"Table of contents\""));
+    assert!(nav.contains("aria-label=\"Landmarks\""));
+    // the title is the entry's text, e.g. with its section number
+    let link = &nav[nav.find("href=\"chapter_1.html\" title=\"").unwrap()..];
+    assert!(link[..link.find('>').unwrap()].contains("Chapter 1\""));
+}
+
+#[test]
+#[serial]
+fn assets_are_discovered() {
+    init_logging();
+    debug!("assets_are_discovered...");
+    let (ctx, _md, _temp) = create_dummy_book("long_book_example").unwrap();
+
+    let assets = mdbook_epub::discover_assets(&ctx).unwrap();
+    debug!("assets = {:?}", assets);
+    let logo = assets
+        .iter()
+        .find(|asset| asset.link == "rust-logo.png")
+        .unwrap();
+    assert_eq!(logo.kind, AssetInfoKind::Local);
+    assert_eq!(logo.mime_type, "image/png");
+    assert!(logo.path.is_absolute() && logo.path.is_file());
+    assert!(assets
+        .iter()
+        .any(|asset| asset.link.ends_with("Epub_logo.svg") && asset.mime_type == "image/svg+xml"));
+    assert!(assets.windows(2).all(|pair| pair[0].link <= pair[1].link));
+}
+
+#[test]
+#[serial]
+fn rights_in_metadata() {
+    init_logging();
+    debug!("rights_in_metadata...");
+    let doc = generate_epub("long_book_example").unwrap();
+    assert!(!doc.0.metadata.contains_key("rights"));
+
+    let doc = generate_epub_with_settings("long_book_example", &[("license", json!("CC-BY-4.0"))])
+        .unwrap();
+    assert_eq!(
+        doc.0.mdata("rights").unwrap(),
+        "Creative Commons Attribution 4.0 International (CC BY 4.0)"
+    );
+}
+
+#[test]
+#[serial]
+fn task_list_checkboxes_are_valid_xhtml() {
+    init_logging();
+    debug!("task_list_checkboxes_are_valid_xhtml...");
+    let mut doc = generate_epub("long_book_example").unwrap();
+    let readme = doc
+        .0
+        .get_resource_str_by_path("OEBPS/02_advanced/README.html")
+        .unwrap();
+    assert!(readme.contains(
+        "<li><input type=\"checkbox\" disabled=\"disabled\" checked=\"checked\" /> Write the chapter</li>"
+    ));
+    assert!(readme.contains("<input type=\"checkbox\" disabled=\"disabled\" /> Check the links"));
+    assert!(!readme.contains("disabled=\"\""));
+}
+
+#[test]
+#[serial]
+fn spine_order_keeps_toc_order() {
+    init_logging();
+    debug!("spine_order_keeps_toc_order...");
+    let mut doc = generate_epub_with_settings(
+        "long_book_example",
+        &[("spine-order", json!(["02_advanced/README.md"]))],
+    )
+    .unwrap();
+
+    // the spine lists the item ids, which are looked up by their href in the manifest
+    let opf = doc.0.get_resource_str_by_path("OEBPS/content.opf").unwrap();
+    let attribute = |element: &str, name: &str| {
+        let start = element.find(&format!(" {name}=\""))? + name.len() + 3;
+        element[start..].split('"').next().map(str::to_string)
+    };
+    let spine: Vec<String> = opf
+        .split("<itemref")
+        .skip(1)
+        .filter_map(|itemref| attribute(itemref, "idref"))
+        .filter_map(|id| {
+            opf.split("<item ")
+                .find(|item| attribute(item, "id").as_deref() == Some(id.as_str()))
+                .and_then(|item| attribute(item, "href"))
+        })
+        .collect();
+    debug!("spine = {:?}", spine);
+    let position = |href: &str| spine.iter().position(|h| h == href).unwrap();
+    assert!(position("02_advanced/README.html") < position("chapter_1.html"));
+    assert!(position("chapter_1.html") < position("01_getting_started/02_article.html"));
+
+    let nav = doc.0.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    let toc = &nav[nav.find("epub:type=\"toc\"").unwrap()..];
+    assert!(toc.find("Chapter 1").unwrap() < toc.find("README.md tests").unwrap());
+}
+
+#[test]
+#[serial]
+fn toc_title_in_nav() {
+    init_logging();
+    debug!("toc_title_in_nav...");
+    let mut doc =
+        generate_epub_with_settings("long_book_example", &[("toc-title", json!("Sommaire"))])
+            .unwrap();
+    let nav = doc.0.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert!(nav.contains("Sommaire"));
+    assert!(!nav.contains("Table Of Contents"));
+}